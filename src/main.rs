@@ -9,6 +9,26 @@ mod rs_contractions;
 mod rs_benchmark;
 mod rs_brill_tagger;
 mod rs_conllu_parser;
+mod rs_ruleset_writer;
+mod rs_rule_dsl;
+mod rs_model_bundle;
+mod rs_ruleset_diff;
+mod rs_rule_analysis;
+mod rs_rule_provenance;
+mod rs_contextual_learner;
+mod rs_lexical_learner;
+mod rs_lexicon_builder;
+mod rs_rule_pruning;
+mod rs_online_learner;
+mod rs_cross_validation;
+mod rs_learning_curve;
+mod rs_active_learning;
+mod rs_weighted_lexicon;
+mod rs_binary_lexicon;
+mod rs_lexicon_layers;
+mod rs_lexicon_loader;
+mod rs_lexicon_lookup;
+mod rs_multiword;
 
 use std::collections::HashMap;
 use std::fs;
@@ -19,40 +39,70 @@ use rs_contextual_ruleset::*;
 use rs_benchmark::benchmark_pos_tagger;
 use crate::rs_lex_rulespec_id::LexicalRulespec;
 use crate::rs_lexical_ruleset::parse_lexical_ruleset;
+use crate::rs_contractions::load_contractions_from;
+use crate::rs_rule_dsl::{convert_brill_rulesets_to_dsl, parse_rule_dsl_file, split_dsl_rules};
+use crate::rs_brill_tagger::{tag_sentence_with_model, TaggerModel, TaggerSettings};
+use crate::rs_model_bundle::{create_model_bundle, load_model_bundle, save_model_bundle};
+use crate::rs_ruleset_diff::{diff_rulesets, format_merge_conflicts, format_ruleset_diff, merge_rulesets};
+use crate::rs_ruleset_writer::{format_contextual_rule, format_lexical_rule, write_contextual_ruleset, write_lexical_ruleset};
+use crate::rs_rule_analysis::{analyse_contextual_rules, format_rule_analysis};
+use crate::rs_conllu_parser::{parse_conllu_file, tagged_sentences, xpos_tagged_sentences, TagColumn};
+use crate::rs_contextual_learner::{adapt_contextual_rules, train_contextual_rules, ContextualLearnerSettings};
+use crate::rs_lexical_learner::{train_lexical_rules, LexicalLearnerSettings};
+use crate::rs_lexicon_builder::{build_lexicon, LexiconBuilder, LexiconCutoffs};
+use crate::rs_rule_pruning::{contextual_rule_contributions, format_pruning_report, lexical_rule_contributions, prune_rules};
+use crate::rs_contextual_learner::TrainingCorpus;
+use crate::rs_cross_validation::{cross_validate_conllu, format_cross_validation, format_lookup_steps, train_pipeline, Evaluation, PipelineSettings};
+use crate::rs_binary_lexicon::{compile_lexicon_file, decompile_lexicon_file, BinaryLexicon};
+use crate::rs_lexicon_layers::load_lexicon_stack;
+use crate::rs_multiword::format_multiword_entry;
+use crate::rs_lexicon_loader::{format_lexicon_report, load_lexicon, load_lexicon_from_str, LoadMode};
+use crate::rs_weighted_lexicon::{weighted_lexicon_to_string, WeightedLexicon};
+use crate::rs_active_learning::{select_for_annotation, UncertaintyWeights};
+use crate::rs_online_learner::CorrectionLog;
+use crate::rs_learning_curve::{format_learning_curve_csv, format_learning_curve_table, learning_curve_conllu, DEFAULT_FRACTIONS};
 
 type WordclassMap = HashMap<String, Vec<Wordclass>>;
 
 
 
-/// ...
+/// Function to read the lexicon at `path` (one word per line, followed by its possible tags, most likely first).
+/// `rs_lexicon_builder` describes the format in full and builds lexicons from tagged corpora. Tags may carry counts
+/// (`NN:45`, see `rs_weighted_lexicon`), which are ignored here.
 pub fn initialize_tagger(path: &str) -> Result<WordclassMap, io::Error>
 {
     // This attempts to read the file, then creates an instance of the WordclassMap.
     let contents = fs::read_to_string(path)?;
-    let mut tagger: WordclassMap = HashMap::new();
+    Ok(initialize_tagger_from_str(&contents))
+}
+
 
-    // Defining a small function to map a vector of strings to a vector of wordclass enumerations.
-    // This function invokes the above `map_pos_tag` function on each element of the original vector.
-    // E.g. the vector of strings: {"WP$", "VBZ"} would map to {`Wordclass::WPO`, `Wordlass::VBZ`}
-    fn process_tags(tags: Vec<String>) -> Vec<Option<Wordclass>> {
-        tags.into_iter().map(|tag| map_pos_tag(&tag)).collect::<Vec<Option<Wordclass>>>()
-    }
 
-    // Here, a type `LineFunction` is declared, to process a row of the lexicon into the `WordclassMap`.
-    // This function `process_line` splits the word literal (key) from its potential wordclasses (value).
-    // E.g. the string 'beans NN' maps the word to its wordclasses ('beans NN' → 'beans': [Wordclass::NN]).
-    type LineFunction = fn(&mut WordclassMap, Vec<&str>);
-    let process_line: LineFunction = |tagger, parts| {
-        let string_vector: Vec<Wordclass> = process_tags(parts[1..].iter().map(|&s| s.to_string()).collect())
-            .into_iter()
-            .filter_map(|opt| opt) // Filter out None values
-            .collect(); // Collect only Some values into a new vector
-        tagger.insert(parts[0].to_string(), string_vector);
-    };
-
-    // Lastly, each line is processed into the wordclass mapping using the `process_line` function.
-    contents.lines().for_each(|ln| process_line(&mut tagger, ln.split_whitespace().collect()));
-    Ok(tagger)
+/// Function to build a `WordclassMap` from the `contents` of a lexicon file. Lines that cannot be loaded are skipped;
+/// `rs_lexicon_loader` reports them, and can refuse a lexicon that has any.
+pub fn initialize_tagger_from_str(contents: &str) -> WordclassMap
+{
+    let (tagger, _) = load_lexicon_from_str(contents, LoadMode::Lenient).expect("lenient lexicon loading does not fail");
+    tagger
+}
+
+
+
+/// Function to write a `WordclassMap` in the lexicon file format read by `initialize_tagger`, sorted by word.
+/// Words the tagger has looked up but not found are cached as `[ANY]`; they are not part of the lexicon and are skipped.
+pub fn lexicon_to_string(tagger: &WordclassMap) -> String
+{
+    let mut words: Vec<&String> = tagger.iter()
+        .filter(|(_, tags)| tags.as_slice() != [Wordclass::ANY])
+        .map(|(word, _)| word)
+        .collect();
+    words.sort();
+    words.into_iter().map(|word| {
+        // Multiword expressions are kept under their words joined by spaces (see `rs_multiword`).
+        if word.contains(' ') { return format!("{}\n", format_multiword_entry(word, &tagger[word])); }
+        let tags: Vec<&str> = tagger[word].iter().map(wordclass_to_pos_tag).collect();
+        format!("{} {}\n", word, tags.join(" "))
+    }).collect()
 }
 
 
@@ -66,6 +116,364 @@ fn format_vec(wordclasses: &Vec<Wordclass>) -> String {
 
 fn main() -> io::Result<()> {
 
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |index: usize, default: &'static str| -> String { args.get(index).cloned().unwrap_or(default.to_string()) };
+    match args.get(1).map(|s| s.as_str()) {
+
+        // `hottnat convert-rules [contextual] [lexical] [output]` rewrites Brill's rule files in the rule DSL (see `rs_rule_dsl`).
+        Some("convert-rules") => {
+            let output_path = arg(4, "data/rules.dsl");
+            fs::write(&output_path, convert_brill_rulesets_to_dsl(&arg(2, "data/rulefile_contextual.txt"), &arg(3, "data/rulefile_lexical.txt"))?)?;
+            println!("Wrote {}", output_path);
+            return Ok(());
+        }
+
+        // `hottnat compile-rules [dsl] [contextual_output] [lexical_output]` compiles a rule DSL file back into Brill's
+        // contextual and lexical rule files, which the tagger loads.
+        Some("compile-rules") => {
+            let (contextual_output, lexical_output) = (arg(3, "data/rulefile_contextual_compiled.txt"), arg(4, "data/rulefile_lexical_compiled.txt"));
+            let (lexical, contextual) = split_dsl_rules(&parse_rule_dsl_file(&arg(2, "data/rules.dsl"))?);
+            write_contextual_ruleset(&contextual_output, &contextual)?;
+            write_lexical_ruleset(&lexical_output, &lexical)?;
+            println!("Wrote {} contextual rules to {} and {} lexical rules to {}", contextual.len(), contextual_output, lexical.len(), lexical_output);
+            return Ok(());
+        }
+
+        // `hottnat build-bundle [output]` packages the lexicon, rulesets and contractions into one model file (see `rs_model_bundle`).
+        Some("build-bundle") => {
+            let output_path = arg(2, "data/model.json");
+            let bundle = create_model_bundle("data/lexicon.txt", "data/rulefile_lexical.txt", "data/rulefile_contextual.txt",
+                                             "data/contractions.json", TaggerSettings::default())?;
+            fs::write(&output_path, bundle)?;
+            println!("Wrote {}", output_path);
+            return Ok(());
+        }
+
+        // `hottnat benchmark-bundle [bundle] [conllu]` benchmarks a model loaded from a single bundle file.
+        Some("benchmark-bundle") => {
+            let mut model = load_model_bundle(&arg(2, "data/model.json"))?;
+            benchmark_pos_tagger(&arg(3, "data/en_ewt-ud-test.conllu"), &model.lexical_ruleset, &model.contextual_ruleset, &mut model.lexicon);
+            return Ok(());
+        }
+
+        // `hottnat diff-rules <old> <new>` reports the contextual rules added, removed and reordered between two rule files.
+        Some("diff-rules") => {
+            let old = parse_contextual_rules(&arg(2, "data/rulefile_contextual.txt"))?;
+            let new = parse_contextual_rules(&arg(3, "data/rulefile_contextual.txt"))?;
+            print!("{}", format_ruleset_diff(&diff_rulesets(&old, &new), format_contextual_rule));
+            return Ok(());
+        }
+
+        // `hottnat merge-rules <base> <ours> <theirs> <output>` three-way merges two forks of a contextual rule file.
+        Some("merge-rules") => {
+            let base = parse_contextual_rules(&arg(2, "data/rulefile_contextual.txt"))?;
+            let ours = parse_contextual_rules(&arg(3, "data/rulefile_contextual.txt"))?;
+            let theirs = parse_contextual_rules(&arg(4, "data/rulefile_contextual.txt"))?;
+            let output_path = arg(5, "data/rulefile_contextual_merged.txt");
+            let merged = merge_rulesets(&base, &ours, &theirs);
+            write_contextual_ruleset(&output_path, &merged.rules)?;
+            print!("{}", format_merge_conflicts(&merged.conflicts, format_contextual_rule));
+            println!("Wrote {} ({} conflicts)", output_path, merged.conflicts.len());
+            return Ok(());
+        }
+
+        // `hottnat analyse-rules [contextual] [conllu]` reports shadowed, subsumed and self-cancelling contextual rules,
+        // using the XPOS tags of the CoNLL-U file as evidence where the rule conditions alone are inconclusive.
+        Some("analyse-rules") => {
+            let rules = parse_contextual_rules(&arg(2, "data/rulefile_contextual.txt"))?;
+            let corpus = xpos_tagged_sentences(&parse_conllu_file(&arg(3, "data/en_ewt-ud-test.conllu"))?);
+            print!("{}", format_rule_analysis(&rules, &analyse_contextual_rules(&rules, Some(&corpus))));
+            return Ok(());
+        }
+
+        // `hottnat learn-contextual [conllu] [output] [threshold]` learns contextual rules from a gold CoNLL-U file, starting
+        // from the lexicon and lexical rules in `data/`, and writes them in the contextual rule file format.
+        Some("learn-contextual") => {
+            let conllu_path = arg(2, "data/en_ewt-ud-test.conllu");
+            let output_path = arg(3, "data/rulefile_contextual_learned.txt");
+            let settings = ContextualLearnerSettings {
+                score_threshold: arg(4, "2").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                source_corpus: std::path::Path::new(&conllu_path).file_stem().map(|stem| stem.to_string_lossy().to_string()),
+                trace: true,
+                ..ContextualLearnerSettings::default()
+            };
+            let mut lexicon = initialize_tagger("data/lexicon.txt")?;
+            let rules = train_contextual_rules(&conllu_path, &parse_lexical_ruleset("data/rulefile_lexical.txt")?, &mut lexicon, &TaggerSettings::default(), &settings)?;
+            write_contextual_ruleset(&output_path, &rules)?;
+            println!("Wrote {} rules to {}", rules.len(), output_path);
+            return Ok(());
+        }
+
+        // `hottnat adapt-contextual [conllu] [output] [threshold]` adapts the model in `data/` to a new domain: the whole
+        // tagger tags a small in-domain gold CoNLL-U file, and the contextual rules learned from its remaining errors are
+        // appended to the existing contextual rules.
+        Some("adapt-contextual") => {
+            let conllu_path = arg(2, "data/en_ewt-ud-test.conllu");
+            let output_path = arg(3, "data/rulefile_contextual_adapted.txt");
+            let settings = ContextualLearnerSettings {
+                score_threshold: arg(4, "2").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                source_corpus: std::path::Path::new(&conllu_path).file_stem().map(|stem| stem.to_string_lossy().to_string()),
+                trace: true,
+                ..ContextualLearnerSettings::default()
+            };
+            let mut lexicon = initialize_tagger("data/lexicon.txt")?;
+            let rules = adapt_contextual_rules(&conllu_path, &parse_lexical_ruleset("data/rulefile_lexical.txt")?,
+                                               &parse_contextual_rules("data/rulefile_contextual.txt")?, &mut lexicon, &TaggerSettings::default(), &settings)?;
+            write_contextual_ruleset(&output_path, &rules)?;
+            println!("Wrote {} rules to {}", rules.len(), output_path);
+            return Ok(());
+        }
+
+        // `hottnat learn-lexical [conllu] [output] [threshold] [xpos|upos]` learns unknown-word rules from a gold CoNLL-U
+        // file, holding out every other sentence as pseudo-unknown words, and writes them in the lexical rule file format.
+        Some("learn-lexical") => {
+            let conllu_path = arg(2, "data/en_ewt-ud-test.conllu");
+            let output_path = arg(3, "data/rulefile_lexical_learned.txt");
+            let defaults = match arg(5, "xpos").parse::<TagColumn>()? {
+                TagColumn::Xpos => LexicalLearnerSettings::default(),
+                TagColumn::Upos => LexicalLearnerSettings::universal(),
+            };
+            let settings = LexicalLearnerSettings {
+                score_threshold: arg(4, "2").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                source_corpus: std::path::Path::new(&conllu_path).file_stem().map(|stem| stem.to_string_lossy().to_string()),
+                trace: true,
+                ..defaults
+            };
+            let rules = train_lexical_rules(&conllu_path, &TaggerSettings::default(), &settings)?;
+            write_lexical_ruleset(&output_path, &rules)?;
+            println!("Wrote {} rules to {}", rules.len(), output_path);
+            return Ok(());
+        }
+
+        // `hottnat build-lexicon [corpus] [output] [min_count] [merge_with|-] [xpos|upos]` counts the word/tag pairs of a
+        // CoNLL-U or `word/TAG` corpus and writes a lexicon, each word's tags ordered by frequency, optionally merged into an
+        // existing one.
+        Some("build-lexicon") => {
+            let output_path = arg(3, "data/lexicon_built.txt");
+            let min_count: u32 = arg(4, "1").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let cutoffs = LexiconCutoffs { min_word_count: min_count, min_tag_count: min_count };
+            let merge_with = args.get(5).map(|path| path.as_str()).filter(|path| *path != "-");
+            let lexicon = build_lexicon(&[arg(2, "data/en_ewt-ud-test.conllu")], arg(6, "xpos").parse()?, &cutoffs, merge_with)?;
+            fs::write(&output_path, lexicon_to_string(&lexicon))?;
+            println!("Wrote {} words to {}", lexicon.len(), output_path);
+            return Ok(());
+        }
+
+        // `hottnat build-weighted-lexicon [corpus] [output] [min_count] [xpos|upos]` builds a lexicon as `build-lexicon`
+        // does, keeping the count of each word/tag pair (`run VB:120 NN:45`).
+        Some("build-weighted-lexicon") => {
+            let output_path = arg(3, "data/lexicon_weighted.txt");
+            let min_count: u32 = arg(4, "1").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mut builder = LexiconBuilder::new();
+            builder.add_corpus_file(&arg(2, "data/en_ewt-ud-test.conllu"), arg(5, "xpos").parse()?)?;
+            let lexicon = builder.build_weighted(&LexiconCutoffs { min_word_count: min_count, min_tag_count: min_count });
+            fs::write(&output_path, weighted_lexicon_to_string(&lexicon))?;
+            println!("Wrote {} words to {}", lexicon.entries.len(), output_path);
+            return Ok(());
+        }
+
+        // `hottnat tag-probabilities <word> [weighted_lexicon] [tag]` prints the probability of each of a word's tags in a
+        // weighted lexicon, or of the given tag only, with the number of times the word was seen.
+        Some("tag-probabilities") => {
+            let lexicon = WeightedLexicon::from_file(&arg(3, "data/lexicon_weighted.txt"))?;
+            let word = arg(2, "");
+            let probabilities = match args.get(4) {
+                Some(tag) => {
+                    let tag = map_pos_tag(tag).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown tag '{}'", tag)))?;
+                    lexicon.tag_probability(&word, &tag).map(|probability| vec![(tag, probability)])
+                }
+                None => lexicon.probabilities(&word),
+            };
+            let Some(probabilities) = probabilities else {
+                println!("{} is not in the lexicon", word);
+                return Ok(());
+            };
+            for (tag, probability) in probabilities {
+                println!("{} {:.4}", wordclass_to_pos_tag(&tag), probability);
+            }
+            match lexicon.word_count(&word) {
+                Some(count) => println!("{} seen {} times", word, count),
+                None => println!("{} has no counts", word),
+            }
+            return Ok(());
+        }
+
+        // `hottnat check-lexicon [lexicon] [strict|lenient]` reports the lexicon's malformed lines, unknown tags, duplicate
+        // words and words left with no tags; in strict mode any of them is an error.
+        Some("check-lexicon") => {
+            let (_, report) = load_lexicon(&arg(2, "data/lexicon.txt"), arg(3, "lenient").parse()?)?;
+            print!("{}", format_lexicon_report(&report));
+            return Ok(());
+        }
+
+        // `hottnat compile-lexicon [text] [binary]` compiles a text lexicon into the memory-mappable binary format (see
+        // `rs_binary_lexicon`); `hottnat decompile-lexicon [binary] [text]` writes one back out as text.
+        Some("compile-lexicon") => {
+            let output_path = arg(3, "data/lexicon.bin");
+            let words = compile_lexicon_file(&arg(2, "data/lexicon.txt"), &output_path)?;
+            println!("Wrote {} words to {}", words, output_path);
+            return Ok(());
+        }
+        Some("decompile-lexicon") => {
+            let output_path = arg(3, "data/lexicon_decompiled.txt");
+            let words = decompile_lexicon_file(&arg(2, "data/lexicon.bin"), &output_path)?;
+            println!("Wrote {} words to {}", words, output_path);
+            return Ok(());
+        }
+
+        // `hottnat lookup <word|prefix*> [binary]` looks a word, or every word starting with a prefix, up in a binary lexicon.
+        Some("lookup") => {
+            let started = std::time::Instant::now();
+            let lexicon_path = arg(3, "data/lexicon.bin");
+            let lexicon = BinaryLexicon::open(&lexicon_path)?;
+            if lexicon.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has no words", lexicon_path)));
+            }
+            let query = arg(2, "");
+            let found = match query.strip_suffix('*') {
+                Some(prefix) => lexicon.words_with_prefix(prefix),
+                None => lexicon.get(&query).map(|tags| vec![(query.clone(), tags)]).unwrap_or_default(),
+            };
+            for (word, tags) in &found {
+                println!("{} {}", word, tags.iter().map(wordclass_to_pos_tag).collect::<Vec<_>>().join(" "));
+            }
+            println!("{} of {} words found in {:?}", found.len(), lexicon.len(), started.elapsed());
+            return Ok(());
+        }
+
+        // `hottnat tag-layered <sentence> [layer ...]` tags a sentence with the model in `data/`, its lexicon overlaid by the
+        // given layer files in order (see `rs_lexicon_layers`), and prints the layer that supplied each word's tag.
+        Some("tag-layered") => {
+            let stack = load_lexicon_stack("data/lexicon.txt", args.get(3..).unwrap_or_default())?;
+            let mut model = TaggerModel::from_files("data/lexicon.txt", "data/rulefile_lexical.txt", "data/rulefile_contextual.txt", "data/contractions.json")?;
+            model.lexicon = stack.to_wordclass_map();
+            let tagged = tag_sentence_with_model(&arg(2, ""), &mut model);
+            for ((word, tag), source) in tagged.iter().zip(stack.tag_sources(&tagged, &model.lexicon, &model.settings.lookup)) {
+                println!("{}\t{}\t{}", word, wordclass_to_pos_tag(tag), source.as_deref().unwrap_or("-"));
+            }
+            return Ok(());
+        }
+
+        // `hottnat prune-rules [dev_conllu] [contextual] [lexical] [contextual_output] [lexical_output]` measures each rule's
+        // net effect on a development CoNLL-U file, in order, and writes rulesets keeping only the rules that help. The
+        // contextual rules are measured on top of the pruned lexical rules, tagged as the pruned rule files will be.
+        Some("prune-rules") => {
+            let gold = xpos_tagged_sentences(&parse_conllu_file(&arg(2, "data/en_ewt-ud-test.conllu"))?);
+            let contextual = parse_contextual_rules(&arg(3, "data/rulefile_contextual.txt"))?;
+            let lexical = parse_lexical_ruleset(&arg(4, "data/rulefile_lexical.txt"))?;
+            let (contextual_output, lexical_output) = (arg(5, "data/rulefile_contextual_pruned.txt"), arg(6, "data/rulefile_lexical_pruned.txt"));
+
+            let tagger_settings = TaggerSettings::default();
+
+            let lexical_contributions = lexical_rule_contributions(&gold, &lexical, &mut initialize_tagger("data/lexicon.txt")?, &tagger_settings);
+            print!("Lexical rules\n{}", format_pruning_report(&lexical, &lexical_contributions, format_lexical_rule));
+            let pruned_lexical = prune_rules(&lexical, &lexical_contributions);
+            let mut corpus = TrainingCorpus::new(&gold, &pruned_lexical, &mut initialize_tagger("data/lexicon.txt")?, &tagger_settings);
+            let contextual_contributions = contextual_rule_contributions(&mut corpus, &contextual);
+            print!("Contextual rules\n{}", format_pruning_report(&contextual, &contextual_contributions, format_contextual_rule));
+
+            write_lexical_ruleset(&lexical_output, &pruned_lexical)?;
+            write_contextual_ruleset(&contextual_output, &prune_rules(&contextual, &contextual_contributions))?;
+            println!("Wrote {} and {}", lexical_output, contextual_output);
+            return Ok(());
+        }
+
+        // `hottnat cross-validate [conllu] [folds] [xpos|upos] [lookup]` trains the lexicon, lexical and contextual rules on
+        // all but one fold of a CoNLL-U file, evaluates on that fold, and reports the accuracy of each fold with the mean and
+        // variance, then the accuracy of the words each lexicon lookup step found (`lookup` is `full` or steps such as
+        // `exact,lowercase`).
+        Some("cross-validate") => {
+            let folds: usize = arg(3, "5").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            if folds < 2 { return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cross-validation needs at least 2 folds")); }
+            let mut settings = PipelineSettings::for_column(arg(4, "xpos").parse()?);
+            settings.tagger.lookup = arg(5, "exact").parse()?;
+            let results = cross_validate_conllu(&arg(2, "data/en_ewt-ud-test.conllu"), folds, &settings)?;
+            print!("{}", format_cross_validation(&results));
+            let mut total = Evaluation::default();
+            results.iter().for_each(|result| total.merge(&result.evaluation));
+            print!("{}", format_lookup_steps(&total));
+            return Ok(());
+        }
+
+        // `hottnat learning-curve [train_conllu] [test_conllu|-] [csv_output] [fractions] [xpos|upos]` trains the lexicon and
+        // rules on increasing fractions of a CoNLL-U training set, evaluates each model on the test set (or, given `-`, on
+        // every tenth training sentence held out), and prints a table and writes a CSV of accuracy against training size.
+        Some("learning-curve") => {
+            let test_path = arg(3, "-");
+            let csv_path = arg(4, "data/learning_curve.csv");
+            let fractions = match args.get(5) {
+                Some(list) => list.split(',').map(|fraction| fraction.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                None => DEFAULT_FRACTIONS.to_vec(),
+            };
+            let points = learning_curve_conllu(&arg(2, "data/en_ewt-ud-test.conllu"), Some(test_path.as_str()).filter(|path| *path != "-"),
+                                               &fractions, &PipelineSettings::for_column(arg(6, "xpos").parse()?))?;
+            print!("{}", format_learning_curve_table(&points));
+            fs::write(&csv_path, format_learning_curve_csv(&points))?;
+            println!("Wrote {}", csv_path);
+            return Ok(());
+        }
+
+        // `hottnat train-model [conllu] [output_prefix] [xpos|upos]` trains a whole model (lexicon, lexical and contextual
+        // rules) from the gold tags of one column of a CoNLL-U file, and writes it as `<prefix>lexicon.txt`,
+        // `<prefix>rulefile_lexical.txt` and `<prefix>rulefile_contextual.txt`, and as the single bundle `<prefix>model.json`.
+        Some("train-model") => {
+            let conllu_path = arg(2, "data/en_ewt-ud-test.conllu");
+            let prefix = arg(3, "data/trained_");
+            let mut settings = PipelineSettings::for_column(arg(4, "xpos").parse()?);
+            let source_corpus = std::path::Path::new(&conllu_path).file_stem().map(|stem| stem.to_string_lossy().to_string());
+            settings.lexical.source_corpus = source_corpus.clone();
+            settings.contextual.source_corpus = source_corpus;
+            let model = train_pipeline(&tagged_sentences(&parse_conllu_file(&conllu_path)?, settings.tag_column), &settings);
+            fs::write(format!("{}lexicon.txt", prefix), lexicon_to_string(&model.lexicon))?;
+            write_lexical_ruleset(&format!("{}rulefile_lexical.txt", prefix), &model.lexical_ruleset)?;
+            write_contextual_ruleset(&format!("{}rulefile_contextual.txt", prefix), &model.contextual_rules)?;
+            let bundled = TaggerModel::new(model.lexicon.clone(), model.lexical_ruleset.clone(), model.contextual_rules.clone(),
+                                           load_contractions_from("data/contractions.json")?, settings.tagger.clone());
+            save_model_bundle(&format!("{}model.json", prefix), &bundled)?;
+            println!("Wrote {} words, {} lexical and {} contextual rules to {}*", model.lexicon.len(), model.lexical_ruleset.len(),
+                     model.contextual_rules.len(), prefix);
+            return Ok(());
+        }
+
+        // `hottnat select-for-annotation [input] [output] [count]` tags unannotated sentences (plain text, one per line, or
+        // CoNLL-U) with the model in `data/`, and writes the ones the tagger is least sure about as CoNLL-U with the
+        // predicted tags filled in, most uncertain first.
+        Some("select-for-annotation") => {
+            let output_path = arg(3, "data/annotation_batch.conllu");
+            let count: usize = arg(4, "50").parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mut model = TaggerModel::from_files("data/lexicon.txt", "data/rulefile_lexical.txt", "data/rulefile_contextual.txt", "data/contractions.json")?;
+            fs::write(&output_path, select_for_annotation(&arg(2, "data/en_ewt-ud-test.conllu"), &mut model, count, &UncertaintyWeights::default())?)?;
+            println!("Wrote {}", output_path);
+            return Ok(());
+        }
+
+        // `hottnat learn-corrections [annotated_conllu] [bundle] [output]` records an annotated batch (such as one written by
+        // `select-for-annotation` and then corrected, in the column the model tags) against the model's own predictions,
+        // appends the contextual rules proposed from the corrections to the model, and writes it as a bundle.
+        Some("learn-corrections") => {
+            let bundle_path = arg(3, "data/model.json");
+            let output_path = args.get(4).cloned().unwrap_or(bundle_path.clone());
+            let mut model = load_model_bundle(&bundle_path)?;
+            let settings = model.settings.clone();
+            model.settings.trace = false;
+            let mut log = CorrectionLog::new();
+            for annotated in tagged_sentences(&parse_conllu_file(&arg(2, "data/annotation_batch.conllu"))?, model.tag_column()) {
+                log.record_annotated(&mut model, &annotated)?;
+            }
+            model.settings = settings;
+            let rules = log.propose_rules(&CorrectionLog::default_settings());
+            println!("{} corrected words in {} sentences; {} rules proposed", log.corrected_words(), log.corrections.len(), rules.len());
+            for rule in &rules {
+                println!("    {}", format_contextual_rule(rule));
+            }
+            log.accept_rules(&mut model, &rules);
+            save_model_bundle(&output_path, &model)?;
+            println!("Wrote {}", output_path);
+            return Ok(());
+        }
+        _ => {}
+    }
 
     // Parse rulesets and lexicon.
     let lexical_ruleset: Vec<LexicalRulespec> = parse_lexical_ruleset("data/rulefile_lexical.txt").unwrap();
@@ -0,0 +1,160 @@
+use std::fs::read_to_string;
+use std::io::Error;
+use crate::rs_benchmark::wordclass_to_upos;
+use crate::rs_brill_tagger::{tag_tokens_with_stats, tokenize_sentence_with, TaggerModel, TaggingStats};
+use crate::rs_conllu_parser::parse_conllu_file;
+use crate::rs_wordclass::{is_universal_tag, wordclass_to_pos_tag, Wordclass};
+
+
+/// How much each sign of uncertainty counts towards a sentence's score. Each sign is measured per word, so long sentences
+/// are not favoured just for being long.
+#[derive(Debug, Clone)]
+pub struct UncertaintyWeights {
+    /// Mean number of tags a known word could take beyond its first.
+    pub ambiguity: f64,
+    /// Rules fired per word.
+    pub rules_fired: f64,
+    /// Share of words the contextual rules flipped back and forth, plus one if they never settled.
+    pub oscillation: f64,
+    /// Share of words not in the lexicon.
+    pub unknown_words: f64,
+}
+
+impl Default for UncertaintyWeights {
+    fn default() -> Self {
+        UncertaintyWeights { ambiguity: 1.0, rules_fired: 1.0, oscillation: 2.0, unknown_words: 2.0 }
+    }
+}
+
+
+/// An unannotated sentence as the tagger tagged it, with how unsure the tagger was.
+#[derive(Debug, Clone)]
+pub struct SentenceUncertainty {
+    /// Position of the sentence in the unannotated input.
+    pub index: usize,
+    pub tagged: Vec<(String, Wordclass)>,
+    pub ambiguity: f64,
+    pub rules_fired: f64,
+    pub oscillation: f64,
+    pub unknown_words: f64,
+    pub score: f64,
+}
+
+
+/// Function to tag the tokenised `sentence` with `model` and measure how unsure the tagger was about it.
+pub fn sentence_uncertainty(index: usize, sentence: Vec<String>, model: &mut TaggerModel, weights: &UncertaintyWeights) -> SentenceUncertainty {
+    let mut stats = TaggingStats::default();
+    let (tagged, possible) = tag_tokens_with_stats(sentence, &model.lexical_ruleset, &model.contextual_ruleset, &mut model.lexicon, &model.settings, &mut stats);
+    let words = possible.len().max(1) as f64;
+    let unknown = possible.iter().filter(|(_, tags)| tags.contains(&Wordclass::ANY)).count() as f64;
+    let extra_tags: usize = possible.iter().filter(|(_, tags)| !tags.contains(&Wordclass::ANY)).map(|(_, tags)| tags.len().saturating_sub(1)).sum();
+
+    let ambiguity = extra_tags as f64 / words;
+    let rules_fired = (stats.lexical_rules_fired + stats.contextual_rules_fired) as f64 / words;
+    let oscillation = stats.oscillating_words as f64 / words + if stats.hit_iteration_limit { 1.0 } else { 0.0 };
+    let unknown_words = unknown / words;
+    let score = weights.ambiguity * ambiguity + weights.rules_fired * rules_fired + weights.oscillation * oscillation + weights.unknown_words * unknown_words;
+    SentenceUncertainty { index, tagged, ambiguity, rules_fired, oscillation, unknown_words, score }
+}
+
+
+/// Function to rank tokenised `sentences` by how unsure `model` is about them, most uncertain first. Sentences with the
+/// same score keep their input order.
+pub fn rank_by_uncertainty(sentences: Vec<Vec<String>>, model: &mut TaggerModel, weights: &UncertaintyWeights) -> Vec<SentenceUncertainty> {
+    let mut ranked: Vec<SentenceUncertainty> = sentences.into_iter().enumerate()
+        .filter(|(_, sentence)| !sentence.is_empty())
+        .map(|(index, sentence)| sentence_uncertainty(index, sentence, model, weights))
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked
+}
+
+
+/// Function to read unannotated sentences from `path`: the word forms of a CoNLL-U file if it ends in `.conllu`, otherwise
+/// one sentence of plain text per line, tokenised as the tagger tokenises it.
+pub fn read_unannotated_sentences(path: &str, model: &TaggerModel) -> Result<Vec<Vec<String>>, Error> {
+    if path.ends_with(".conllu") {
+        return Ok(parse_conllu_file(path)?.iter().map(|sentence| {
+            sentence.iter()
+                .filter(|token| !token.id.contains('-') && !token.id.contains('.'))
+                .map(|token| token.form.clone())
+                .collect()
+        }).collect());
+    }
+    Ok(read_to_string(path)?.lines().map(|line| tokenize_sentence_with(line, &model.contractions)).collect())
+}
+
+
+/// Function to write `sentences` as CoNLL-U for annotation, with the predicted tags filled in as XPOS and the UPOS they
+/// map to (or as UPOS alone, for a model that tags Universal POS). Each sentence's `sent_id` is its position in the
+/// unannotated input, and a comment records its score and the signs of uncertainty it is made of.
+pub fn format_for_annotation(sentences: &[SentenceUncertainty]) -> String {
+    let mut conllu = String::new();
+    for sentence in sentences {
+        conllu += &format!("# sent_id = {}\n", sentence.index + 1);
+        conllu += &format!("# text = {}\n", sentence.tagged.iter().map(|(word, _)| word.as_str()).collect::<Vec<_>>().join(" "));
+        conllu += &format!("# uncertainty = {:.4} (ambiguity {:.4}, rules fired {:.4}, oscillation {:.4}, unknown words {:.4})\n",
+                           sentence.score, sentence.ambiguity, sentence.rules_fired, sentence.oscillation, sentence.unknown_words);
+        for (id, (word, tag)) in sentence.tagged.iter().enumerate() {
+            let xpos = if is_universal_tag(tag) { "_" } else { wordclass_to_pos_tag(tag) };
+            conllu += &format!("{}\t{}\t_\t{:?}\t{}\t_\t_\t_\t_\t_\n", id + 1, word, wordclass_to_upos(tag), xpos);
+        }
+        conllu += "\n";
+    }
+    conllu
+}
+
+
+/// Function to rank the unannotated sentences at `input_path` by uncertainty and give the top `count` as CoNLL-U with the
+/// predicted tags filled in.
+pub fn select_for_annotation(input_path: &str, model: &mut TaggerModel, count: usize, weights: &UncertaintyWeights) -> Result<String, Error> {
+    let sentences = read_unannotated_sentences(input_path, model)?;
+    let mut ranked = rank_by_uncertainty(sentences, model, weights);
+    ranked.truncate(count);
+    Ok(format_for_annotation(&ranked))
+}
+
+
+#[test]
+fn test_rank_by_uncertainty() {
+    use std::collections::HashMap;
+    use crate::initialize_tagger_from_str;
+    use crate::rs_brill_tagger::TaggerSettings;
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let lexicon = initialize_tagger_from_str("the DT\ncat NN\nsat VBD\nrun NN VB VBP\nto TO\nI PRP\nwant VBP\n");
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG TO\n").unwrap();
+    let settings = TaggerSettings::default();
+    let mut model = TaggerModel::new(lexicon, Vec::new(), rules, HashMap::new(), settings);
+    let sentences = ["the cat sat", "I want to run", "the zorblat zonked"].iter()
+        .map(|text| text.split(' ').map(String::from).collect())
+        .collect();
+
+    // The unknown word outweighs the ambiguous word and the rule that fired on it; the plain sentence comes last.
+    let ranked = rank_by_uncertainty(sentences, &mut model, &UncertaintyWeights::default());
+    assert_eq!(ranked.iter().map(|sentence| sentence.index).collect::<Vec<_>>(), [2, 1, 0]);
+    assert_eq!((ranked[1].ambiguity, ranked[1].rules_fired), (0.5, 0.25));
+    assert_eq!(ranked[2].score, 0.0);
+
+    let conllu = format_for_annotation(&ranked[1..2]);
+    assert!(conllu.starts_with("# sent_id = 2\n# text = I want to run\n"));
+    assert!(conllu.contains("(ambiguity 0.5000, rules fired 0.2500, oscillation 0.0000, unknown words 0.0000)\n"));
+    assert!(conllu.contains("4\trun\t_\tVERB\tVB\t"));
+}
+
+
+#[test]
+fn test_oscillation_counted() {
+    use std::collections::HashMap;
+    use crate::initialize_tagger_from_str;
+    use crate::rs_brill_tagger::TaggerSettings;
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    // The two rules undo each other, so `run` flips until the passes run out.
+    let lexicon = initialize_tagger_from_str("to TO\nrun NN VB\n");
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG TO\nVB NN PREVTAG TO\n").unwrap();
+    let settings = TaggerSettings { contextual_max_iterations: 5, ..TaggerSettings::default() };
+    let mut model = TaggerModel::new(lexicon, Vec::new(), rules, HashMap::new(), settings);
+    let sentence = sentence_uncertainty(0, vec![String::from("to"), String::from("run")], &mut model, &UncertaintyWeights::default());
+    assert_eq!(sentence.oscillation, 1.5);
+}
@@ -7,7 +7,7 @@ use crate::rs_contextual_rulespec::ContextualRulespec;
 use crate::rs_lex_rulespec_id::LexicalRulespec;
 use crate::WordclassMap;
 
-/// Function to map a `Wordclass` POS tag to a `rs_conllu::UPOS` POS tag (sacrificing variety).
+/// Function to map a `Wordclass` POS tag to a `rs_conllu::UPOS` POS tag (sacrificing variety). Universal tags map to themselves.
 pub fn wordclass_to_upos(wordclass: &Wordclass) -> crate::rs_conllu_parser::UPOS {
     match wordclass {
         Wordclass::CC    => crate::rs_conllu_parser::UPOS::CCONJ,
@@ -47,6 +47,22 @@ pub fn wordclass_to_upos(wordclass: &Wordclass) -> crate::rs_conllu_parser::UPOS
         Wordclass::WPO   => crate::rs_conllu_parser::UPOS::PRON,
         Wordclass::WRB   => crate::rs_conllu_parser::UPOS::ADV,
         Wordclass::PUNC => crate::rs_conllu_parser::UPOS::PUNCT,
+        Wordclass::ADJ   => crate::rs_conllu_parser::UPOS::ADJ,
+        Wordclass::ADP   => crate::rs_conllu_parser::UPOS::ADP,
+        Wordclass::ADV   => crate::rs_conllu_parser::UPOS::ADV,
+        Wordclass::AUX   => crate::rs_conllu_parser::UPOS::AUX,
+        Wordclass::CCONJ => crate::rs_conllu_parser::UPOS::CCONJ,
+        Wordclass::DET   => crate::rs_conllu_parser::UPOS::DET,
+        Wordclass::INTJ  => crate::rs_conllu_parser::UPOS::INTJ,
+        Wordclass::NOUN  => crate::rs_conllu_parser::UPOS::NOUN,
+        Wordclass::NUM   => crate::rs_conllu_parser::UPOS::NUM,
+        Wordclass::PART  => crate::rs_conllu_parser::UPOS::PART,
+        Wordclass::PRON  => crate::rs_conllu_parser::UPOS::PRON,
+        Wordclass::PROPN => crate::rs_conllu_parser::UPOS::PROPN,
+        Wordclass::PUNCT => crate::rs_conllu_parser::UPOS::PUNCT,
+        Wordclass::SCONJ => crate::rs_conllu_parser::UPOS::SCONJ,
+        Wordclass::VERB  => crate::rs_conllu_parser::UPOS::VERB,
+        Wordclass::X     => crate::rs_conllu_parser::UPOS::X,
         Wordclass::ANY   => crate::rs_conllu_parser::UPOS::X,
     }
 }
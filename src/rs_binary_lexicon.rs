@@ -0,0 +1,282 @@
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind};
+use memmap2::Mmap;
+use crate::rs_wordclass::{map_pos_tag, wordclass_to_pos_tag, Wordclass};
+use crate::{lexicon_to_string, WordclassMap};
+
+// A compiled lexicon holds the same entries as the text lexicon in a form that is looked up in place, so it can be memory
+// mapped and used at once instead of being parsed into a `WordclassMap`. All integers are little-endian `u32`s.
+//
+//     header    magic "HTLX", version, word count, tag count,
+//               then the offsets of the tag names, the index, the word strings and the tag ids
+//     tag names each tag as a length byte and its name, in the order of the tag ids
+//     index     one (word start, tags start) record per word, sorted by word, and a final record marking the ends
+//     words     the words' UTF-8 bytes, back to back
+//     tag ids   one byte per tag, each word's tags in lexicon order
+//
+// A word's bytes and tag ids run from its record to the next record, so the index can be binary searched by word and
+// a word's tags are read without touching any other entry.
+
+const MAGIC: &[u8; 4] = b"HTLX";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 32;
+const RECORD_LEN: usize = 8;
+
+
+/// Function to compile `lexicon` into the binary lexicon format.
+pub fn compile_lexicon(lexicon: &WordclassMap) -> Vec<u8> {
+    let mut words: Vec<&String> = lexicon.keys().collect();
+    words.sort();
+
+    let mut tag_names: Vec<&'static str> = Vec::new();
+    let mut index: Vec<u8> = Vec::with_capacity((words.len() + 1) * RECORD_LEN);
+    let mut strings: Vec<u8> = Vec::new();
+    let mut tag_ids: Vec<u8> = Vec::new();
+    for word in &words {
+        index.extend((strings.len() as u32).to_le_bytes());
+        index.extend((tag_ids.len() as u32).to_le_bytes());
+        strings.extend(word.as_bytes());
+        for tag in &lexicon[*word] {
+            let name = wordclass_to_pos_tag(tag);
+            let id = tag_names.iter().position(|known| *known == name).unwrap_or_else(|| {
+                tag_names.push(name);
+                tag_names.len() - 1
+            });
+            tag_ids.push(id as u8);
+        }
+    }
+    index.extend((strings.len() as u32).to_le_bytes());
+    index.extend((tag_ids.len() as u32).to_le_bytes());
+
+    let mut names: Vec<u8> = Vec::new();
+    for name in &tag_names {
+        names.push(name.len() as u8);
+        names.extend(name.as_bytes());
+    }
+
+    let names_offset = HEADER_LEN;
+    let index_offset = names_offset + names.len();
+    let strings_offset = index_offset + index.len();
+    let tags_offset = strings_offset + strings.len();
+    let mut bytes: Vec<u8> = Vec::with_capacity(tags_offset + tag_ids.len());
+    bytes.extend(MAGIC);
+    for field in [VERSION, words.len() as u32, tag_names.len() as u32, names_offset as u32, index_offset as u32, strings_offset as u32, tags_offset as u32] {
+        bytes.extend(field.to_le_bytes());
+    }
+    bytes.extend(names);
+    bytes.extend(index);
+    bytes.extend(strings);
+    bytes.extend(tag_ids);
+    bytes
+}
+
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid binary lexicon: {}", message))
+}
+
+
+/// A compiled lexicon, looked up in place in `bytes` (a memory map of the file, or bytes read into memory).
+pub struct BinaryLexicon<B: AsRef<[u8]> = Mmap> {
+    bytes: B,
+    word_count: usize,
+    tags: Vec<Wordclass>,
+    index_offset: usize,
+    strings_offset: usize,
+    tags_offset: usize,
+}
+
+impl BinaryLexicon<Mmap> {
+    /// Function to memory map the compiled lexicon at `path`.
+    pub fn open(path: &str) -> Result<BinaryLexicon<Mmap>, Error> {
+        let file = File::open(path)?;
+        // SAFETY: the map is only read, and every offset read from it is checked by `from_bytes` before it is used. The
+        // file must not be modified while it is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        BinaryLexicon::from_bytes(mmap)
+    }
+}
+
+impl<B: AsRef<[u8]>> BinaryLexicon<B> {
+    /// Function to check the header and index of a compiled lexicon, so that lookups cannot read out of bounds.
+    pub fn from_bytes(bytes: B) -> Result<BinaryLexicon<B>, Error> {
+        let data = bytes.as_ref();
+        if data.len() < HEADER_LEN || &data[..4] != MAGIC { return Err(invalid("missing header")); }
+        let field = |index: usize| u32::from_le_bytes(data[4 + index * 4..8 + index * 4].try_into().unwrap()) as usize;
+        if field(0) != VERSION as usize { return Err(invalid("unsupported version")); }
+        let (word_count, tag_count, names_offset, index_offset, strings_offset, tags_offset) = (field(1), field(2), field(3), field(4), field(5), field(6));
+        let index_end = word_count.checked_add(1).and_then(|records| records.checked_mul(RECORD_LEN)).and_then(|len| len.checked_add(index_offset));
+        if !(names_offset <= index_offset && index_end == Some(strings_offset) && strings_offset <= tags_offset && tags_offset <= data.len()) {
+            return Err(invalid("bad section offsets"));
+        }
+
+        let mut tags = Vec::with_capacity(tag_count);
+        let mut position = names_offset;
+        for _ in 0..tag_count {
+            let len = *data.get(position).ok_or_else(|| invalid("truncated tag names"))? as usize;
+            let name = data.get(position + 1..position + 1 + len).and_then(|name| std::str::from_utf8(name).ok()).ok_or_else(|| invalid("bad tag name"))?;
+            tags.push(map_pos_tag(name).ok_or_else(|| invalid("unknown tag"))?);
+            position += 1 + len;
+        }
+
+        let lexicon = BinaryLexicon { bytes, word_count, tags, index_offset, strings_offset, tags_offset };
+        let data = lexicon.bytes.as_ref();
+        let (strings_len, tag_ids_len) = (tags_offset - strings_offset, data.len() - tags_offset);
+        let mut previous = (0, 0);
+        for record in 0..=word_count {
+            let (word_start, tags_start) = lexicon.record(record);
+            if word_start < previous.0 || tags_start < previous.1 || word_start > strings_len || tags_start > tag_ids_len {
+                return Err(invalid("bad index"));
+            }
+            previous = (word_start, tags_start);
+        }
+        if data[tags_offset..].iter().any(|id| *id as usize >= lexicon.tags.len()) { return Err(invalid("unknown tag id")); }
+        if std::str::from_utf8(&data[strings_offset..tags_offset]).is_err() { return Err(invalid("words are not UTF-8")); }
+        Ok(lexicon)
+    }
+
+    fn record(&self, record: usize) -> (usize, usize) {
+        let data = self.bytes.as_ref();
+        let start = self.index_offset + record * RECORD_LEN;
+        let read = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+        (read(start), read(start + 4))
+    }
+
+    fn word(&self, entry: usize) -> &[u8] {
+        let (start, end) = (self.record(entry).0, self.record(entry + 1).0);
+        &self.bytes.as_ref()[self.strings_offset + start..self.strings_offset + end]
+    }
+
+    fn entry_tags(&self, entry: usize) -> Vec<Wordclass> {
+        let (start, end) = (self.record(entry).1, self.record(entry + 1).1);
+        self.bytes.as_ref()[self.tags_offset + start..self.tags_offset + end].iter().map(|id| self.tags[*id as usize].clone()).collect()
+    }
+
+    /// Function to give the number of words in the lexicon.
+    pub fn len(&self) -> usize {
+        self.word_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.word_count == 0
+    }
+
+    /// Function to look up the tags of `word`, most likely first, or `None` if it is not in the lexicon.
+    pub fn get(&self, word: &str) -> Option<Vec<Wordclass>> {
+        let entry = self.first_entry_not_below(word.as_bytes());
+        (entry < self.word_count && self.word(entry) == word.as_bytes()).then(|| self.entry_tags(entry))
+    }
+
+    /// Function to give every word starting with `prefix`, in sorted order, with its tags.
+    pub fn words_with_prefix(&self, prefix: &str) -> Vec<(String, Vec<Wordclass>)> {
+        (self.first_entry_not_below(prefix.as_bytes())..self.word_count)
+            .take_while(|entry| self.word(*entry).starts_with(prefix.as_bytes()))
+            .map(|entry| (String::from_utf8_lossy(self.word(entry)).to_string(), self.entry_tags(entry)))
+            .collect()
+    }
+
+    fn first_entry_not_below(&self, key: &[u8]) -> usize {
+        let (mut low, mut high) = (0, self.word_count);
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.word(middle) < key { low = middle + 1; } else { high = middle; }
+        }
+        low
+    }
+
+    /// Function to expand the lexicon into the `WordclassMap` the tagger uses.
+    pub fn to_wordclass_map(&self) -> WordclassMap {
+        (0..self.word_count).map(|entry| (String::from_utf8_lossy(self.word(entry)).to_string(), self.entry_tags(entry))).collect()
+    }
+}
+
+
+/// Function to load the lexicon at `path` for tagging: a binary lexicon if the path ends in `.bin`, otherwise a text
+/// lexicon.
+pub fn load_lexicon_file(path: &str) -> Result<WordclassMap, Error> {
+    if path.ends_with(".bin") {
+        Ok(BinaryLexicon::open(path)?.to_wordclass_map())
+    } else {
+        crate::initialize_tagger(path)
+    }
+}
+
+
+/// Function to compile the text lexicon at `text_path` into the binary lexicon at `binary_path`.
+pub fn compile_lexicon_file(text_path: &str, binary_path: &str) -> Result<usize, Error> {
+    let lexicon = crate::initialize_tagger(text_path)?;
+    fs::write(binary_path, compile_lexicon(&lexicon))?;
+    Ok(lexicon.len())
+}
+
+
+/// Function to write the binary lexicon at `binary_path` back out as the text lexicon at `text_path`.
+pub fn decompile_lexicon_file(binary_path: &str, text_path: &str) -> Result<usize, Error> {
+    let lexicon = BinaryLexicon::open(binary_path)?;
+    fs::write(text_path, lexicon_to_string(&lexicon.to_wordclass_map()))?;
+    Ok(lexicon.len())
+}
+
+
+#[test]
+fn test_binary_lexicon_matches_text_lexicon() {
+    use crate::initialize_tagger;
+
+    let text = initialize_tagger("data/lexicon.txt").unwrap();
+    let binary = BinaryLexicon::from_bytes(compile_lexicon(&text)).unwrap();
+    assert_eq!(binary.len(), text.len());
+    for (word, tags) in &text {
+        assert_eq!(binary.get(word).as_ref(), Some(tags), "{}", word);
+    }
+    assert_eq!(binary.get("zzzznotaword"), None);
+    assert_eq!(binary.get(""), None);
+    assert_eq!(binary.to_wordclass_map(), text);
+
+    let mut expected: Vec<&String> = text.keys().filter(|word| word.starts_with("walk")).collect();
+    expected.sort();
+    let found = binary.words_with_prefix("walk");
+    assert_eq!(found.iter().map(|(word, _)| word).collect::<Vec<_>>(), expected);
+    assert_eq!(found[0].1, text["walk"]);
+    assert!(binary.words_with_prefix("zzzz").is_empty());
+}
+
+
+#[test]
+fn test_model_from_binary_lexicon() {
+    use crate::rs_brill_tagger::{tag_sentence_with_model, TaggerModel};
+
+    let binary_path = std::env::temp_dir().join(format!("hottnat_lexicon_{}.bin", std::process::id()));
+    let binary_path = binary_path.to_str().unwrap();
+    compile_lexicon_file("data/lexicon.txt", binary_path).unwrap();
+    let load = |lexicon_path: &str| TaggerModel::from_files(lexicon_path, "data/rulefile_lexical.txt",
+                                                             "data/rulefile_contextual.txt", "data/contractions.json").unwrap();
+    let mut text_model = load("data/lexicon.txt");
+    let mut binary_model = load(binary_path);
+    fs::remove_file(binary_path).unwrap();
+    assert_eq!(binary_model.lexicon, text_model.lexicon);
+    let sentence = "The actual vote is a little confusing";
+    assert_eq!(tag_sentence_with_model(sentence, &mut binary_model), tag_sentence_with_model(sentence, &mut text_model));
+}
+
+
+#[test]
+fn test_binary_lexicon_round_trip() {
+    use crate::initialize_tagger_from_str;
+
+    let contents = "walk VB NN\nwalked VBD VBN\nwater NN\n\u{e9}t\u{e9} NN\nrun NN|VB\n";
+    let lexicon = initialize_tagger_from_str(contents);
+    let bytes = compile_lexicon(&lexicon);
+    let binary = BinaryLexicon::from_bytes(bytes.as_slice()).unwrap();
+    assert_eq!(binary.get("été"), Some(vec![Wordclass::NN]));
+    assert_eq!(binary.get("run"), Some(vec![Wordclass::ANY]));
+    assert_eq!(binary.words_with_prefix("walk").len(), 2);
+    assert_eq!(lexicon_to_string(&binary.to_wordclass_map()), lexicon_to_string(&lexicon));
+
+    // Truncated or corrupted files are rejected rather than read out of bounds.
+    assert!(BinaryLexicon::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(BinaryLexicon::from_bytes(&bytes[..20]).is_err());
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() = 200;
+    assert!(BinaryLexicon::from_bytes(corrupted).is_err());
+    assert!(BinaryLexicon::from_bytes(compile_lexicon(&WordclassMap::new())).unwrap().is_empty());
+}
@@ -1,53 +1,183 @@
 use std::collections::HashMap;
-use crate::rs_contextual_ruleset::parse_contextual_ruleset;
+use std::io;
+use serde::{Deserialize, Serialize};
+use crate::rs_contextual_ruleset::{group_contextual_rules, parse_contextual_rules};
 use crate::rs_contextual_rulespec::{contextual_rule_apply, ContextualRulespec};
-use crate::rs_wordclass::Wordclass;
-use crate::{initialize_tagger, WordclassMap};
-use crate::rs_contractions::find_contractions;
+use crate::rs_conllu_parser::TagColumn;
+use crate::rs_wordclass::{is_universal_tag, Wordclass};
+use crate::WordclassMap;
+use crate::rs_binary_lexicon::load_lexicon_file;
+use crate::rs_contractions::{find_contractions, find_contractions_in, load_contractions_from};
 use crate::rs_lex_rulespec_id::LexicalRulespec;
 use crate::rs_lexical_ruleset::parse_lexical_ruleset;
+use crate::rs_lexicon_lookup::{lookup_tokens, LookupChain};
 //use crate::rs_lexical_ruleset::parse_lexical_ruleset;
 use crate::rs_lexical_rulespec::lexical_rule_apply;
+use crate::rs_ruleset_writer::{format_contextual_rule, format_lexical_rule};
 
 
-/// Function to tag a `sentence` using lexical and contextual rules.
-pub fn tag_sentence(sentence: &str, lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, wc_mapping: &mut WordclassMap) -> Vec<(String, Wordclass)> {
+/// Each word of a sentence with the tags it can take, as looked up in the lexicon.
+pub type PossibleTags = Vec<(String, Vec<Wordclass>)>;
+
+
+/// Settings controlling how many passes the tagger makes over a sentence, and whether applied rules are printed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaggerSettings {
+    pub lexical_max_iterations: i32,
+    pub contextual_max_iterations: i32,
+    /// Print each rule as it is applied.
+    pub trace: bool,
+    /// How words are looked up in the lexicon. Left out of bundles when it is the default (exact words only).
+    #[serde(default, skip_serializing_if = "LookupChain::is_default")]
+    pub lookup: LookupChain,
+}
+
+impl Default for TaggerSettings {
+    fn default() -> Self {
+        TaggerSettings { lexical_max_iterations: 1, contextual_max_iterations: 100, trace: false, lookup: LookupChain::default() }
+    }
+}
+
+
+/// Everything needed to tag text: the lexicon, both rulesets, the contraction table and the tagger settings.
+pub struct TaggerModel {
+    pub lexicon: WordclassMap,
+    pub lexical_ruleset: Vec<LexicalRulespec>,
+    /// Contextual rules in file order, kept alongside `contextual_ruleset` so the model can be written back out.
+    pub contextual_rules: Vec<ContextualRulespec>,
+    pub contextual_ruleset: HashMap<Wordclass, Vec<ContextualRulespec>>,
+    pub contractions: HashMap<String, Vec<String>>,
+    pub settings: TaggerSettings,
+}
+
+impl TaggerModel {
+    /// Function to build a model, grouping the flat `contextual_rules` by source tag for the rule engine.
+    pub fn new(lexicon: WordclassMap, lexical_ruleset: Vec<LexicalRulespec>, contextual_rules: Vec<ContextualRulespec>,
+               contractions: HashMap<String, Vec<String>>, settings: TaggerSettings) -> TaggerModel {
+        let contextual_ruleset = group_contextual_rules(contextual_rules.clone());
+        TaggerModel { lexicon, lexical_ruleset, contextual_rules, contextual_ruleset, contractions, settings }
+    }
+
+    /// Function to load a model from separate lexicon, rule and contraction files, using the default settings.
+    pub fn from_files(lexicon_path: &str, lexical_path: &str, contextual_path: &str, contractions_path: &str) -> Result<TaggerModel, io::Error> {
+        Ok(TaggerModel::new(
+            load_lexicon_file(lexicon_path)?,
+            parse_lexical_ruleset(lexical_path)?,
+            parse_contextual_rules(contextual_path)?,
+            load_contractions_from(contractions_path)?,
+            TaggerSettings::default(),
+        ))
+    }
 
-    // Tokenise sentence, and map each word to its possible tags.
+    /// Function to tell which CoNLL-U column the model's tags come from: the Universal POS column if most of the tags in
+    /// its lexicon are Universal POS tags, otherwise XPOS.
+    pub fn tag_column(&self) -> TagColumn {
+        let tags: Vec<&Wordclass> = self.lexicon.values().flatten().filter(|tag| **tag != Wordclass::ANY).collect();
+        let universal = tags.iter().filter(|tag| is_universal_tag(tag)).count();
+        if universal * 2 > tags.len() { TagColumn::Upos } else { TagColumn::Xpos }
+    }
+
+    /// Function to append `rules` to the end of the model's contextual rules, so they take effect on the next sentence
+    /// tagged.
+    pub fn append_contextual_rules(&mut self, rules: &[ContextualRulespec]) {
+        for rule in rules {
+            self.contextual_rules.push(rule.clone());
+            self.contextual_ruleset.entry(rule.source_tag.clone()).or_default().push(rule.clone());
+        }
+    }
+}
+
+
+/// Function to tag a `sentence` using lexical and contextual rules, printing each rule as it is applied.
+pub fn tag_sentence(sentence: &str, lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, wc_mapping: &mut WordclassMap) -> Vec<(String, Wordclass)> {
     let tokenised_sentence = tokenize_sentence(sentence);
-    let words_to_tags: Vec<(String, Vec<Wordclass>)> = get_possible_tags(tokenised_sentence, wc_mapping);
+    tag_tokens(tokenised_sentence, lexical_ruleset, contextual_ruleset, wc_mapping, &TaggerSettings { trace: true, ..TaggerSettings::default() })
+}
+
+
+/// Function to tag a `sentence` using everything held in a `model`, including its contraction table and settings.
+pub fn tag_sentence_with_model(sentence: &str, model: &mut TaggerModel) -> Vec<(String, Wordclass)> {
+    let tokenised_sentence = tokenize_sentence_with(sentence, &model.contractions);
+    tag_tokens(tokenised_sentence, &model.lexical_ruleset, &model.contextual_ruleset, &mut model.lexicon, &model.settings)
+}
+
+
+/// Function to tag an already tokenised sentence using lexical and contextual rules.
+fn tag_tokens(tokenised_sentence: Vec<String>, lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, wc_mapping: &mut WordclassMap, settings: &TaggerSettings) -> Vec<(String, Wordclass)> {
+
+    tag_tokens_with_possible_tags(tokenised_sentence, lexical_ruleset, contextual_ruleset, wc_mapping, settings).0
+}
+
+
+/// Function to tag already tokenised words using lexical and contextual rules, returning the tagged words along with each
+/// word's possible tags. This is the starting point for learning further rules on top of an existing model.
+pub fn tag_tokens_with_possible_tags(tokenised_sentence: Vec<String>, lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, wc_mapping: &mut WordclassMap, settings: &TaggerSettings) -> (Vec<(String, Wordclass)>, PossibleTags) {
+    tag_tokens_with_stats(tokenised_sentence, lexical_ruleset, contextual_ruleset, wc_mapping, settings, &mut TaggingStats::default())
+}
+
+
+/// What the rules did while tagging. Counts add up over every sentence tagged with the same `TaggingStats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaggingStats {
+    pub lexical_rules_fired: usize,
+    pub contextual_rules_fired: usize,
+    /// Words the contextual rules retagged back to a tag they had already held.
+    pub oscillating_words: usize,
+    /// Whether the contextual rules were still firing when `contextual_max_iterations` passes ran out.
+    pub hit_iteration_limit: bool,
+}
+
+
+/// Function to tag already tokenised words using lexical and contextual rules, returning the tagged words along with each
+/// word's possible tags, and counting what the rules did along the way in `stats`.
+pub fn tag_tokens_with_stats(tokenised_sentence: Vec<String>, lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, wc_mapping: &mut WordclassMap, settings: &TaggerSettings, stats: &mut TaggingStats) -> (Vec<(String, Wordclass)>, PossibleTags) {
+
+    // Map each word to its possible tags.
+    let words_to_tags: PossibleTags = get_possible_tags(tokenised_sentence, wc_mapping, &settings.lookup);
 
     //println!("possible tags: {:?}", words_to_tags);
     let mut sentence_to_tag: Vec<(String, Wordclass)> = retrieve_sentence_to_tag(words_to_tags.clone());
 
     // Apply lexical and contextual rules.
-    apply_lexical_rules(&mut sentence_to_tag, &lexical_ruleset, &words_to_tags, &wc_mapping, 1);
-    apply_contextual_rules(&mut sentence_to_tag, &words_to_tags, &contextual_ruleset, 100).ok_or("Max iterations reached in contextual rules");
+    stats.lexical_rules_fired += apply_lexical_rules(&mut sentence_to_tag, lexical_ruleset, &words_to_tags, wc_mapping, settings.lexical_max_iterations, settings.trace);
+    apply_contextual_rules(&mut sentence_to_tag, &words_to_tags, contextual_ruleset, settings.contextual_max_iterations, settings.trace, stats);
+
+    (sentence_to_tag, words_to_tags)
+}
 
 
-    return sentence_to_tag;
+/// Function to tag already tokenised words with the initial-state tagger only: each word's most likely tag from the lexicon,
+/// then the lexical rules. Returns the tagged words along with each word's possible tags, which bound the tags contextual
+/// rules may assign. This is the starting point the contextual rule learners train from.
+pub fn initial_state_tags(tokens: Vec<String>, lexical_ruleset: &Vec<LexicalRulespec>, wc_mapping: &mut WordclassMap, settings: &TaggerSettings) -> (Vec<(String, Wordclass)>, PossibleTags) {
+    let words_to_tags: PossibleTags = get_possible_tags(tokens, wc_mapping, &settings.lookup);
+    let mut sentence_to_tag: Vec<(String, Wordclass)> = retrieve_sentence_to_tag(words_to_tags.clone());
+    apply_lexical_rules(&mut sentence_to_tag, lexical_ruleset, &words_to_tags, wc_mapping, settings.lexical_max_iterations, settings.trace);
+    (sentence_to_tag, words_to_tags)
 }
 
 
-/// Apply lexical rules to a sentence `sentence_to_tag`
-fn apply_lexical_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, lexical_ruleset: &Vec<LexicalRulespec>, possible_tags: &Vec<(String, Vec<Wordclass>)>, wc_mapping: &WordclassMap, max_iterations: i32) {
+/// Apply lexical rules to a sentence `sentence_to_tag`, returning how many times a rule fired.
+fn apply_lexical_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, lexical_ruleset: &Vec<LexicalRulespec>, possible_tags: &PossibleTags, wc_mapping: &WordclassMap, max_iterations: i32, trace: bool) -> usize {
 
     let mut iterations = 0;
+    let mut total_applied = 0;
     loop {
         let mut rules_applied = 0;
         for (index, (word, _)) in sentence_to_tag.clone().iter().enumerate() {
             for rule in lexical_ruleset {
-                if !is_tag_contained_in_word_possible_tags(&possible_tags, &word, &rule.target_tag) { continue; }
+                if !is_tag_contained_in_word_possible_tags(possible_tags, index, &rule.target_tag) { continue; }
                 match lexical_rule_apply(sentence_to_tag, index as i32, rule, wc_mapping){
                     Some(true) => {
-                        println!("lexical rule applied");
+                        if trace { println!("lexical rule applied to '{}': {}", word, format_lexical_rule(rule)); }
                         rules_applied += 1},
                     _ => {},
                 }
             }
         }
         //if are_tags_valid(&sentence_to_tag, &possible_tags) {return Some(true)}
-        if iterations == max_iterations || rules_applied == 0 {return}
+        total_applied += rules_applied;
+        if iterations == max_iterations || rules_applied == 0 {return total_applied}
         iterations +=1;
     }
 
@@ -55,8 +185,11 @@ fn apply_lexical_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, lexical_r
 
 
 /// Continuously apply contextual rules to a sentence `sentence_to_tag` until each word's tag is in `possible_tags` or no rules were applied.
-fn apply_contextual_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, possible_tags: &Vec<(String, Vec<Wordclass>)>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, threshold:i32) -> Option<bool> {
+/// The rules fired, and the words retagged back to a tag they already held, are counted in `stats`.
+fn apply_contextual_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, possible_tags: &PossibleTags, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, threshold:i32, trace: bool, stats: &mut TaggingStats) {
     let mut iterations = 0;
+    let mut tags_held: Vec<Vec<Wordclass>> = sentence_to_tag.iter().map(|(_, tag)| vec![tag.clone()]).collect();
+    let mut oscillating = vec![false; sentence_to_tag.len()];
     loop {
         let mut rules_applied = 0;
         for (index, (word, tag)) in sentence_to_tag.clone().iter().enumerate() {
@@ -64,10 +197,11 @@ fn apply_contextual_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, possib
             match valid_rules {
                 Some(_valid_rules) => {
                     for rule in _valid_rules {
-                        if !is_tag_contained_in_word_possible_tags(possible_tags, &word, &rule.target_tag) {continue;}
+                        if !is_tag_contained_in_word_possible_tags(possible_tags, index, &rule.target_tag) {continue;}
                         match contextual_rule_apply(sentence_to_tag, index as i32, rule.clone()) {
                             Some(true) => {
-                                println!("rule applied");
+                                if trace { println!("contextual rule applied to '{}': {}", word, format_contextual_rule(rule)); }
+                                if tags_held[index].contains(&rule.target_tag) { oscillating[index] = true; } else { tags_held[index].push(rule.target_tag.clone()); }
                                 rules_applied += 1},
                             _ => {},
                         }
@@ -78,7 +212,12 @@ fn apply_contextual_rules(sentence_to_tag: &mut Vec<(String, Wordclass)>, possib
             }
         }
         //if are_tags_valid(&sentence_to_tag, &possible_tags) {return Some(true)}
-        if iterations == threshold || rules_applied == 0 {return Some(true)}
+        stats.contextual_rules_fired += rules_applied;
+        if iterations == threshold || rules_applied == 0 {
+            stats.oscillating_words += oscillating.iter().filter(|&&oscillates| oscillates).count();
+            stats.hit_iteration_limit |= rules_applied > 0;
+            return
+        }
         iterations +=1;
     }
 
@@ -94,34 +233,42 @@ fn tokenize_sentence(sentence: &str) -> Vec<String> {
 }
 
 
-/// Function to: given a tokenized `sentence` and mapping `wc_mapping`, retrieve the possible tags for each word.
-fn get_possible_tags(sentence: Vec<String>, wc_mapping: &mut WordclassMap) -> Vec<(String, Vec<Wordclass>)> {
-    sentence.iter()
-        .map(|word| (word.as_str().to_owned(), wc_mapping.entry(word.as_str().parse().unwrap()).or_insert(vec![Wordclass::ANY]).to_owned()))
+/// Function to take a `sentence`, split whitespace and tokenize any contractions found in `contractions_map`.
+pub fn tokenize_sentence_with(sentence: &str, contractions_map: &HashMap<String, Vec<String>>) -> Vec<String> {
+    sentence.split_whitespace()
+        .flat_map(|word| find_contractions_in(String::from(word), contractions_map).unwrap())
+        .collect()
+}
+
+
+/// Function to: given a tokenized `sentence` and mapping `wc_mapping`, retrieve the possible tags for each word, looking
+/// each word up with the steps of `lookup` (see `rs_lexicon_lookup`).
+fn get_possible_tags(sentence: Vec<String>, wc_mapping: &mut WordclassMap, lookup: &LookupChain) -> PossibleTags {
+    lookup_tokens(&sentence, wc_mapping, lookup).into_iter()
+        .map(|token| (token.word, token.tags))
         .collect()
 }
 
 
 /// Function to alter the first tag of the word's possible tags. Retrieve this tag for each word.
-fn retrieve_sentence_to_tag(sentence: Vec<(String, Vec<Wordclass>)>) -> Vec<(String, Wordclass)> {
+fn retrieve_sentence_to_tag(sentence: PossibleTags) -> Vec<(String, Wordclass)> {
     sentence
         .iter()
         .filter_map(|(word, tags)| tags.first().map(|first_tag| (word.to_owned(), first_tag.clone()))).collect()
 }
 
 
-/// Function to check if `possible_tag`s of a given `word` contain `target_tag`.
-fn is_tag_contained_in_word_possible_tags(possible_tags: &Vec<(String, Vec<Wordclass>)>, word: &String, target_tag: &Wordclass) -> bool {
-    let possible_tags_for_word =     possible_tags.iter()
-        .find(|(first, _)| first == word)
-        .map(|(_, second)| second).unwrap();
-
-    possible_tags_for_word.contains(target_tag)
+/// Function to check if the `possible_tags` of the word at `index` contain `target_tag`. Unknown words (possible tags
+/// `[ANY]`) may take any tag. Words are found by position, not by spelling, since the same word may have different
+/// possible tags inside and outside a multiword expression.
+fn is_tag_contained_in_word_possible_tags(possible_tags: &[(String, Vec<Wordclass>)], index: usize, target_tag: &Wordclass) -> bool {
+    let possible_tags_for_word = &possible_tags[index].1;
+    possible_tags_for_word.contains(target_tag) || possible_tags_for_word.contains(&Wordclass::ANY)
 }
 
 
 /// Function to check if all tags in a `sentence` are contained in their list of `possible_tags`.
-fn are_tags_valid(sentence: &Vec<(String, Wordclass)>, possible_tags: &Vec<(String, Vec<Wordclass>)>) -> bool {
+fn are_tags_valid(sentence: &Vec<(String, Wordclass)>, possible_tags: &PossibleTags) -> bool {
     sentence.iter().all(|(word, tag)| {
         possible_tags
             .iter()
@@ -137,6 +284,9 @@ fn are_tags_valid(sentence: &Vec<(String, Wordclass)>, possible_tags: &Vec<(Stri
 
 #[test]
 fn test_tag_sentence() {
+    use crate::rs_contextual_ruleset::parse_contextual_ruleset;
+    use crate::initialize_tagger;
+
     // To do proper tests, need to know what the sentences should be tagged as!
     // Parse rulesets and lexicon.
     let lexical_ruleset: Vec<LexicalRulespec> = parse_lexical_ruleset("data/rulefile_lexical.txt").unwrap();
@@ -144,4 +294,13 @@ fn test_tag_sentence() {
     let mut wc_mapping: WordclassMap = initialize_tagger("data/lexicon.txt").unwrap();
 
     tag_sentence("The actual vote is a little confusing", &lexical_ruleset, &contextual_ruleset, &mut wc_mapping);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_model_tag_column() {
+    use crate::initialize_tagger_from_str;
+
+    let model = |lexicon: &str| TaggerModel::new(initialize_tagger_from_str(lexicon), Vec::new(), Vec::new(), HashMap::new(), TaggerSettings::default());
+    assert_eq!(model("run VB NN\nthe DT\n").tag_column(), TagColumn::Xpos);
+    assert_eq!(model("run VERB NOUN\nthe DET\n$ SYM\n").tag_column(), TagColumn::Upos);
+}
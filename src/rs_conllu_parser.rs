@@ -1,5 +1,7 @@
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::str::FromStr;
+use crate::rs_wordclass::{map_pos_tag, Wordclass};
 
 /// Enum representing Universal POS tags.
 #[derive(Debug, Clone, PartialEq)]
@@ -78,6 +80,7 @@ pub fn parse_conllu_file(filepath: &str) -> Result<Vec<Vec<Token>>, io::Error> {
                 "SCONJ" => Some(UPOS::SCONJ),
                 "SYM" => Some(UPOS::SYM),
                 "VERB" => Some(UPOS::VERB),
+                "X" => Some(UPOS::X),
                 _ => None, // Handle any other cases
             };
 
@@ -105,3 +108,98 @@ pub fn parse_conllu_file(filepath: &str) -> Result<Vec<Vec<Token>>, io::Error> {
 
     Ok(sentences)
 }
+
+
+/// Function to turn parsed CoNLL-U `sentences` into (word, tag) pairs using their XPOS (Penn Treebank) tags.
+/// Multiword-token ranges (`1-2`) and empty nodes (`1.1`) are skipped; tags with no `Wordclass` become `Wordclass::ANY`.
+pub fn xpos_tagged_sentences(sentences: &[Vec<Token>]) -> Vec<Vec<(String, Wordclass)>> {
+    sentences.iter().map(|sentence| {
+        sentence.iter()
+            .filter(|token| !token.id.contains('-') && !token.id.contains('.'))
+            .map(|token| (token.form.clone(), token.xpos.as_deref().and_then(map_pos_tag).unwrap_or(Wordclass::ANY)))
+            .collect()
+    }).collect()
+}
+
+
+/// The column of a CoNLL-U file whose tags a model is trained on and evaluated against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagColumn {
+    /// The language-specific XPOS column, Penn Treebank tags for English.
+    #[default]
+    Xpos,
+    /// The Universal POS column.
+    Upos,
+}
+
+impl FromStr for TagColumn {
+    type Err = io::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "xpos" => Ok(TagColumn::Xpos),
+            "upos" => Ok(TagColumn::Upos),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown tag column '{}' (expected xpos or upos)", name))),
+        }
+    }
+}
+
+
+/// Function to map a `UPOS` tag to the `Wordclass` the rule engine uses for it.
+pub fn upos_to_wordclass(upos: &UPOS) -> Wordclass {
+    match upos {
+        UPOS::ADJ => Wordclass::ADJ,
+        UPOS::ADP => Wordclass::ADP,
+        UPOS::ADV => Wordclass::ADV,
+        UPOS::AUX => Wordclass::AUX,
+        UPOS::CCONJ => Wordclass::CCONJ,
+        UPOS::DET => Wordclass::DET,
+        UPOS::INTJ => Wordclass::INTJ,
+        UPOS::NOUN => Wordclass::NOUN,
+        UPOS::NUM => Wordclass::NUM,
+        UPOS::PART => Wordclass::PART,
+        UPOS::PRON => Wordclass::PRON,
+        UPOS::PROPN => Wordclass::PROPN,
+        UPOS::PUNCT => Wordclass::PUNCT,
+        UPOS::SCONJ => Wordclass::SCONJ,
+        UPOS::SYM => Wordclass::SYM,
+        UPOS::VERB => Wordclass::VERB,
+        UPOS::X => Wordclass::X,
+    }
+}
+
+
+/// Function to turn parsed CoNLL-U `sentences` into (word, tag) pairs using their UPOS tags. Multiword-token ranges and
+/// empty nodes are skipped as in `xpos_tagged_sentences`; missing tags become `Wordclass::ANY`.
+pub fn upos_tagged_sentences(sentences: &[Vec<Token>]) -> Vec<Vec<(String, Wordclass)>> {
+    sentences.iter().map(|sentence| {
+        sentence.iter()
+            .filter(|token| !token.id.contains('-') && !token.id.contains('.'))
+            .map(|token| (token.form.clone(), token.upos.as_ref().map(upos_to_wordclass).unwrap_or(Wordclass::ANY)))
+            .collect()
+    }).collect()
+}
+
+
+/// Function to turn parsed CoNLL-U `sentences` into (word, tag) pairs using the tags of `column`.
+pub fn tagged_sentences(sentences: &[Vec<Token>], column: TagColumn) -> Vec<Vec<(String, Wordclass)>> {
+    match column {
+        TagColumn::Xpos => xpos_tagged_sentences(sentences),
+        TagColumn::Upos => upos_tagged_sentences(sentences),
+    }
+}
+
+
+#[test]
+fn test_upos_tagged_sentences() {
+    let sentences = parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap();
+    let upos = tagged_sentences(&sentences, TagColumn::Upos);
+    let xpos = tagged_sentences(&sentences, TagColumn::Xpos);
+    assert_eq!(upos.len(), xpos.len());
+    assert!(upos.iter().zip(&xpos).all(|(u, x)| u.len() == x.len()));
+
+    // `IN` covers both adpositions and subordinating conjunctions; the UPOS column tells them apart.
+    let because = upos.iter().flatten().zip(xpos.iter().flatten()).find(|((form, _), _)| form == "because").unwrap();
+    assert_eq!((&because.0.1, &because.1.1), (&Wordclass::SCONJ, &Wordclass::IN));
+    assert!(upos.iter().flatten().all(|(_, tag)| *tag == Wordclass::ANY || crate::rs_wordclass::is_universal_tag(tag) || *tag == Wordclass::SYM));
+}
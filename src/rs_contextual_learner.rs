@@ -0,0 +1,554 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::io::Error;
+use crate::rs_brill_tagger::{initial_state_tags, tag_tokens_with_possible_tags, PossibleTags, TaggerSettings};
+use crate::rs_conllu_parser::{parse_conllu_file, tagged_sentences, TagColumn};
+use crate::rs_contextual_ruleset::group_contextual_rules;
+use crate::rs_contextual_rulespec::{rule_window_alternatives, template_slots, window_alternatives_hold, ContextualRulespec, WindowAlternatives};
+use crate::rs_lex_rulespec_id::LexicalRulespec;
+use crate::rs_rule_provenance::RuleProvenance;
+use crate::rs_rulespec_id::{RulespecID, RULESPEC_IDS};
+use crate::rs_ruleset_writer::format_contextual_rule;
+use crate::rs_wordclass::{wordclass_to_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+
+/// Version recorded in the provenance of every rule the learners produce.
+pub const LEARNER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+
+/// Settings for learning contextual rules.
+#[derive(Debug, Clone)]
+pub struct ContextualLearnerSettings {
+    /// Learning stops once the best rule's score (errors fixed minus errors introduced) falls below this.
+    pub score_threshold: i64,
+    /// Learning stops after this many rules, if set.
+    pub max_rules: Option<usize>,
+    /// Rules that would break more correctly tagged words than this are never chosen, if set.
+    pub max_errors_introduced: Option<u32>,
+    /// The templates rules are instantiated from.
+    pub templates: Vec<RulespecID>,
+    /// Name of the training corpus, recorded in each rule's provenance.
+    pub source_corpus: Option<String>,
+    /// The CoNLL-U column whose tags are learned.
+    pub tag_column: TagColumn,
+    /// Print each rule as it is learned, and the errors before and after learning.
+    pub trace: bool,
+}
+
+impl Default for ContextualLearnerSettings {
+    fn default() -> Self {
+        ContextualLearnerSettings {
+            score_threshold: 2,
+            max_rules: None,
+            max_errors_introduced: None,
+            // Generalised `CONJUNCTION` rules have no fixed shape to instantiate, so only Brill's templates are searched.
+            templates: RULESPEC_IDS.iter().filter(|id| **id != RulespecID::CONJUNCTION).cloned().collect(),
+            source_corpus: None,
+            tag_column: TagColumn::Xpos,
+            trace: false,
+        }
+    }
+}
+
+
+/// A gold-tagged corpus along with the tags the tagger currently assigns. Learning starts from the initial-state tags and
+/// updates `sentences` as each learned rule is applied.
+#[derive(Debug, Clone)]
+pub struct TrainingCorpus {
+    /// Each word with its current tag.
+    pub sentences: Vec<Vec<(String, Wordclass)>>,
+    /// The gold tag of each word. Words whose gold tag has no `Wordclass` hold `Wordclass::ANY` and are never scored.
+    pub gold: Vec<Vec<Wordclass>>,
+    /// The possible tags of each word (from the lexicon); a rule can only retag a word to one of these. Unknown words
+    /// have `[ANY]` and may take any tag.
+    pub possible: Vec<Vec<Vec<Wordclass>>>,
+    /// The gold tags seen in the corpus, which are the tags an unknown word could be retagged to.
+    pub tagset: Vec<Wordclass>,
+}
+
+impl TrainingCorpus {
+    /// Function to build a training corpus from `gold_sentences`, tagging them with the initial-state tagger (the
+    /// `lexicon` and `lexical_ruleset`) run with `tagger_settings`, which should be the settings the model will tag with.
+    pub fn new(gold_sentences: &[Vec<(String, Wordclass)>], lexical_ruleset: &Vec<LexicalRulespec>, lexicon: &mut WordclassMap, tagger_settings: &TaggerSettings) -> TrainingCorpus {
+        TrainingCorpus::from_tagger(gold_sentences, |tokens| initial_state_tags(tokens, lexical_ruleset, lexicon, tagger_settings))
+    }
+
+    /// Function to build a training corpus from `gold_sentences`, tagging them with a whole existing model (the `lexicon`,
+    /// `lexical_ruleset` and `contextual_ruleset`, run with `tagger_settings`), so that the rules learned correct what that
+    /// model still gets wrong.
+    pub fn from_model(gold_sentences: &[Vec<(String, Wordclass)>], lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &HashMap<Wordclass, Vec<ContextualRulespec>>, lexicon: &mut WordclassMap, tagger_settings: &TaggerSettings) -> TrainingCorpus {
+        TrainingCorpus::from_tagger(gold_sentences, |tokens| tag_tokens_with_possible_tags(tokens, lexical_ruleset, contextual_ruleset, lexicon, tagger_settings))
+    }
+
+    /// Function to build a training corpus from `gold_sentences`, using `tag` to give each sentence's starting tags and
+    /// its words' possible tags.
+    pub fn from_tagger<F>(gold_sentences: &[Vec<(String, Wordclass)>], mut tag: F) -> TrainingCorpus
+    where F: FnMut(Vec<String>) -> (Vec<(String, Wordclass)>, PossibleTags) {
+        let mut corpus = TrainingCorpus { sentences: Vec::new(), gold: Vec::new(), possible: Vec::new(), tagset: Vec::new() };
+        for gold_sentence in gold_sentences {
+            let tokens: Vec<String> = gold_sentence.iter().map(|(word, _)| word.clone()).collect();
+            let (tagged, possible) = tag(tokens);
+            corpus.sentences.push(tagged);
+            corpus.gold.push(gold_sentence.iter().map(|(_, tag)| tag.clone()).collect());
+            corpus.possible.push(possible.into_iter().map(|(_, tags)| tags).collect());
+        }
+        for tag in corpus.gold.iter().flatten() {
+            if *tag != Wordclass::ANY && !corpus.tagset.contains(tag) { corpus.tagset.push(tag.clone()); }
+        }
+        corpus
+    }
+
+    /// Function to count the scored words whose current tag differs from their gold tag.
+    pub fn errors(&self) -> usize {
+        self.positions().filter(|&(s, i)| self.is_scored(s, i) && self.sentences[s][i].1 != self.gold[s][i]).count()
+    }
+
+    /// Function to count the words that are scored (their gold tag is known).
+    pub fn scored_words(&self) -> usize {
+        self.positions().filter(|&(s, i)| self.is_scored(s, i)).count()
+    }
+
+    /// Function to iterate over every (sentence, word) position of the corpus.
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.sentences.iter().enumerate().flat_map(|(s, sentence)| (0..sentence.len()).map(move |i| (s, i)))
+    }
+
+    fn is_scored(&self, sentence: usize, index: usize) -> bool {
+        self.gold[sentence][index] != Wordclass::ANY
+    }
+
+    /// Function to check whether the word at `index` of `sentence` may be retagged to `tag`, as the tagger allows.
+    pub fn can_take(&self, sentence: usize, index: usize, tag: &Wordclass) -> bool {
+        let possible = &self.possible[sentence][index];
+        possible.contains(tag) || possible.contains(&Wordclass::ANY)
+    }
+
+    /// Function to list the tags the word at `index` of `sentence` may be retagged to.
+    fn retag_options(&self, sentence: usize, index: usize) -> &[Wordclass] {
+        let possible = &self.possible[sentence][index];
+        if possible.contains(&Wordclass::ANY) { &self.tagset } else { possible }
+    }
+
+    /// Function to check whether `rule` would retag the word at `index` of `sentence`, as the tagger does: the current tag
+    /// is the rule's source, the target is a possible tag of the word, and the conditions hold.
+    pub fn rule_fires(&self, rule: &ContextualRulespec, alternatives: &WindowAlternatives, sentence: usize, index: usize) -> bool {
+        self.sentences[sentence][index].1 == rule.source_tag
+            && self.can_take(sentence, index, &rule.target_tag)
+            && window_alternatives_hold(&self.sentences[sentence], index as i32, alternatives)
+    }
+
+    /// Function to apply `rule` to every sentence, left to right with each change visible to later words (as the tagger
+    /// does). Returns the positions that were retagged.
+    pub fn apply_rule(&mut self, rule: &ContextualRulespec) -> Vec<(usize, usize)> {
+        let Some(alternatives) = rule_window_alternatives(rule) else { return Vec::new() };
+        let mut changed: Vec<(usize, usize)> = Vec::new();
+        for sentence in 0..self.sentences.len() {
+            for index in 0..self.sentences[sentence].len() {
+                if self.rule_fires(rule, &alternatives, sentence, index) {
+                    self.sentences[sentence][index].1 = rule.target_tag.clone();
+                    changed.push((sentence, index));
+                }
+            }
+        }
+        changed
+    }
+}
+
+
+/// Function to list the parameter lists `template` can take so that it holds at `index` of `sentence`: one per choice of
+/// offset for each parameter (e.g. both the previous and the one-before-previous tag for `PREV1OR2TAG`). Offsets outside
+/// the sentence, and tags with no single Penn tag, give no instance.
+pub fn instantiate_template(template: &RulespecID, sentence: &[(String, Wordclass)], index: usize) -> Vec<Vec<String>> {
+    let mut instances: Vec<Vec<String>> = vec![Vec::new()];
+    for (is_word, offsets) in template_slots(template) {
+        let values: Vec<String> = offsets.iter().filter_map(|offset| {
+            let (word, tag) = sentence.get(usize::try_from(index as i64 + *offset as i64).ok()?)?;
+            if *is_word { Some(word.clone()) }
+            else if *tag == Wordclass::ANY { None }
+            else { Some(wordclass_to_pos_tag(tag).to_string()) }
+        }).collect();
+        instances = instances.iter().flat_map(|instance| values.iter().map(|value| {
+            let mut extended = instance.clone();
+            extended.push(value.clone());
+            extended
+        }).collect::<Vec<_>>()).collect();
+    }
+    instances.retain(|instance| !instance.is_empty());
+    instances
+}
+
+
+/// Function to list the rules, instantiated from `templates`, that would retag the word at `index` of `sentence` from its
+/// current tag to `target`, in template order without repeats.
+fn rules_at(corpus: &TrainingCorpus, sentence: usize, index: usize, target: &Wordclass, templates: &[RulespecID]) -> Vec<ContextualRulespec> {
+    let current = &corpus.sentences[sentence][index].1;
+    let mut rules: Vec<ContextualRulespec> = Vec::new();
+    for template in templates {
+        for parameters in instantiate_template(template, &corpus.sentences[sentence], index) {
+            let rule = ContextualRulespec {
+                source_tag: current.clone(),
+                target_tag: target.clone(),
+                ruleset_id: template.clone(),
+                parameters,
+                conditions: None,
+                provenance: None,
+            };
+            if !rules.contains(&rule) { rules.push(rule); }
+        }
+    }
+    rules
+}
+
+
+/// Function to list every rule that would fix the error at `index` of `sentence`: the current tag to the gold tag, under
+/// any instance of the `templates` there. Nothing is proposed for correct words, unscored words, words still tagged
+/// `Wordclass::ANY`, or when the gold tag is not a possible tag of the word (no rule can assign it).
+pub fn candidate_rules(corpus: &TrainingCorpus, sentence: usize, index: usize, templates: &[RulespecID]) -> Vec<ContextualRulespec> {
+    let current = &corpus.sentences[sentence][index].1;
+    let gold = &corpus.gold[sentence][index];
+    if !corpus.is_scored(sentence, index) || current == gold || *current == Wordclass::ANY || !corpus.can_take(sentence, index, gold) {
+        return Vec::new();
+    }
+    rules_at(corpus, sentence, index, gold, templates)
+}
+
+
+/// Function to list every rule that would break the correctly tagged word at `index` of `sentence`, by retagging it to any
+/// of its other possible tags.
+fn breaking_rules(corpus: &TrainingCorpus, sentence: usize, index: usize, templates: &[RulespecID]) -> Vec<ContextualRulespec> {
+    let current = &corpus.sentences[sentence][index].1;
+    if !corpus.is_scored(sentence, index) || *current != corpus.gold[sentence][index] || *current == Wordclass::ANY {
+        return Vec::new();
+    }
+    corpus.retag_options(sentence, index).iter()
+        .filter(|target| *target != current && **target != Wordclass::ANY)
+        .flat_map(|target| rules_at(corpus, sentence, index, target, templates))
+        .collect()
+}
+
+
+/// Function to count the errors `rule` would fix and introduce across the corpus, without applying it.
+fn score_rule(corpus: &TrainingCorpus, rule: &ContextualRulespec) -> (u32, u32) {
+    let Some(alternatives) = rule_window_alternatives(rule) else { return (0, 0) };
+    let (mut fixed, mut introduced) = (0, 0);
+    for (sentence, index) in corpus.positions() {
+        if !corpus.is_scored(sentence, index) || !corpus.rule_fires(rule, &alternatives, sentence, index) { continue; }
+        let gold = &corpus.gold[sentence][index];
+        if *gold == rule.target_tag { fixed += 1; } else if *gold == rule.source_tag { introduced += 1; }
+    }
+    (fixed, introduced)
+}
+
+
+/// Function to build the provenance of a learned rule from its counts when it was chosen.
+pub fn learned_provenance(fixed: u32, introduced: u32, source_corpus: &Option<String>) -> RuleProvenance {
+    RuleProvenance {
+        score: Some(fixed as i64 - introduced as i64),
+        errors_fixed: Some(fixed),
+        errors_introduced: Some(introduced),
+        source_corpus: source_corpus.clone(),
+        learner_version: Some(LEARNER_VERSION.to_string()),
+    }
+}
+
+
+/// Function to attach learning statistics to a chosen `rule`.
+pub fn with_provenance(rule: ContextualRulespec, fixed: u32, introduced: u32, settings: &ContextualLearnerSettings) -> ContextualRulespec {
+    ContextualRulespec { provenance: Some(learned_provenance(fixed, introduced, &settings.source_corpus)), ..rule }
+}
+
+
+/// The good (errors fixed) and bad (errors introduced) counts of every candidate rule, kept up to date as rules are applied.
+///
+/// Following Ngai & Florian's fast TBL, only rules that fix at least one error are tracked, and a rule's bad count is only
+/// computed (by a scan of the corpus) once it reaches the top of the queue; from then on it is updated incrementally like
+/// the good count. Until then its good count alone is its priority, which can only overestimate its score.
+struct RuleCounts {
+    rules: Vec<ContextualRulespec>,
+    ids: HashMap<ContextualRulespec, usize>,
+    good: Vec<i64>,
+    bad: Vec<Option<i64>>,
+    queue: BinaryHeap<(i64, Reverse<usize>)>,
+}
+
+impl RuleCounts {
+    fn new() -> RuleCounts {
+        RuleCounts { rules: Vec::new(), ids: HashMap::new(), good: Vec::new(), bad: Vec::new(), queue: BinaryHeap::new() }
+    }
+
+    fn id(&mut self, rule: ContextualRulespec) -> usize {
+        if let Some(&id) = self.ids.get(&rule) { return id; }
+        self.rules.push(rule.clone());
+        self.good.push(0);
+        self.bad.push(None);
+        self.ids.insert(rule, self.rules.len() - 1);
+        self.rules.len() - 1
+    }
+
+    fn priority(&self, id: usize) -> i64 {
+        self.good[id] - self.bad[id].unwrap_or(0)
+    }
+
+    /// Function to add (`sign` = 1) or remove (`sign` = -1) the contributions of the word at `index` of `sentence`: the
+    /// rules that would fix it, and the rules with a known bad count that would break it. Returns the rules touched.
+    fn count_position(&mut self, corpus: &TrainingCorpus, sentence: usize, index: usize, templates: &[RulespecID], sign: i64) -> Vec<usize> {
+        let mut touched: Vec<usize> = Vec::new();
+        for rule in candidate_rules(corpus, sentence, index, templates) {
+            let id = self.id(rule);
+            self.good[id] += sign;
+            touched.push(id);
+        }
+        for rule in breaking_rules(corpus, sentence, index, templates) {
+            if let Some(&id) = self.ids.get(&rule) {
+                if let Some(bad) = self.bad[id].as_mut() {
+                    *bad += sign;
+                    touched.push(id);
+                }
+            }
+        }
+        touched
+    }
+
+    /// Function to requeue `touched` rules at their new priority. Rules that no longer fix anything are dropped, and
+    /// forget their bad count (it would no longer be kept up to date).
+    fn requeue(&mut self, touched: Vec<usize>) {
+        for id in touched {
+            if self.good[id] <= 0 {
+                self.bad[id] = None;
+            } else {
+                self.queue.push((self.priority(id), Reverse(id)));
+            }
+        }
+    }
+
+    /// Function to pop the best rule, computing bad counts as rules reach the top. Queue entries whose priority is out of
+    /// date are skipped; each rule has an up-to-date entry further down. Rules breaking more than `max_errors_introduced`
+    /// words are left out of the queue until their counts change.
+    fn best(&mut self, corpus: &TrainingCorpus, max_errors_introduced: Option<u32>) -> Option<usize> {
+        while let Some((priority, Reverse(id))) = self.queue.pop() {
+            if self.good[id] <= 0 || priority != self.priority(id) { continue; }
+            if self.bad[id].is_none() {
+                self.bad[id] = Some(score_rule(corpus, &self.rules[id]).1 as i64);
+                self.queue.push((self.priority(id), Reverse(id)));
+                continue;
+            }
+            if max_errors_introduced.is_some_and(|max| self.bad[id].unwrap_or(0) > max as i64) { continue; }
+            // Put it back, so it stays queued if it is not applied (e.g. its score is below the threshold).
+            self.queue.push((priority, Reverse(id)));
+            return Some(id);
+        }
+        None
+    }
+}
+
+
+/// Function to learn contextual rules by transformation-based learning. Each round takes the rule with the best score
+/// (errors fixed minus errors introduced), applies it to the corpus and records it; learning stops when no rule reaches
+/// `settings.score_threshold`.
+///
+/// Scores are kept incrementally (see `RuleCounts`): applying a rule only recounts the words within reach of a template
+/// of a word it changed, rather than rescoring every rule against the whole corpus.
+///
+/// Rules are returned in the order they were learned, which is the order they must be applied in.
+pub fn learn_contextual_rules(corpus: &mut TrainingCorpus, settings: &ContextualLearnerSettings) -> Vec<ContextualRulespec> {
+    // The furthest a template looks from the word it retags; changing a word can change the rules matching this far away.
+    let reach: usize = settings.templates.iter()
+        .flat_map(|template| template_slots(template).iter().flat_map(|(_, offsets)| offsets.iter()))
+        .map(|offset| offset.unsigned_abs() as usize)
+        .max().unwrap_or(0);
+
+    let mut counts = RuleCounts::new();
+    let mut touched: Vec<usize> = Vec::new();
+    for (sentence, index) in corpus.positions().collect::<Vec<_>>() {
+        touched.extend(counts.count_position(corpus, sentence, index, &settings.templates, 1));
+    }
+    counts.requeue(touched);
+
+    let mut learned: Vec<ContextualRulespec> = Vec::new();
+    while settings.max_rules.is_none_or(|max_rules| learned.len() < max_rules) {
+        let Some(id) = counts.best(corpus, settings.max_errors_introduced) else { break };
+        let (fixed, introduced) = (counts.good[id], counts.bad[id].unwrap_or(0));
+        if fixed - introduced < settings.score_threshold { break; }
+        let rule = counts.rules[id].clone();
+
+        // Apply the rule one sentence at a time, recounting the words near each change before and after it.
+        let alternatives = rule_window_alternatives(&rule).unwrap_or_default();
+        let mut touched: Vec<usize> = Vec::new();
+        for sentence in 0..corpus.sentences.len() {
+            let mut retagged = corpus.sentences[sentence].clone();
+            let mut changed: Vec<usize> = Vec::new();
+            for index in 0..retagged.len() {
+                if retagged[index].1 == rule.source_tag && corpus.can_take(sentence, index, &rule.target_tag)
+                    && window_alternatives_hold(&retagged, index as i32, &alternatives) {
+                    retagged[index].1 = rule.target_tag.clone();
+                    changed.push(index);
+                }
+            }
+            if changed.is_empty() { continue; }
+
+            let affected: BTreeSet<usize> = changed.iter()
+                .flat_map(|&index| index.saturating_sub(reach)..(index + reach + 1).min(retagged.len()))
+                .collect();
+            for &index in &affected {
+                touched.extend(counts.count_position(corpus, sentence, index, &settings.templates, -1));
+            }
+            corpus.sentences[sentence] = retagged;
+            for &index in &affected {
+                touched.extend(counts.count_position(corpus, sentence, index, &settings.templates, 1));
+            }
+        }
+        counts.requeue(touched);
+
+        let rule = with_provenance(rule, fixed as u32, introduced as u32, settings);
+        if settings.trace { println!("learned rule {}: {}", learned.len() + 1, format_contextual_rule(&rule)); }
+        learned.push(rule);
+    }
+    learned
+}
+
+
+/// Function to learn contextual rules from the gold tags (in `settings.tag_column`) of the CoNLL-U file at `conllu_path`,
+/// starting from the initial-state tagger given by `lexicon` and `lexical_ruleset`, run with `tagger_settings`.
+pub fn train_contextual_rules(conllu_path: &str, lexical_ruleset: &Vec<LexicalRulespec>, lexicon: &mut WordclassMap, tagger_settings: &TaggerSettings, settings: &ContextualLearnerSettings) -> Result<Vec<ContextualRulespec>, Error> {
+    let gold = tagged_sentences(&parse_conllu_file(conllu_path)?, settings.tag_column);
+    let mut corpus = TrainingCorpus::new(&gold, lexical_ruleset, lexicon, tagger_settings);
+    if settings.trace { println!("initial-state errors: {} of {} words", corpus.errors(), corpus.scored_words()); }
+    let rules = learn_contextual_rules(&mut corpus, settings);
+    if settings.trace { println!("errors after {} rules: {} of {} words", rules.len(), corpus.errors(), corpus.scored_words()); }
+    Ok(rules)
+}
+
+
+/// Function to adapt an existing model to a new domain: the whole model (the `lexicon`, `lexical_ruleset` and
+/// `contextual_ruleset`, run with `tagger_settings`) tags the small in-domain gold CoNLL-U file at `conllu_path`, contextual rules are learned to
+/// correct the errors it still makes, and they are appended to the existing rules.
+///
+/// The new rules are scored as if applied once, after the whole model. The tagger instead applies them together with the
+/// existing rules of the same source tag (after them) until no rule fires, so a new rule undoing what an existing rule
+/// did to a word can retrigger that rule; such pairs leave the word to whichever fires last.
+pub fn adapt_contextual_rules(conllu_path: &str, lexical_ruleset: &Vec<LexicalRulespec>, contextual_ruleset: &[ContextualRulespec], lexicon: &mut WordclassMap, tagger_settings: &TaggerSettings, settings: &ContextualLearnerSettings) -> Result<Vec<ContextualRulespec>, Error> {
+    let gold = tagged_sentences(&parse_conllu_file(conllu_path)?, settings.tag_column);
+    let mut corpus = TrainingCorpus::from_model(&gold, lexical_ruleset, &group_contextual_rules(contextual_ruleset.to_vec()), lexicon, tagger_settings);
+    if settings.trace { println!("errors with the existing model: {} of {} words", corpus.errors(), corpus.scored_words()); }
+    let learned = learn_contextual_rules(&mut corpus, settings);
+    if settings.trace { println!("errors after {} additional rules: {} of {} words", learned.len(), corpus.errors(), corpus.scored_words()); }
+    Ok(contextual_ruleset.iter().cloned().chain(learned).collect())
+}
+
+
+/// Function to build a small gold corpus in which `run` and `walk` are verbs after `to` but nouns after `the`.
+#[cfg(test)]
+pub fn toy_training_corpus() -> (Vec<Vec<(String, Wordclass)>>, WordclassMap) {
+    use crate::initialize_tagger_from_str;
+
+    let lexicon = initialize_tagger_from_str("the DT\nto TO\nrun NN VB\nwalk NN VB\nI PRP\nwant VBP\nlike VBP\na DT\n");
+    let tagged = |text: &str, tags: &[Wordclass]| text.split(' ').map(String::from).zip(tags.iter().cloned()).collect::<Vec<_>>();
+    let gold = vec![
+        tagged("I want to run", &[Wordclass::PRPE, Wordclass::VBP, Wordclass::TO, Wordclass::VB]),
+        tagged("I like to walk", &[Wordclass::PRPE, Wordclass::VBP, Wordclass::TO, Wordclass::VB]),
+        tagged("I like the run", &[Wordclass::PRPE, Wordclass::VBP, Wordclass::DT, Wordclass::NN]),
+        tagged("I want a walk", &[Wordclass::PRPE, Wordclass::VBP, Wordclass::DT, Wordclass::NN]),
+        tagged("I want to walk", &[Wordclass::PRPE, Wordclass::VBP, Wordclass::TO, Wordclass::VB]),
+    ];
+    (gold, lexicon)
+}
+
+
+#[test]
+fn test_learn_contextual_rules() {
+    use crate::rs_brill_tagger::tag_sentence;
+    use crate::rs_contextual_ruleset::{group_contextual_rules, parse_contextual_rules_from_str};
+    use crate::rs_ruleset_writer::contextual_ruleset_to_string;
+
+    let (gold, mut lexicon) = toy_training_corpus();
+    let mut corpus = TrainingCorpus::new(&gold, &Vec::new(), &mut lexicon, &TaggerSettings::default());
+    assert_eq!(corpus.errors(), 3);
+
+    let settings = ContextualLearnerSettings { source_corpus: Some(String::from("toy")), ..ContextualLearnerSettings::default() };
+    let rules = learn_contextual_rules(&mut corpus, &settings);
+    assert_eq!(corpus.errors(), 0);
+    assert_eq!(rules.len(), 1);
+    assert_eq!((&rules[0].source_tag, &rules[0].target_tag), (&Wordclass::NN, &Wordclass::VB));
+    let provenance = rules[0].provenance.as_ref().unwrap();
+    assert_eq!((provenance.score, provenance.source_corpus.as_deref()), (Some(3), Some("toy")));
+
+    // The written rules load back unchanged, and the tagger applies them the way the learner scored them.
+    let reloaded = parse_contextual_rules_from_str(&contextual_ruleset_to_string(&rules)).unwrap();
+    assert_eq!(reloaded, rules);
+    let tagged = tag_sentence("I like to run", &Vec::new(), &group_contextual_rules(reloaded), &mut lexicon);
+    assert_eq!(tagged[3].1, Wordclass::VB);
+
+    // A threshold above the best score learns nothing.
+    let mut corpus = TrainingCorpus::new(&gold, &Vec::new(), &mut lexicon, &TaggerSettings::default());
+    assert!(learn_contextual_rules(&mut corpus, &ContextualLearnerSettings { score_threshold: 4, ..settings }).is_empty());
+}
+
+
+#[test]
+fn test_adapt_existing_model() {
+    use crate::rs_brill_tagger::tag_sentence;
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    // The existing rule gets the verbs after `want to` right, but misses `like to walk`.
+    let (mut gold, mut lexicon) = toy_training_corpus();
+    gold.retain(|sentence| sentence[2].0 != "a");
+    let existing = parse_contextual_rules_from_str("NN VB PREV1OR2WD want\n").unwrap();
+    let mut corpus = TrainingCorpus::from_model(&gold, &Vec::new(), &group_contextual_rules(existing.clone()), &mut lexicon, &TaggerSettings::default());
+    assert_eq!(corpus.errors(), 1);
+
+    let settings = ContextualLearnerSettings { score_threshold: 1, ..ContextualLearnerSettings::default() };
+    let learned = learn_contextual_rules(&mut corpus, &settings);
+    assert_eq!(corpus.errors(), 0);
+    let adapted: Vec<ContextualRulespec> = existing.iter().cloned().chain(learned).collect();
+    assert_eq!(adapted[0], existing[0]);
+    assert_eq!((&adapted[1].source_tag, &adapted[1].target_tag), (&Wordclass::NN, &Wordclass::VB));
+    assert_eq!(adapted.len(), 2);
+
+    let contextual_ruleset = group_contextual_rules(adapted);
+    for sentence in &gold {
+        let text: Vec<&str> = sentence.iter().map(|(word, _)| word.as_str()).collect();
+        assert_eq!(&tag_sentence(&text.join(" "), &Vec::new(), &contextual_ruleset, &mut lexicon), sentence);
+    }
+}
+
+
+#[test]
+fn test_instantiate_template() {
+    let sentence: Vec<(String, Wordclass)> = vec![(String::from("to"), Wordclass::TO), (String::from("run"), Wordclass::NN)];
+    assert_eq!(instantiate_template(&RulespecID::PREVTAG, &sentence, 1), vec![vec![String::from("TO")]]);
+    assert_eq!(instantiate_template(&RulespecID::WDPREVTAG, &sentence, 1), vec![vec![String::from("TO"), String::from("run")]]);
+    assert_eq!(instantiate_template(&RulespecID::PREV1OR2TAG, &sentence, 1), vec![vec![String::from("TO")]]);
+    assert!(instantiate_template(&RulespecID::NEXTTAG, &sentence, 1).is_empty());
+}
+
+
+/// Test that the incrementally kept counts agree with rescoring each learned rule from scratch, on part of EWT.
+#[test]
+fn test_incremental_counts_match_rescoring() {
+    use crate::initialize_tagger;
+    use crate::rs_lexical_ruleset::parse_lexical_ruleset;
+
+    let gold = crate::rs_conllu_parser::xpos_tagged_sentences(&parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap()[..150]);
+    let mut lexicon = initialize_tagger("data/lexicon.txt").unwrap();
+    let lexical_ruleset = parse_lexical_ruleset("data/rulefile_lexical.txt").unwrap();
+    let mut corpus = TrainingCorpus::new(&gold, &lexical_ruleset, &mut lexicon, &TaggerSettings::default());
+    let mut replay = corpus.clone();
+
+    // The first rule is the best of every candidate.
+    let settings = ContextualLearnerSettings { max_rules: Some(15), ..ContextualLearnerSettings::default() };
+    let best_score = corpus.positions()
+        .flat_map(|(sentence, index)| candidate_rules(&corpus, sentence, index, &settings.templates))
+        .map(|rule| { let (fixed, introduced) = score_rule(&corpus, &rule); fixed as i64 - introduced as i64 })
+        .max().unwrap();
+
+    let rules = learn_contextual_rules(&mut corpus, &settings);
+    assert_eq!(rules.len(), 15);
+    assert_eq!(rules[0].provenance.as_ref().unwrap().score, Some(best_score));
+    for rule in &rules {
+        let provenance = rule.provenance.as_ref().unwrap();
+        assert_eq!(score_rule(&replay, rule), (provenance.errors_fixed.unwrap(), provenance.errors_introduced.unwrap()), "{}", rule);
+        replay.apply_rule(rule);
+    }
+    assert_eq!(replay.sentences, corpus.sentences);
+}
@@ -3,14 +3,30 @@ use crate::rs_wordclass::{map_pos_tag, Wordclass};
 use std::io::{Error, ErrorKind};
 use crate::rs_rulespec_id::*;
 use crate::rs_contextual_rulespec::*;
+use crate::rs_rule_provenance::split_provenance;
 use std::collections::HashMap;
 
-/// ...
+/// Function to parse a contextual rule file at `path`, grouping the rules by the source tag they apply to.
 pub fn parse_contextual_ruleset(path: &str) -> Result<HashMap<Wordclass, Vec<ContextualRulespec>>, Error>
 {
-    let mut result: HashMap<Wordclass, Vec<ContextualRulespec>> = HashMap::new();
-    for line in read_to_string(path)?.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
+    Ok(group_contextual_rules(parse_contextual_rules(path)?))
+}
+
+
+/// Function to parse a contextual rule file at `path` into a flat vector, preserving the order of the rules in the file.
+pub fn parse_contextual_rules(path: &str) -> Result<Vec<ContextualRulespec>, Error>
+{
+    parse_contextual_rules_from_str(&read_to_string(path)?)
+}
+
+
+/// Function to parse the `contents` of a contextual rule file into a flat vector, preserving the order of the rules.
+pub fn parse_contextual_rules_from_str(contents: &str) -> Result<Vec<ContextualRulespec>, Error>
+{
+    let mut result: Vec<ContextualRulespec> = Vec::new();
+    for line in contents.lines() {
+        // Anything after `##` is the rule's provenance (see `RuleProvenance`), not part of the rule.
+        let (parts, provenance) = split_provenance(&line.split_whitespace().collect::<Vec<&str>>())?;
 
         // Brill's original contextual rules are in the form `TAG` `TAG` `NAME` followed by rule-specific parameters, which can be
         // additional tags, or string literals. Here, for each line, it is ensured that the first 3 of the sequence exist to ensure
@@ -23,24 +39,48 @@ pub fn parse_contextual_ruleset(path: &str) -> Result<HashMap<Wordclass, Vec<Con
         let source_tag: Option<Wordclass> = map_pos_tag(source);
         let target_tag: Option<Wordclass> = map_pos_tag(target);
 
-        match (source_tag, target_tag) {
-            (Some(s), Some(t)) => {
-                let ruleset_id: RulespecID = map_rulespec_id(rulestring)?;
-
-                // Finally, any additional parameters are collected, before the structure is added to the vector.
-                let parameters: Vec<String> = parts.iter().skip(3).map(|s| s.to_string()).collect();
-                let new_rulespec = ContextualRulespec {
-                    source_tag: s.clone(),
-                    target_tag: t,
-                    ruleset_id, parameters,
-                };
-
-                // Append the rule specification into the vector mapping of the source tag, meaning this rule applies to the source tag.
-                result.entry(s).or_default().push(new_rulespec.clone());
-            }
-            _ => {
-            }
+        // Rules whose source or target tag is not a recognised POS tag (e.g. `''`) are skipped.
+        if let (Some(s), Some(t)) = (source_tag, target_tag) {
+            let ruleset_id: RulespecID = map_rulespec_id(rulestring)?;
+
+            // Finally, any additional parameters are collected, before the structure is added to the vector.
+            let parameters: Vec<String> = parts.iter().skip(3).map(|s| s.to_string()).collect();
+
+            // Generalised rules have their conditions parsed here, once, so a malformed condition is reported when the file is
+            // loaded.
+            let conditions = compile_window_conditions(&ruleset_id, &parameters)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", e, line)))?;
+            result.push(ContextualRulespec {
+                source_tag: s,
+                target_tag: t,
+                ruleset_id, parameters, conditions, provenance,
+            });
         }
     }
     Ok(result)
 }
+
+
+/// Function to group a flat vector of contextual `rules` by source tag, keeping the relative order of the rules for each tag.
+pub fn group_contextual_rules(rules: Vec<ContextualRulespec>) -> HashMap<Wordclass, Vec<ContextualRulespec>>
+{
+    let mut result: HashMap<Wordclass, Vec<ContextualRulespec>> = HashMap::new();
+
+    // Append each rule specification into the vector mapping of its source tag, meaning this rule applies to the source tag.
+    for rule in rules {
+        result.entry(rule.source_tag.clone()).or_default().push(rule);
+    }
+    result
+}
+
+
+#[test]
+fn test_parse_conjunction_rules() {
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG TO\nNN VB CONJUNCTION tag@-1=MD word@+1=the !tag@-2=VB\n").unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[1].ruleset_id, RulespecID::CONJUNCTION);
+    assert_eq!(rules[1].parameters.len(), 3);
+
+    assert!(parse_contextual_rules_from_str("NN VB CONJUNCTION tag@-1=XYZ\n").is_err());
+    assert!(parse_contextual_rules_from_str("NN VB CONJUNCTION\n").is_err());
+}
@@ -1,6 +1,9 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, ErrorKind};
+use crate::rs_rule_provenance::RuleProvenance;
 use crate::rs_rulespec_id::RulespecID;
-use crate::rs_wordclass::{map_pos_tag, Wordclass};
+use crate::rs_wordclass::{map_pos_tag, wordclass_to_pos_tag, Wordclass};
 
 
 /// Function to check if the tag at index - 1 is equal to `tag` in a sentence.
@@ -95,7 +98,7 @@ pub fn prev_two_tag(sentence: Vec<(String, Wordclass)>, current_index: i32, tag:
 
 /// Function to check if the word at index + 1 is equal to `word` in a sentence.
 pub fn next_word (sentence: Vec<(String, Wordclass)>, current_index: i32, word: &str) -> bool {
-    match sentence.get((current_index - 1) as usize) {
+    match sentence.get((current_index + 1) as usize) {
         Some((_word, _)) if _word == &word => true,
         _ => false,
     }
@@ -105,7 +108,7 @@ pub fn next_word (sentence: Vec<(String, Wordclass)>, current_index: i32, word:
 /// Function to check current word and tag of hte next word.
 pub fn word_and_next_tag(sentence: Vec<(String, Wordclass)>, current_index: i32, word_one: &str, next_tag: Wordclass) -> bool {
     if sentence.get(current_index as usize).map_or(false, |(w1, _)| w1 == &word_one) {
-        sentence.get(current_index as usize + 2).map_or(false, |(_, _tag)| _tag == &next_tag)
+        sentence.get(current_index as usize + 1).map_or(false, |(_, _tag)| _tag == &next_tag)
     } else { false }
 }
 
@@ -122,10 +125,10 @@ pub fn surrounding_tags(sentence: Vec<(String, Wordclass)>, current_index: i32,
 }
 
 
-/// Function to check current word and tag of hte next word.
+/// Function to check current word and the tag two words before.
 pub fn word_and_two_tag_before(sentence: Vec<(String, Wordclass)>, current_index: i32, word: &str, tag: Wordclass) -> bool {
     if sentence.get(current_index as usize).map_or(false, |(w1, _)| w1 == &word) {
-        sentence.get(current_index as usize - 2).map_or(false, |(_, _tag)| _tag == &tag)
+        sentence.get((current_index - 2) as usize).map_or(false, |(_, _tag)| _tag == &tag)
     } else { false }
 }
 
@@ -177,7 +180,7 @@ pub fn current_word(sentence: Vec<(String, Wordclass)>, current_index: i32, word
 /// Function to check word and previous tag
 pub fn word_and_previous_tag(sentence: Vec<(String, Wordclass)>, current_index: i32, word: &str, tag: Wordclass) -> bool {
     if sentence.get(current_index as usize).map_or(false, |(w1, _)| w1 == &word) {
-        sentence.get(current_index as usize - 1).map_or(false, |(_, _tag)| _tag == &tag)
+        sentence.get((current_index - 1) as usize).map_or(false, |(_, _tag)| _tag == &tag)
     } else { false }
 }
 
@@ -191,6 +194,153 @@ pub fn next_two_tags(sentence: Vec<(String, Wordclass)>, current_index: i32, tag
 
 
 
+/// A test made by a generalised (`CONJUNCTION`) contextual rule on the token at `offset` from the current index.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum WindowTest {
+    Tag(Wordclass),
+    Word(String),
+    Class(Vec<Wordclass>),
+}
+
+
+/// A single condition of a `CONJUNCTION` rule. In rule files each condition is one parameter token of the form
+/// `[!]field@offset=value`, e.g. `tag@-1=MD`, `word@+1=the`, `!tag@-2=VB` or `class@+2=VB|VBD|VBZ`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct WindowCondition {
+    pub negated: bool,
+    pub offset: i32,
+    pub test: WindowTest,
+}
+
+
+/// Function to parse a `CONJUNCTION` parameter token into a `WindowCondition`, returning `None` if it is malformed.
+pub fn parse_window_condition(token: &str) -> Option<WindowCondition> {
+    let (negated, token) = match token.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+    let (field, rest) = token.split_once('@')?;
+    let (offset, value) = rest.split_once('=')?;
+    let offset: i32 = offset.strip_prefix('+').unwrap_or(offset).parse().ok()?;
+    let test = match field {
+        "tag" => WindowTest::Tag(map_pos_tag(value)?),
+        "word" if !value.is_empty() => WindowTest::Word(value.to_string()),
+        "class" => WindowTest::Class(value.split('|').map(map_pos_tag).collect::<Option<Vec<Wordclass>>>()?),
+        _ => return None,
+    };
+    Some(WindowCondition { negated, offset, test })
+}
+
+
+/// Function to write a `WindowCondition` as a `CONJUNCTION` parameter token (inverse of `parse_window_condition`).
+pub fn format_window_condition(condition: &WindowCondition) -> String {
+    let (field, value) = match &condition.test {
+        WindowTest::Tag(tag) => ("tag", wordclass_to_pos_tag(tag).to_string()),
+        WindowTest::Word(word) => ("word", word.clone()),
+        WindowTest::Class(tags) => ("class", tags.iter().map(wordclass_to_pos_tag).collect::<Vec<&str>>().join("|")),
+    };
+    format!("{}{}@{:+}={}", if condition.negated { "!" } else { "" }, field, condition.offset, value)
+}
+
+
+/// Function to check a `WindowCondition` against the token at `current_index` + `offset`.
+/// A position outside the sentence fails every positive test, so its negation holds (there is no `VB` before the start).
+pub fn window_condition_holds(sentence: &[(String, Wordclass)], current_index: i32, condition: &WindowCondition) -> bool {
+    let index = current_index + condition.offset;
+    let token = if index < 0 { None } else { sentence.get(index as usize) };
+    let passes = token.is_some_and(|(word, tag)| match &condition.test {
+        WindowTest::Tag(expected) => tag == expected,
+        WindowTest::Word(expected) => word == expected,
+        WindowTest::Class(expected) => expected.contains(tag),
+    });
+    passes != condition.negated
+}
+
+
+/// Function to parse the conditions of a `CONJUNCTION` rule from its `parameters`. Other rule kinds have no conditions.
+pub fn compile_window_conditions(ruleset_id: &RulespecID, parameters: &[String]) -> Result<Option<Vec<WindowCondition>>, Error> {
+    if *ruleset_id != RulespecID::CONJUNCTION { return Ok(None); }
+    if parameters.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "CONJUNCTION rule without conditions"));
+    }
+    parameters.iter()
+        .map(|parameter| parse_window_condition(parameter)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid CONJUNCTION condition '{}'", parameter))))
+        .collect::<Result<Vec<_>, Error>>()
+        .map(Some)
+}
+
+
+/// Function to check that every condition of a `CONJUNCTION` rule holds.
+pub fn conjunction_holds(sentence: &[(String, Wordclass)], current_index: i32, conditions: &[WindowCondition]) -> bool {
+    !conditions.is_empty() && conditions.iter().all(|condition| window_condition_holds(sentence, current_index, condition))
+}
+
+
+
+/// A rule's conditions (apart from its source tag) as alternatives: the rule applies when every condition of any one
+/// alternative holds. `PREV1OR2TAG NN`, for example, becomes `[[tag@-1=NN], [tag@-2=NN]]`.
+pub type WindowAlternatives = Vec<Vec<WindowCondition>>;
+
+
+/// Function to map a contextual template to the offsets it tests, one entry per parameter in file order. Each entry gives
+/// whether the parameter is a word (rather than a tag) and the offsets where it may be found (any one will do).
+pub fn template_slots(id: &RulespecID) -> &'static [(bool, &'static [i32])] {
+    match id {
+        RulespecID::PREVTAG => &[(false, &[-1])],
+        RulespecID::PREVWD => &[(true, &[-1])],
+        RulespecID::PREV1OR2TAG => &[(false, &[-1, -2])],
+        RulespecID::PREV1OR2OR3TAG => &[(false, &[-1, -2, -3])],
+        RulespecID::NEXT1OR2OR3TAG => &[(false, &[1, 2, 3])],
+        RulespecID::WDAND2TAGAFT => &[(true, &[0]), (false, &[2])],
+        RulespecID::WDAND2AFT => &[(true, &[0]), (true, &[2])],
+        RulespecID::PREV1OR2WD => &[(true, &[-1, -2])],
+        RulespecID::NEXT1OR2TAG => &[(false, &[1, 2])],
+        RulespecID::NEXTTAG => &[(false, &[1])],
+        RulespecID::PREV2TAG => &[(false, &[-2])],
+        RulespecID::NEXTWD => &[(true, &[1])],
+        RulespecID::WDNEXTTAG => &[(true, &[0]), (false, &[1])],
+        RulespecID::SURROUNDTAG => &[(false, &[-1]), (false, &[1])],
+        RulespecID::WDAND2TAGBFR => &[(false, &[-2]), (true, &[0])],
+        RulespecID::RBIGRAM => &[(true, &[0]), (true, &[1])],
+        RulespecID::PREVBIGRAM => &[(false, &[-2]), (false, &[-1])],
+        RulespecID::CURWD => &[(true, &[0])],
+        RulespecID::WDPREVTAG => &[(false, &[-1]), (true, &[0])],
+        RulespecID::NEXTBIGRAM => &[(false, &[1]), (false, &[2])],
+        RulespecID::NEXT2TAG => &[(false, &[2])],
+        RulespecID::LBIGRAM => &[(true, &[-1]), (true, &[0])],
+        RulespecID::CONJUNCTION => &[],
+    }
+}
+
+
+/// Function to express the conditions of a contextual `rule` as window alternatives, or `None` if they cannot be (an unknown
+/// tag such as `STAART`, or missing parameters).
+pub fn rule_window_alternatives(rule: &ContextualRulespec) -> Option<WindowAlternatives> {
+    if rule.ruleset_id == RulespecID::CONJUNCTION {
+        return rule.conditions.clone().filter(|conditions| !conditions.is_empty()).map(|conditions| vec![conditions]);
+    }
+
+    let mut alternatives: WindowAlternatives = vec![Vec::new()];
+    for (index, (is_word, offsets)) in template_slots(&rule.ruleset_id).iter().enumerate() {
+        let parameter = rule.parameters.get(index)?;
+        let test = if *is_word { WindowTest::Word(parameter.clone()) } else { WindowTest::Tag(map_pos_tag(parameter)?) };
+        alternatives = alternatives.iter().flat_map(|alternative| offsets.iter().map(|offset| {
+            let mut extended = alternative.clone();
+            extended.push(WindowCondition { negated: false, offset: *offset, test: test.clone() });
+            extended
+        }).collect::<Vec<_>>()).collect();
+    }
+    Some(alternatives)
+}
+
+
+/// Function to check whether any of the `alternatives` holds in full at `current_index`.
+pub fn window_alternatives_hold(sentence: &[(String, Wordclass)], current_index: i32, alternatives: &WindowAlternatives) -> bool {
+    alternatives.iter().any(|alternative| alternative.iter().all(|condition| window_condition_holds(sentence, current_index, condition)))
+}
+
+
 // Checks a given contextual rule.
 pub fn contextual_rule_holds(sentence: Vec<(String, Wordclass)>, current_index: i32, rule: ContextualRulespec) -> Option<bool> {
 
@@ -311,9 +461,10 @@ pub fn contextual_rule_holds(sentence: Vec<(String, Wordclass)>, current_index:
             }
         },
 
+        // Brill's files give the tag two before first, then the current word (e.g. `WDAND2TAGBFR DT hit`).
         RulespecID::WDAND2TAGBFR => {
-            let word_parameter = rule.parameters.get(0)?;
-            let type_parameter = rule.parameters.get(1)?;
+            let type_parameter = rule.parameters.get(0)?;
+            let word_parameter = rule.parameters.get(1)?;
             let type_wordclass = map_pos_tag(type_parameter);
             match type_wordclass {
                 Some(_wordclass) => { Option::from(word_and_two_tag_before(sentence, current_index, word_parameter, _wordclass)) }
@@ -328,9 +479,10 @@ pub fn contextual_rule_holds(sentence: Vec<(String, Wordclass)>, current_index:
         },
 
 
+        // The parameters are in sentence order: the tag at index - 2, then the tag at index - 1.
         RulespecID::PREVBIGRAM => {
-            let type_parameter1 = rule.parameters.get(0)?;
-            let type_parameter2 = rule.parameters.get(1)?;
+            let type_parameter2 = rule.parameters.get(0)?;
+            let type_parameter1 = rule.parameters.get(1)?;
             let type_wordclass1 = map_pos_tag(type_parameter1);
             let type_wordclass2 = map_pos_tag(type_parameter2);
             match (type_wordclass1, type_wordclass2) {
@@ -344,9 +496,10 @@ pub fn contextual_rule_holds(sentence: Vec<(String, Wordclass)>, current_index:
             Option::from(current_word(sentence, current_index, word_param))
         },
 
+        // Brill's files give the previous tag first, then the current word (e.g. `RB RP WDPREVTAG VB up`).
         RulespecID::WDPREVTAG => {
-            let word_parameter = rule.parameters.get(0)?;
-            let type_parameter = rule.parameters.get(1)?;
+            let type_parameter = rule.parameters.get(0)?;
+            let word_parameter = rule.parameters.get(1)?;
             let type_wordclass = map_pos_tag(type_parameter);
             match type_wordclass {
                 Some(_wordclass) => { Option::from(word_and_previous_tag(sentence, current_index, word_parameter, _wordclass)) }
@@ -375,11 +528,15 @@ pub fn contextual_rule_holds(sentence: Vec<(String, Wordclass)>, current_index:
         },
 
 
+        // The parameters are in sentence order: the previous word, then the current word.
         RulespecID::LBIGRAM => {
-            let first_word_param = rule.parameters.get(0)?;
-            let second_word_param = rule.parameters.get(1)?;
-            Option::from(left_bigram(sentence, current_index, first_word_param, second_word_param))
-        }
+            let previous_word_param = rule.parameters.get(0)?;
+            let current_word_param = rule.parameters.get(1)?;
+            Option::from(left_bigram(sentence, current_index, current_word_param, previous_word_param))
+        },
+
+        // A rule whose conditions were never parsed (see `compile_window_conditions`) cannot be checked.
+        RulespecID::CONJUNCTION => Some(conjunction_holds(&sentence, current_index, rule.conditions.as_ref()?)),
     }
 }
 
@@ -406,12 +563,33 @@ pub fn contextual_rule_apply(sentence: &mut Vec<(String, Wordclass)>, current_in
 
 
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+/// A contextual rule. Two rules are equal when they retag the same tags under the same conditions: their provenance is not
+/// compared, so a rule relearned on another corpus is still the same rule to `diff_rulesets` and `merge_rulesets`.
+#[derive(Debug, Clone)]
 pub struct ContextualRulespec {
     pub source_tag: Wordclass,
     pub target_tag: Wordclass,
     pub ruleset_id: RulespecID,
     pub parameters: Vec<String>,
+    /// The parsed conditions of a `CONJUNCTION` rule, built once when the rule is loaded.
+    pub conditions: Option<Vec<WindowCondition>>,
+    /// Where the rule came from, if it was learned (see `RuleProvenance`).
+    pub provenance: Option<RuleProvenance>,
+}
+
+impl PartialEq for ContextualRulespec {
+    fn eq(&self, other: &Self) -> bool {
+        self.source_tag == other.source_tag && self.target_tag == other.target_tag && self.ruleset_id == other.ruleset_id
+            && self.parameters == other.parameters
+    }
+}
+
+impl Eq for ContextualRulespec {}
+
+impl Hash for ContextualRulespec {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&self.source_tag, &self.target_tag, &self.ruleset_id, &self.parameters).hash(state);
+    }
 }
 
 
@@ -420,7 +598,11 @@ impl fmt::Display for ContextualRulespec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "RuleContextual {{{:?} -> {:?} if {} passes with parameters: [{}] }}",
                self.source_tag, self.target_tag, self.ruleset_id, self.parameters.join(", ")
-        )
+        )?;
+        match &self.provenance {
+            Some(provenance) => write!(f, " ({})", provenance),
+            None => Ok(()),
+        }
     }
 }
 
@@ -439,6 +621,8 @@ fn test_contextual_rule() {
         target_tag: Wordclass::FW,
         ruleset_id: RulespecID::SURROUNDTAG,
         parameters: vec!["NN".parse().unwrap(), "NN".parse().unwrap()],
+        conditions: None,
+        provenance: None,
     };
 
     for (w, c) in sentence.clone() {
@@ -735,4 +919,79 @@ fn test_prev_two_tag_out_of_bounds() {
     assert!(!prev_two_tag(sentence.clone(), 0, Wordclass::NN));
     assert!(!prev_two_tag(sentence.clone(), 1, Wordclass::NN));
 
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn test_conjunction_rule() {
+    let sentence = vec![
+        (String::from("They"), Wordclass::PRPE),
+        (String::from("will"), Wordclass::MD),
+        (String::from("book"), Wordclass::NN),
+        (String::from("the"), Wordclass::DT),
+        (String::from("room"), Wordclass::NN),
+    ];
+    let parameters = vec![String::from("tag@-1=MD"), String::from("word@+1=the"), String::from("!tag@-2=VB")];
+    let rule = ContextualRulespec {
+        source_tag: Wordclass::NN,
+        target_tag: Wordclass::VB,
+        ruleset_id: RulespecID::CONJUNCTION,
+        conditions: compile_window_conditions(&RulespecID::CONJUNCTION, &parameters).unwrap(),
+        parameters,
+        provenance: None,
+    };
+
+    // "room" is an `NN`, but is not preceded by a modal, so only "book" is retagged.
+    let mut tagged = sentence.clone();
+    assert_eq!(contextual_rule_apply(&mut tagged, 4, rule.clone()), Some(false));
+    assert_eq!(contextual_rule_apply(&mut tagged, 2, rule.clone()), Some(true));
+    assert_eq!(tagged[2].1, Wordclass::VB);
+
+    // The negated test fails once the tag two to the left is a `VB`.
+    let mut blocked = sentence.clone();
+    blocked[0].1 = Wordclass::VB;
+    assert_eq!(contextual_rule_apply(&mut blocked, 2, rule), Some(false));
+}
+
+
+#[test]
+fn test_window_conditions() {
+    let sentence = vec![
+        (String::from("to"), Wordclass::TO),
+        (String::from("run"), Wordclass::NN),
+    ];
+    let class = parse_window_condition("class@-1=TO|MD").unwrap();
+    assert_eq!(class.test, WindowTest::Class(vec![Wordclass::TO, Wordclass::MD]));
+    assert!(window_condition_holds(&sentence, 1, &class));
+
+    // Positions outside the sentence fail positive tests and pass negated ones.
+    assert!(!window_condition_holds(&sentence, 0, &parse_window_condition("tag@-1=TO").unwrap()));
+    assert!(window_condition_holds(&sentence, 0, &parse_window_condition("!tag@-1=TO").unwrap()));
+
+    for token in ["!word@+1=the", "tag@-2=PRP$", "class@+3=VB|VBD"] {
+        assert_eq!(format_window_condition(&parse_window_condition(token).unwrap()), token);
+    }
+    for malformed in ["tag@-1", "tag=MD", "tag@x=MD", "tag@-1=XYZ", "lemma@0=run", "word@0="] {
+        assert_eq!(parse_window_condition(malformed), None, "{}", malformed);
+    }
+}
+
+
+/// Test that every template checks the positions its window alternatives describe, on real sentences.
+#[test]
+fn test_templates_match_window_alternatives() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules;
+    use crate::rs_conllu_parser::{parse_conllu_file, xpos_tagged_sentences};
+
+    let rules = parse_contextual_rules("data/rulefile_contextual.txt").unwrap();
+    let sentences = xpos_tagged_sentences(&parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap()[..40]);
+    for rule in &rules {
+        let Some(alternatives) = rule_window_alternatives(rule) else { continue };
+        for sentence in &sentences {
+            for index in 0..sentence.len() as i32 {
+                assert_eq!(contextual_rule_holds(sentence.clone(), index, rule.clone()), Some(window_alternatives_hold(sentence, index, &alternatives)),
+                           "{} at {} in {:?}", rule, index, sentence);
+            }
+        }
+    }
+}
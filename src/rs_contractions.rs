@@ -14,8 +14,19 @@ struct Contractions {
 
 /// Function to load `data/contractions.json` as a hashmap of contractions to their expansions.
 fn load_contractions() -> Result<HashMap<String, Vec<String>>, io::Error> {
-    let data = fs::read_to_string("data/contractions.json")?;
-    let contractions: Contractions = serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    load_contractions_from("data/contractions.json")
+}
+
+
+/// Function to load a contractions file at `path` as a hashmap of contractions to their expansions.
+pub fn load_contractions_from(path: &str) -> Result<HashMap<String, Vec<String>>, io::Error> {
+    parse_contractions(&fs::read_to_string(path)?)
+}
+
+
+/// Function to parse the JSON `data` of a contractions file as a hashmap of contractions to their expansions.
+pub fn parse_contractions(data: &str) -> Result<HashMap<String, Vec<String>>, io::Error> {
+    let contractions: Contractions = serde_json::from_str(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
     Ok(contractions.contractions)
 }
 
@@ -29,10 +40,15 @@ fn expand_contraction(input: String, contractions_map: &HashMap<String, Vec<Stri
 /// Function to find contractions for a given `input`
 pub fn find_contractions(input: String) -> Result<Vec<String>, String> {
     let contractions_map = load_contractions().map_err(|e| format!("Error loading contractions: {}", e))?;
+    find_contractions_in(input, &contractions_map)
+}
+
 
+/// Function to find contractions for a given `input` in an already loaded `contractions_map`.
+pub fn find_contractions_in(input: String, contractions_map: &HashMap<String, Vec<String>>) -> Result<Vec<String>, String> {
     // Map the `input` to its corresponding contraction
     let mut result: Vec<String> = Vec::new();
-    if let Some(expansion) = expand_contraction(input.clone().to_lowercase(), &contractions_map) {
+    if let Some(expansion) = expand_contraction(input.clone().to_lowercase(), contractions_map) {
         match expansion.get(0) {
             Some(first_expansion) => {
                 result = first_expansion
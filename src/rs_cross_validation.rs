@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::io::Error;
+use crate::rs_brill_tagger::{tag_tokens_with_possible_tags, TaggerSettings};
+use crate::rs_conllu_parser::{parse_conllu_file, tagged_sentences, TagColumn};
+use crate::rs_contextual_learner::{learn_contextual_rules, ContextualLearnerSettings, TrainingCorpus};
+use crate::rs_contextual_ruleset::group_contextual_rules;
+use crate::rs_contextual_rulespec::ContextualRulespec;
+use crate::rs_lex_rulespec_id::LexicalRulespec;
+use crate::rs_lexical_learner::{learn_lexical_rules, LexicalLearnerSettings, UnknownWordCorpus};
+use crate::rs_lexicon_builder::{LexiconBuilder, LexiconCutoffs};
+use crate::rs_lexicon_lookup::{lookup_sentence, LookupStep};
+use crate::rs_wordclass::Wordclass;
+use crate::WordclassMap;
+
+
+type TaggedSentence = Vec<(String, Wordclass)>;
+
+
+/// Settings for training the whole pipeline (lexicon, lexical rules, contextual rules) from a gold corpus.
+#[derive(Debug, Clone)]
+pub struct PipelineSettings {
+    pub lexicon: LexiconCutoffs,
+    pub lexical: LexicalLearnerSettings,
+    pub contextual: ContextualLearnerSettings,
+    /// The CoNLL-U column whose tags are learned and evaluated.
+    pub tag_column: TagColumn,
+    /// How the tagger runs, both while the contextual rules are learned and when the trained model is evaluated, so that
+    /// the rules are learned for the tagger they are evaluated with. This includes how words are looked up in the lexicon.
+    pub tagger: TaggerSettings,
+}
+
+impl Default for PipelineSettings {
+    fn default() -> Self {
+        PipelineSettings::for_column(TagColumn::default())
+    }
+}
+
+impl PipelineSettings {
+    /// Function to give the default settings for training on the tags in `column`.
+    pub fn for_column(column: TagColumn) -> Self {
+        let lexical = match column {
+            TagColumn::Xpos => LexicalLearnerSettings::default(),
+            TagColumn::Upos => LexicalLearnerSettings::universal(),
+        };
+        PipelineSettings {
+            lexicon: LexiconCutoffs::default(),
+            lexical,
+            contextual: ContextualLearnerSettings { tag_column: column, ..ContextualLearnerSettings::default() },
+            tag_column: column,
+            tagger: TaggerSettings::default(),
+        }
+    }
+}
+
+
+/// A model trained from a gold corpus.
+#[derive(Debug, Clone)]
+pub struct TrainedModel {
+    pub lexicon: WordclassMap,
+    pub lexical_ruleset: Vec<LexicalRulespec>,
+    pub contextual_rules: Vec<ContextualRulespec>,
+}
+
+
+/// Function to train the whole pipeline from `gold_sentences`: the lexicon from their word/tag counts, the lexical rules
+/// from their pseudo-unknown words, then the contextual rules starting from the lexicon and lexical rules.
+pub fn train_pipeline(gold_sentences: &[TaggedSentence], settings: &PipelineSettings) -> TrainedModel {
+    let mut builder = LexiconBuilder::new();
+    builder.add_sentences(gold_sentences);
+    let lexicon = builder.build(&settings.lexicon);
+
+    let lexical_ruleset = learn_lexical_rules(&mut UnknownWordCorpus::new(gold_sentences, settings.lexical.held_out_every), &settings.lexical, &settings.tagger);
+    let mut corpus = TrainingCorpus::new(gold_sentences, &lexical_ruleset, &mut lexicon.clone(), &settings.tagger);
+    let contextual_rules = learn_contextual_rules(&mut corpus, &settings.contextual);
+    TrainedModel { lexicon, lexical_ruleset, contextual_rules }
+}
+
+
+/// Tagging accuracy on a gold corpus, overall and split by whether the word was found in the lexicon.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    pub known_correct: usize,
+    pub known_total: usize,
+    pub unknown_correct: usize,
+    pub unknown_total: usize,
+    /// The (correct, total) words for each lookup step that found words, and for words no step found (`None`).
+    pub lookup_steps: BTreeMap<Option<LookupStep>, (usize, usize)>,
+}
+
+impl Evaluation {
+    /// Function to add the counts of `other` to these, as when summing the folds of a cross-validation.
+    pub fn merge(&mut self, other: &Evaluation) {
+        self.known_correct += other.known_correct;
+        self.known_total += other.known_total;
+        self.unknown_correct += other.unknown_correct;
+        self.unknown_total += other.unknown_total;
+        for (step, (correct, total)) in &other.lookup_steps {
+            let counts = self.lookup_steps.entry(*step).or_insert((0, 0));
+            counts.0 += correct;
+            counts.1 += total;
+        }
+    }
+
+    pub fn correct(&self) -> usize {
+        self.known_correct + self.unknown_correct
+    }
+
+    pub fn total(&self) -> usize {
+        self.known_total + self.unknown_total
+    }
+
+    /// Function to give the proportion of words tagged right, or 0 if there were none.
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct(), self.total())
+    }
+
+    pub fn known_accuracy(&self) -> f64 {
+        ratio(self.known_correct, self.known_total)
+    }
+
+    pub fn unknown_accuracy(&self) -> f64 {
+        ratio(self.unknown_correct, self.unknown_total)
+    }
+}
+
+fn ratio(correct: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { correct as f64 / total as f64 }
+}
+
+
+/// Function to tag the words of `gold_sentences` with `model`, run with `tagger_settings`, and count the words whose tag
+/// matches the gold tag, by the lookup step that found each word. Words whose gold tag has no `Wordclass` are not counted.
+pub fn evaluate_model(model: &TrainedModel, gold_sentences: &[TaggedSentence], tagger_settings: &TaggerSettings) -> Evaluation {
+    let lookup = &tagger_settings.lookup;
+    let contextual_ruleset = group_contextual_rules(model.contextual_rules.clone());
+    let mut lexicon = model.lexicon.clone();
+    let mut evaluation = Evaluation::default();
+    for gold_sentence in gold_sentences {
+        let tokens: Vec<String> = gold_sentence.iter().map(|(word, _)| word.clone()).collect();
+        let steps: Vec<Option<LookupStep>> = lookup_sentence(&tokens, &model.lexicon, lookup).into_iter().map(|token| token.step).collect();
+        let (tagged, _) = tag_tokens_with_possible_tags(tokens, &model.lexical_ruleset, &contextual_ruleset, &mut lexicon, tagger_settings);
+        for (((_, gold), (_, tag)), step) in gold_sentence.iter().zip(&tagged).zip(steps) {
+            if *gold == Wordclass::ANY { continue; }
+            let (correct, total) = if step.is_some() {
+                (&mut evaluation.known_correct, &mut evaluation.known_total)
+            } else {
+                (&mut evaluation.unknown_correct, &mut evaluation.unknown_total)
+            };
+            let step_counts = evaluation.lookup_steps.entry(step).or_insert((0, 0));
+            *total += 1;
+            step_counts.1 += 1;
+            if tag == gold {
+                *correct += 1;
+                step_counts.0 += 1;
+            }
+        }
+    }
+    evaluation
+}
+
+
+/// Function to format the accuracy of the words found by each lookup step, to attribute errors to the steps.
+pub fn format_lookup_steps(evaluation: &Evaluation) -> String {
+    let mut table = format!("{:<22} {:>8} {:>8} {:>9}\n", "lookup step", "words", "correct", "accuracy");
+    for (step, (correct, total)) in &evaluation.lookup_steps {
+        let name = step.map_or("not found", |step| step.name());
+        table += &format!("{:<22} {:>8} {:>8} {:>9.4}\n", name, total, correct, ratio(*correct, *total));
+    }
+    table
+}
+
+
+/// The result of one fold of cross-validation.
+#[derive(Debug, Clone)]
+pub struct FoldResult {
+    pub fold: usize,
+    pub training_sentences: usize,
+    pub evaluation: Evaluation,
+}
+
+
+/// Function to split `sentences` into `folds` folds, sentence `i` going to fold `i % folds` (so each fold samples the
+/// whole corpus), and return the (training, held-out) sentences of `fold`.
+pub fn split_fold(sentences: &[TaggedSentence], folds: usize, fold: usize) -> (Vec<TaggedSentence>, Vec<TaggedSentence>) {
+    let (held_out, training): (Vec<_>, Vec<_>) = sentences.iter().enumerate().partition(|(index, _)| index % folds == fold);
+    let strip = |part: Vec<(usize, &TaggedSentence)>| part.into_iter().map(|(_, sentence)| sentence.clone()).collect();
+    (strip(training), strip(held_out))
+}
+
+
+/// Function to run `folds`-fold cross-validation on `sentences`: for each fold, train the pipeline on the other folds and
+/// evaluate it on the fold.
+pub fn cross_validate(sentences: &[TaggedSentence], folds: usize, settings: &PipelineSettings) -> Vec<FoldResult> {
+    (0..folds).map(|fold| {
+        let (training, held_out) = split_fold(sentences, folds, fold);
+        let model = train_pipeline(&training, settings);
+        FoldResult { fold, training_sentences: training.len(), evaluation: evaluate_model(&model, &held_out, &settings.tagger) }
+    }).collect()
+}
+
+
+/// Function to give the mean and (population) variance of `values`, or zeros if there are none.
+pub fn mean_and_variance(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() { return (0.0, 0.0); }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean, variance)
+}
+
+
+/// Function to format the accuracy of each fold, then the mean and variance across folds.
+pub fn format_cross_validation(results: &[FoldResult]) -> String {
+    let mut report = String::new();
+    for result in results {
+        report += &format!("fold {:>2}: trained on {:>5} sentences, {:>6} of {:>6} words right, accuracy {:.4}\n", result.fold + 1,
+                           result.training_sentences, result.evaluation.correct(), result.evaluation.total(), result.evaluation.accuracy());
+    }
+    let accuracies: Vec<f64> = results.iter().map(|result| result.evaluation.accuracy()).collect();
+    let (mean, variance) = mean_and_variance(&accuracies);
+    report += &format!("mean accuracy {:.4}, variance {:.6} (standard deviation {:.4})\n", mean, variance, variance.sqrt());
+    report
+}
+
+
+/// Function to run `folds`-fold cross-validation on the gold tags (in `settings.tag_column`) of the CoNLL-U file at
+/// `conllu_path`.
+pub fn cross_validate_conllu(conllu_path: &str, folds: usize, settings: &PipelineSettings) -> Result<Vec<FoldResult>, Error> {
+    Ok(cross_validate(&tagged_sentences(&parse_conllu_file(conllu_path)?, settings.tag_column), folds, settings))
+}
+
+
+#[test]
+fn test_mean_and_variance() {
+    let (mean, variance) = mean_and_variance(&[0.5, 0.7, 0.9]);
+    assert!((mean - 0.7).abs() < 1e-12);
+    assert!((variance - 0.08 / 3.0).abs() < 1e-12);
+    assert_eq!(mean_and_variance(&[]), (0.0, 0.0));
+}
+
+
+#[test]
+fn test_cross_validate() {
+    use crate::rs_lexicon_lookup::LookupChain;
+
+    let sentences = tagged_sentences(&parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap()[..300], TagColumn::Xpos);
+    let (training, held_out) = split_fold(&sentences, 3, 1);
+    assert_eq!((training.len(), held_out.len()), (200, 100));
+    assert_eq!(held_out[0], sentences[1]);
+
+    let settings = PipelineSettings {
+        lexical: LexicalLearnerSettings { max_rules: Some(10), ..LexicalLearnerSettings::default() },
+        contextual: ContextualLearnerSettings { max_rules: Some(10), ..ContextualLearnerSettings::default() },
+        ..PipelineSettings::default()
+    };
+    let results = cross_validate(&sentences, 3, &settings);
+    assert_eq!(results.len(), 3);
+    for result in &results {
+        assert!(result.evaluation.accuracy() > 0.6, "{:?}", result);
+        assert!(result.evaluation.known_accuracy() > result.evaluation.unknown_accuracy());
+    }
+    assert_eq!(results.iter().map(|result| result.evaluation.total()).sum::<usize>(),
+               sentences.iter().flatten().filter(|(_, tag)| *tag != Wordclass::ANY).count());
+    assert!(format_cross_validation(&results).starts_with("fold  1: trained on   200 sentences"));
+
+    // The full lookup chain finds some of the words the exact lookup misses, and every word is counted under one step.
+    let model = train_pipeline(&training, &settings);
+    let full_chain = TaggerSettings { lookup: LookupChain::full(), ..settings.tagger.clone() };
+    let (exact, full) = (evaluate_model(&model, &held_out, &settings.tagger), evaluate_model(&model, &held_out, &full_chain));
+    assert_eq!(full.lookup_steps.values().map(|(_, total)| total).sum::<usize>(), full.total());
+    assert!(full.lookup_steps.contains_key(&Some(LookupStep::Lowercase)));
+    assert!(full.unknown_total < exact.unknown_total);
+    assert_eq!(exact.lookup_steps.keys().collect::<Vec<_>>(), [&None, &Some(LookupStep::Exact)]);
+
+    // Training and evaluation run the tagger with the same settings: before any contextual rule, the corpus the rules are
+    // learned from has exactly the errors the evaluation finds.
+    let initial_state = TrainedModel { contextual_rules: Vec::new(), ..model.clone() };
+    let corpus = TrainingCorpus::new(&held_out, &model.lexical_ruleset, &mut model.lexicon.clone(), &settings.tagger);
+    let evaluation = evaluate_model(&initial_state, &held_out, &settings.tagger);
+    assert_eq!((corpus.errors(), corpus.scored_words()), (evaluation.total() - evaluation.correct(), evaluation.total()));
+}
+
+
+#[test]
+fn test_train_on_universal_tags() {
+    let sentences = tagged_sentences(&parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap()[..400], TagColumn::Upos);
+    let (training, held_out) = split_fold(&sentences, 4, 3);
+    let mut settings = PipelineSettings::for_column(TagColumn::Upos);
+    settings.lexical.max_rules = Some(10);
+    settings.contextual.max_rules = Some(10);
+
+    // The whole pipeline learns and tags Universal POS tags, without going through Penn tags.
+    let model = train_pipeline(&training, &settings);
+    assert!(model.lexicon.values().flatten().all(|tag| crate::rs_wordclass::is_universal_tag(tag) || *tag == Wordclass::SYM));
+    assert!(model.lexical_ruleset.iter().all(|rule| crate::rs_wordclass::is_universal_tag(&rule.target_tag)));
+    assert!(model.contextual_rules.iter().all(|rule| crate::rs_wordclass::is_universal_tag(&rule.target_tag)));
+    assert!(evaluate_model(&model, &held_out, &settings.tagger).accuracy() > 0.7);
+}
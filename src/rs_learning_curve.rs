@@ -0,0 +1,98 @@
+use std::io::Error;
+use crate::rs_conllu_parser::{parse_conllu_file, tagged_sentences};
+use crate::rs_cross_validation::{evaluate_model, split_fold, train_pipeline, Evaluation, PipelineSettings};
+use crate::rs_wordclass::Wordclass;
+
+
+/// The training fractions used when none are given.
+pub const DEFAULT_FRACTIONS: [f64; 6] = [0.05, 0.1, 0.25, 0.5, 0.75, 1.0];
+
+
+/// One point of a learning curve: a model trained on the first `training_sentences` sentences of the training set, and
+/// its accuracy on the test set.
+#[derive(Debug, Clone)]
+pub struct LearningCurvePoint {
+    pub fraction: f64,
+    pub training_sentences: usize,
+    pub training_words: usize,
+    pub evaluation: Evaluation,
+}
+
+
+/// Function to train the pipeline on increasing `fractions` of `training` and evaluate each model on `test`. Each fraction
+/// takes the sentences from the start of the training set, so every training set contains the smaller ones before it.
+pub fn learning_curve(training: &[Vec<(String, Wordclass)>], test: &[Vec<(String, Wordclass)>], fractions: &[f64], settings: &PipelineSettings) -> Vec<LearningCurvePoint> {
+    fractions.iter().map(|&fraction| {
+        let training_sentences = ((training.len() as f64 * fraction).round() as usize).clamp(1, training.len());
+        let part = &training[..training_sentences];
+        let model = train_pipeline(part, settings);
+        LearningCurvePoint { fraction, training_sentences, training_words: part.iter().map(Vec::len).sum(), evaluation: evaluate_model(&model, test, &settings.tagger) }
+    }).collect()
+}
+
+
+/// Function to format a learning curve as a table for the terminal.
+pub fn format_learning_curve_table(points: &[LearningCurvePoint]) -> String {
+    let mut table = format!("{:>8} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}\n", "fraction", "sentences", "words", "accuracy", "known", "unknown", "unknown%");
+    for point in points {
+        let evaluation = &point.evaluation;
+        table += &format!("{:>8.2} {:>9} {:>9} {:>9.4} {:>9.4} {:>9.4} {:>9.2}\n", point.fraction, point.training_sentences, point.training_words,
+                          evaluation.accuracy(), evaluation.known_accuracy(), evaluation.unknown_accuracy(),
+                          100.0 * evaluation.unknown_total as f64 / evaluation.total().max(1) as f64);
+    }
+    table
+}
+
+
+/// Function to format a learning curve as CSV, one row per training size.
+pub fn format_learning_curve_csv(points: &[LearningCurvePoint]) -> String {
+    let mut csv = String::from("fraction,training_sentences,training_words,test_words,accuracy,known_accuracy,unknown_accuracy,unknown_words\n");
+    for point in points {
+        let evaluation = &point.evaluation;
+        csv += &format!("{},{},{},{},{:.6},{:.6},{:.6},{}\n", point.fraction, point.training_sentences, point.training_words, evaluation.total(),
+                        evaluation.accuracy(), evaluation.known_accuracy(), evaluation.unknown_accuracy(), evaluation.unknown_total);
+    }
+    csv
+}
+
+
+/// Function to run a learning curve on the gold tags (in `settings.tag_column`) of the CoNLL-U files at `training_path`
+/// and `test_path`. If there is no separate test file, every tenth sentence of the training file is held out as the test
+/// set.
+pub fn learning_curve_conllu(training_path: &str, test_path: Option<&str>, fractions: &[f64], settings: &PipelineSettings) -> Result<Vec<LearningCurvePoint>, Error> {
+    let sentences = tagged_sentences(&parse_conllu_file(training_path)?, settings.tag_column);
+    let (training, test) = match test_path {
+        Some(path) => (sentences, tagged_sentences(&parse_conllu_file(path)?, settings.tag_column)),
+        None => split_fold(&sentences, 10, 9),
+    };
+    Ok(learning_curve(&training, &test, fractions, settings))
+}
+
+
+#[test]
+fn test_learning_curve() {
+    use crate::rs_conllu_parser::TagColumn;
+    use crate::rs_contextual_learner::ContextualLearnerSettings;
+    use crate::rs_lexical_learner::LexicalLearnerSettings;
+
+    let sentences = tagged_sentences(&parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap()[..400], TagColumn::Xpos);
+    let (training, test) = split_fold(&sentences, 4, 3);
+    let settings = PipelineSettings {
+        lexical: LexicalLearnerSettings { max_rules: Some(10), ..LexicalLearnerSettings::default() },
+        contextual: ContextualLearnerSettings { max_rules: Some(10), ..ContextualLearnerSettings::default() },
+        ..PipelineSettings::default()
+    };
+    let points = learning_curve(&training, &test, &[0.1, 1.0], &settings);
+    assert_eq!(points.iter().map(|point| point.training_sentences).collect::<Vec<_>>(), [30, 300]);
+
+    // More training data leaves fewer unknown words and tags the test set better.
+    let (small, large) = (&points[0].evaluation, &points[1].evaluation);
+    assert_eq!(small.total(), large.total());
+    assert!(large.unknown_total < small.unknown_total);
+    assert!(large.accuracy() > small.accuracy());
+
+    let csv = format_learning_curve_csv(&points);
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.lines().nth(2).unwrap().starts_with(&format!("1,300,{},{},", points[1].training_words, large.total())));
+    assert_eq!(format_learning_curve_table(&points).lines().count(), 3);
+}
@@ -1,14 +1,56 @@
 
 use std::{fmt};
+use std::hash::{Hash, Hasher};
 use std::io::{Error, ErrorKind};
+use regex::Regex;
+use crate::rs_rule_provenance::RuleProvenance;
 use crate::rs_wordclass::{Wordclass};
 
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+/// A lexical rule. As with contextual rules, two rules are equal when their templates, target tags and parameters are: the
+/// compiled pattern follows from the parameters, and the provenance is not compared.
+#[derive(Debug, Clone)]
 pub struct LexicalRulespec {
     pub ruleset_id: LexicalRuleID,
     pub target_tag: Wordclass,
     pub parameters: Vec<String>,
+    /// The compiled expression of a `regex`/`fregex` rule, built once when the rule is loaded.
+    pub pattern: Option<LexicalPattern>,
+    /// Where the rule came from, if it was learned (see `RuleProvenance`).
+    pub provenance: Option<RuleProvenance>,
+}
+
+impl PartialEq for LexicalRulespec {
+    fn eq(&self, other: &Self) -> bool {
+        self.ruleset_id == other.ruleset_id && self.target_tag == other.target_tag && self.parameters == other.parameters
+    }
+}
+
+impl Eq for LexicalRulespec {}
+
+impl Hash for LexicalRulespec {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&self.ruleset_id, &self.target_tag, &self.parameters).hash(state);
+    }
+}
+
+
+/// A compiled regular expression over word forms. Two patterns are equal when their source expressions are.
+#[derive(Debug, Clone)]
+pub struct LexicalPattern(pub Regex);
+
+impl PartialEq for LexicalPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for LexicalPattern {}
+
+impl Hash for LexicalPattern {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_str().hash(state);
+    }
 }
 
 /// LexicalRuleID Enumeration
@@ -29,8 +71,19 @@ pub enum LexicalRuleID {
     FADDSUF,
     FHASPREF,
     DELETESUF,
+    REGEX,
+    FREGEX,
 }
 
+/// Every `LexicalRuleID` template, in the order they are declared.
+pub const LEXICAL_RULE_IDS: [LexicalRuleID; 17] = [
+    LexicalRuleID::FHASSUF, LexicalRuleID::FCHAR, LexicalRuleID::HASSUF, LexicalRuleID::ADDSUF,
+    LexicalRuleID::FGOODRIGHT, LexicalRuleID::DELETEPREF, LexicalRuleID::FGOODLEFT, LexicalRuleID::GOODLEFT,
+    LexicalRuleID::GOODRIGHT, LexicalRuleID::FDELETESUF, LexicalRuleID::CHAR, LexicalRuleID::FDELETEPREF,
+    LexicalRuleID::FADDSUF, LexicalRuleID::FHASPREF, LexicalRuleID::DELETESUF, LexicalRuleID::REGEX,
+    LexicalRuleID::FREGEX,
+];
+
 /// Implementation to display LexicalRuleID in CLI.
 impl fmt::Display for LexicalRuleID {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -50,6 +103,8 @@ impl fmt::Display for LexicalRuleID {
             LexicalRuleID::FADDSUF => "Final Add Suffix",
             LexicalRuleID::FHASPREF => "Final Has Prefix",
             LexicalRuleID::DELETESUF => "Delete Suffix",
+            LexicalRuleID::REGEX => "Regular Expression",
+            LexicalRuleID::FREGEX => "Final Regular Expression",
         };
         write!(f, "{}", name)
     }
@@ -73,6 +128,60 @@ pub fn map_lexical_rule_id(string: &str) -> Result<LexicalRuleID, Error> {
         "faddsuf" => Ok(LexicalRuleID::FADDSUF),
         "fhaspref" => Ok(LexicalRuleID::FHASPREF),
         "deletesuf" => Ok(LexicalRuleID::DELETESUF),
+        "regex" => Ok(LexicalRuleID::REGEX),
+        "fregex" => Ok(LexicalRuleID::FREGEX),
         _ => Err(Error::new(ErrorKind::InvalidData, format!("Invalid LexicalRuleID Identifier: {}", string))),
     }
 }
+
+/// Function to map a LexicalRuleID back to the identifier used in Brill's lexical rule files (inverse of `map_lexical_rule_id`).
+pub fn lexical_rule_id_to_string(id: &LexicalRuleID) -> &'static str {
+    match id {
+        LexicalRuleID::FHASSUF => "fhassuf",
+        LexicalRuleID::FCHAR => "fchar",
+        LexicalRuleID::HASSUF => "hassuf",
+        LexicalRuleID::ADDSUF => "addsuf",
+        LexicalRuleID::FGOODRIGHT => "fgoodright",
+        LexicalRuleID::DELETEPREF => "deletepref",
+        LexicalRuleID::FGOODLEFT => "fgoodleft",
+        LexicalRuleID::GOODLEFT => "goodleft",
+        LexicalRuleID::GOODRIGHT => "goodright",
+        LexicalRuleID::FDELETESUF => "fdeletesuf",
+        LexicalRuleID::CHAR => "char",
+        LexicalRuleID::FDELETEPREF => "fdeletepref",
+        LexicalRuleID::FADDSUF => "faddsuf",
+        LexicalRuleID::FHASPREF => "fhaspref",
+        LexicalRuleID::DELETESUF => "deletesuf",
+        LexicalRuleID::REGEX => "regex",
+        LexicalRuleID::FREGEX => "fregex",
+    }
+}
+
+/// Function to check whether a LexicalRuleID is one of the `f`-variants, which are written with a leading source tag.
+/// In Brill's files these rules carry their rulestring as the 3rd token, rather than the 2nd.
+pub fn is_source_tagged(id: &LexicalRuleID) -> bool {
+    matches!(id,
+        LexicalRuleID::FHASSUF | LexicalRuleID::FCHAR | LexicalRuleID::FGOODRIGHT | LexicalRuleID::FGOODLEFT |
+        LexicalRuleID::FDELETESUF | LexicalRuleID::FDELETEPREF | LexicalRuleID::FADDSUF | LexicalRuleID::FHASPREF |
+        LexicalRuleID::FREGEX)
+}
+
+/// Function to check whether a lexical template carries the length of its affix as a parameter (e.g. `ly hassuf 2 RB x`).
+pub fn has_length_parameter(id: &LexicalRuleID) -> bool {
+    !matches!(id,
+        LexicalRuleID::CHAR | LexicalRuleID::FCHAR | LexicalRuleID::GOODLEFT | LexicalRuleID::FGOODLEFT |
+        LexicalRuleID::GOODRIGHT | LexicalRuleID::FGOODRIGHT | LexicalRuleID::REGEX | LexicalRuleID::FREGEX)
+}
+
+/// Function to compile the regular expression of a `regex`/`fregex` rule from its `parameters` (the expression follows the
+/// source tag for `fregex`). Other rule kinds have no pattern.
+pub fn compile_lexical_pattern(ruleset_id: &LexicalRuleID, parameters: &[String]) -> Result<Option<LexicalPattern>, Error> {
+    let expression = match ruleset_id {
+        LexicalRuleID::REGEX => parameters.first(),
+        LexicalRuleID::FREGEX => parameters.get(1),
+        _ => return Ok(None),
+    }.ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} rule is missing its expression", ruleset_id)))?;
+    Regex::new(expression)
+        .map(|regex| Some(LexicalPattern(regex)))
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Invalid expression '{}': {}", expression, e)))
+}
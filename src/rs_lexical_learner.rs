@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::io::Error;
+use crate::rs_brill_tagger::TaggerSettings;
+use crate::rs_conllu_parser::{parse_conllu_file, tagged_sentences, TagColumn};
+use crate::rs_contextual_learner::learned_provenance;
+use crate::rs_lex_rulespec_id::{compile_lexical_pattern, has_length_parameter, is_source_tagged, LexicalRuleID, LexicalRulespec, LEXICAL_RULE_IDS};
+use crate::rs_lexical_rulespec::{is_word_in_lexicon, lexical_rule_apply};
+use crate::rs_ruleset_writer::format_lexical_rule;
+use crate::rs_wordclass::{wordclass_to_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+
+/// Settings for learning lexical (unknown-word) rules.
+#[derive(Debug, Clone)]
+pub struct LexicalLearnerSettings {
+    /// Learning stops once the best rule's score (errors fixed minus errors introduced) falls below this.
+    pub score_threshold: i64,
+    /// Learning stops after this many rules (not counting the default-tag rules), if set.
+    pub max_rules: Option<usize>,
+    /// The templates rules are instantiated from.
+    pub templates: Vec<LexicalRuleID>,
+    /// The longest affix (in characters) the affix templates are instantiated with.
+    pub max_affix_length: usize,
+    /// Every `held_out_every`-th sentence is held out; its words that do not occur in the other sentences are the
+    /// pseudo-unknown words rules are learned from.
+    pub held_out_every: usize,
+    /// The tag unknown words start from.
+    pub default_tag: Wordclass,
+    /// The tag capitalised unknown words start from instead, if set.
+    pub capitalised_tag: Option<Wordclass>,
+    /// Name of the training corpus, recorded in each rule's provenance.
+    pub source_corpus: Option<String>,
+    /// The CoNLL-U column whose tags are learned.
+    pub tag_column: TagColumn,
+    /// Print each rule as it is learned, and the errors before and after learning.
+    pub trace: bool,
+}
+
+impl Default for LexicalLearnerSettings {
+    fn default() -> Self {
+        LexicalLearnerSettings {
+            score_threshold: 2,
+            max_rules: None,
+            // Regular expressions have no fixed shape to instantiate, so only Brill's templates are searched.
+            templates: LEXICAL_RULE_IDS.iter()
+                .filter(|id| !matches!(id, LexicalRuleID::REGEX | LexicalRuleID::FREGEX))
+                .cloned().collect(),
+            max_affix_length: 4,
+            held_out_every: 2,
+            default_tag: Wordclass::NN,
+            capitalised_tag: Some(Wordclass::NNP),
+            source_corpus: None,
+            tag_column: TagColumn::Xpos,
+            trace: false,
+        }
+    }
+}
+
+impl LexicalLearnerSettings {
+    /// Function to give the default settings for learning Universal POS tags, starting unknown words from their nouns.
+    pub fn universal() -> Self {
+        LexicalLearnerSettings {
+            default_tag: Wordclass::NOUN,
+            capitalised_tag: Some(Wordclass::PROPN),
+            tag_column: TagColumn::Upos,
+            ..LexicalLearnerSettings::default()
+        }
+    }
+}
+
+
+/// The held-out part of a gold corpus, with the tags the lexical rules have assigned so far to its pseudo-unknown words,
+/// and the lexicon built from the rest of the corpus.
+#[derive(Debug, Clone)]
+pub struct UnknownWordCorpus {
+    /// Each held-out word with its current tag. Known words keep their gold tag; pseudo-unknown words start untagged.
+    pub sentences: Vec<Vec<(String, Wordclass)>>,
+    /// The gold tag of each held-out word.
+    pub gold: Vec<Vec<Wordclass>>,
+    /// The (sentence, index) of every pseudo-unknown word with a gold tag.
+    pub unknown: Vec<(usize, usize)>,
+    /// The words of the sentences that were not held out, with the tags they were seen with.
+    pub lexicon: WordclassMap,
+    /// The lexicon's words in sorted order, to find the words that extend a given word (for `addsuf`).
+    sorted_words: Vec<String>,
+}
+
+impl UnknownWordCorpus {
+    /// Function to split `gold_sentences` into the held-out sentences (every `held_out_every`-th) and the lexicon built
+    /// from the others.
+    pub fn new(gold_sentences: &[Vec<(String, Wordclass)>], held_out_every: usize) -> UnknownWordCorpus {
+        let held_out = |index: usize| held_out_every > 0 && index % held_out_every == held_out_every - 1;
+
+        let mut lexicon: WordclassMap = HashMap::new();
+        for (_, sentence) in gold_sentences.iter().enumerate().filter(|(index, _)| !held_out(*index)) {
+            for (word, tag) in sentence.iter().filter(|(_, tag)| *tag != Wordclass::ANY) {
+                let tags = lexicon.entry(word.clone()).or_default();
+                if !tags.contains(tag) { tags.push(tag.clone()); }
+            }
+        }
+        let mut sorted_words: Vec<String> = lexicon.keys().cloned().collect();
+        sorted_words.sort();
+
+        let mut corpus = UnknownWordCorpus { sentences: Vec::new(), gold: Vec::new(), unknown: Vec::new(), lexicon, sorted_words };
+        for (_, sentence) in gold_sentences.iter().enumerate().filter(|(index, _)| held_out(*index)) {
+            let s = corpus.sentences.len();
+            let mut tagged: Vec<(String, Wordclass)> = Vec::new();
+            for (i, (word, tag)) in sentence.iter().enumerate() {
+                let known = corpus.lexicon.contains_key(word);
+                if !known && *tag != Wordclass::ANY { corpus.unknown.push((s, i)); }
+                tagged.push((word.clone(), if known { tag.clone() } else { Wordclass::ANY }));
+            }
+            corpus.sentences.push(tagged);
+            corpus.gold.push(sentence.iter().map(|(_, tag)| tag.clone()).collect());
+        }
+        corpus
+    }
+
+    /// Function to count the pseudo-unknown words whose tag, once the `defaults` have tagged anything still untagged,
+    /// differs from their gold tag.
+    pub fn errors(&self, defaults: &[LexicalRulespec]) -> usize {
+        self.unknown.iter().filter(|&&(s, i)| effective_tag(&self.sentences[s][i], defaults) != self.gold[s][i]).count()
+    }
+}
+
+
+/// Function to build the rules that tag every still-untagged word with the default tag (`^ regex NN x`), preceded by one
+/// for capitalised words if `settings.capitalised_tag` is set.
+pub fn default_tag_rules(settings: &LexicalLearnerSettings) -> Vec<LexicalRulespec> {
+    let rule = |expression: &str, target_tag: &Wordclass| {
+        let parameters = vec![expression.to_string(), String::from("x")];
+        LexicalRulespec {
+            ruleset_id: LexicalRuleID::REGEX,
+            target_tag: target_tag.clone(),
+            pattern: compile_lexical_pattern(&LexicalRuleID::REGEX, &parameters).ok().flatten(),
+            parameters,
+            provenance: None,
+        }
+    };
+    settings.capitalised_tag.iter().map(|tag| rule(r"^\p{Lu}", tag))
+        .chain(std::iter::once(rule("^", &settings.default_tag)))
+        .collect()
+}
+
+
+/// Function to find the tag a word ends up with if the `defaults` are applied to it: its current tag if it has one,
+/// otherwise the target of the first default rule matching it.
+fn effective_tag(word: &(String, Wordclass), defaults: &[LexicalRulespec]) -> Wordclass {
+    if word.1 != Wordclass::ANY { return word.1.clone(); }
+    defaults.iter()
+        .find(|rule| rule.pattern.as_ref().is_some_and(|pattern| pattern.0.is_match(&word.0)))
+        .map_or(Wordclass::ANY, |rule| rule.target_tag.clone())
+}
+
+
+/// A lexical rule without its target tag: the template and its parameters, as written in the rule file.
+type LexicalCondition = (LexicalRuleID, Vec<String>);
+
+
+/// Function to list the instances of `templates` that hold for the word at `index` of `sentence`, as the engine checks
+/// them: the `f`-variants (with the word's current tag as source) if it is tagged, the others if it is not.
+fn conditions_at(corpus: &UnknownWordCorpus, sentence: usize, index: usize, templates: &[LexicalRuleID], max_affix_length: usize) -> Vec<LexicalCondition> {
+    let words = &corpus.sentences[sentence];
+    let (word, tag) = &words[index];
+    let chars: Vec<char> = word.chars().collect();
+    let source = if *tag == Wordclass::ANY { None } else { Some(wordclass_to_pos_tag(tag)) };
+    let in_lexicon = |candidate: String| is_word_in_lexicon(candidate, &corpus.lexicon);
+
+    let mut conditions: Vec<LexicalCondition> = Vec::new();
+    for template in templates {
+        if is_source_tagged(template) != source.is_some() { continue; }
+        let suffixes = (1..=max_affix_length.min(chars.len())).map(|n| chars[chars.len() - n..].iter().collect::<String>());
+        let prefixes = (1..=max_affix_length.min(chars.len())).map(|n| chars[..n].iter().collect::<String>());
+        let values: Vec<String> = match template {
+            LexicalRuleID::HASSUF | LexicalRuleID::FHASSUF => suffixes.collect(),
+            LexicalRuleID::FHASPREF => prefixes.collect(),
+            LexicalRuleID::DELETESUF | LexicalRuleID::FDELETESUF =>
+                suffixes.filter(|suffix| in_lexicon(word[..word.len() - suffix.len()].to_string())).collect(),
+            LexicalRuleID::DELETEPREF | LexicalRuleID::FDELETEPREF =>
+                prefixes.filter(|prefix| in_lexicon(word[prefix.len()..].to_string())).collect(),
+            LexicalRuleID::ADDSUF | LexicalRuleID::FADDSUF => {
+                let start = corpus.sorted_words.partition_point(|other| other.as_str() <= word.as_str());
+                corpus.sorted_words[start..].iter()
+                    .take_while(|other| other.starts_with(word.as_str()))
+                    .map(|other| other[word.len()..].to_string())
+                    .filter(|suffix| suffix.chars().count() <= max_affix_length)
+                    .collect()
+            }
+            LexicalRuleID::CHAR | LexicalRuleID::FCHAR => chars.iter().map(|c| c.to_string()).collect(),
+            LexicalRuleID::GOODLEFT | LexicalRuleID::FGOODLEFT =>
+                index.checked_sub(1).and_then(|left| words.get(left)).map(|(left, _)| left.clone()).into_iter().collect(),
+            LexicalRuleID::GOODRIGHT | LexicalRuleID::FGOODRIGHT =>
+                words.get(index + 1).map(|(right, _)| right.clone()).into_iter().collect(),
+            LexicalRuleID::REGEX | LexicalRuleID::FREGEX => Vec::new(),
+        };
+        for value in values {
+            let length = value.chars().count().to_string();
+            let mut parameters: Vec<String> = source.iter().map(|tag| tag.to_string()).collect();
+            parameters.push(value);
+            if has_length_parameter(template) { parameters.push(length); }
+            parameters.push(String::from("x"));
+            let condition = (template.clone(), parameters);
+            if !conditions.contains(&condition) { conditions.push(condition); }
+        }
+    }
+    conditions
+}
+
+
+/// How the words a condition holds for stand: how many are wrong, by gold tag, and how many are right, by tag.
+#[derive(Debug, Default)]
+struct ConditionCounts {
+    wrong: HashMap<Wordclass, u32>,
+    right: HashMap<Wordclass, u32>,
+}
+
+
+/// Function to find the best rule instantiated from `templates` with its (errors fixed, errors introduced): for each
+/// condition, the rule retagging to the gold tag most often wrong under it. Ties go to the rule written first in order.
+fn best_rule(corpus: &UnknownWordCorpus, templates: &[LexicalRuleID], defaults: &[LexicalRulespec], settings: &LexicalLearnerSettings) -> Option<(LexicalRulespec, u32, u32)> {
+    let mut counts: HashMap<LexicalCondition, ConditionCounts> = HashMap::new();
+    for &(s, i) in &corpus.unknown {
+        let tag = effective_tag(&corpus.sentences[s][i], defaults);
+        let gold = &corpus.gold[s][i];
+        for condition in conditions_at(corpus, s, i, templates, settings.max_affix_length) {
+            let entry = counts.entry(condition).or_default();
+            let tally = if tag == *gold { &mut entry.right } else { &mut entry.wrong };
+            *tally.entry(gold.clone()).or_default() += 1;
+        }
+    }
+
+    let mut best: Option<(LexicalRulespec, u32, u32)> = None;
+    for ((ruleset_id, parameters), condition_counts) in counts {
+        let right: u32 = condition_counts.right.values().sum();
+        for (target_tag, fixed) in condition_counts.wrong {
+            // Retagging a right word to its own tag changes nothing; any other tag breaks it.
+            let introduced = right - condition_counts.right.get(&target_tag).copied().unwrap_or(0);
+            let rule = LexicalRulespec { ruleset_id: ruleset_id.clone(), target_tag, parameters: parameters.clone(), pattern: None, provenance: None };
+            let score = fixed as i64 - introduced as i64;
+            let better = match &best {
+                None => true,
+                Some((best_rule, best_fixed, best_introduced)) => {
+                    let best_score = *best_fixed as i64 - *best_introduced as i64;
+                    score > best_score || (score == best_score && format_lexical_rule(&rule) < format_lexical_rule(best_rule))
+                }
+            };
+            if better { best = Some((rule, fixed, introduced)); }
+        }
+    }
+    best
+}
+
+
+/// Function to apply `rule` to every pseudo-unknown word, as the tagger would. Returns the number of words retagged.
+fn apply_to_unknown(corpus: &mut UnknownWordCorpus, rule: &LexicalRulespec) -> usize {
+    let mut retagged = 0;
+    for &(s, i) in &corpus.unknown {
+        if lexical_rule_apply(&mut corpus.sentences[s], i as i32, rule, &corpus.lexicon) == Some(true) { retagged += 1; }
+    }
+    retagged
+}
+
+
+/// The tags the pseudo-unknown words had when the first `f`-rule was learned, and how many passes the tagger makes.
+struct TaggerPasses {
+    start_tags: Vec<Wordclass>,
+    first_rule: usize,
+    max_iterations: i32,
+}
+
+
+/// Function to retag the pseudo-unknown words from their `passes.start_tags` with the `f`-rules `rules`, as the tagger
+/// does: every rule in order on each word, for up to `max_iterations` passes after the first, until a pass retags
+/// nothing. The rules before the `f`-rules only tag untagged words, so they have nothing left to do after the first pass.
+fn retag_unknown(corpus: &mut UnknownWordCorpus, rules: &[LexicalRulespec], passes: &TaggerPasses) {
+    for (&(s, i), start_tag) in corpus.unknown.iter().zip(&passes.start_tags) {
+        corpus.sentences[s][i].1 = start_tag.clone();
+        let mut iterations = 0;
+        loop {
+            let retagged = rules.iter().filter(|rule| lexical_rule_apply(&mut corpus.sentences[s], i as i32, rule, &corpus.lexicon) == Some(true)).count();
+            if iterations == passes.max_iterations || retagged == 0 { break; }
+            iterations += 1;
+        }
+    }
+}
+
+
+/// Function to learn rules from `templates` until none reaches the threshold (or `budget` rules have been learned).
+/// With `passes`, each learned rule is followed by retagging the words as the tagger would with every rule since
+/// `passes.first_rule`; otherwise the rule is applied once. Returns the number of rules learned.
+fn learn_phase(corpus: &mut UnknownWordCorpus, templates: &[LexicalRuleID], defaults: &[LexicalRulespec], settings: &LexicalLearnerSettings, budget: Option<usize>, passes: Option<&TaggerPasses>, learned: &mut Vec<LexicalRulespec>) -> usize {
+    let mut count = 0;
+    while budget.is_none_or(|budget| count < budget) {
+        let Some((rule, fixed, introduced)) = best_rule(corpus, templates, defaults, settings) else { break };
+        if (fixed as i64 - introduced as i64) < settings.score_threshold { break; }
+        let rule = LexicalRulespec { provenance: Some(learned_provenance(fixed, introduced, &settings.source_corpus)), ..rule };
+        if settings.trace { println!("learned rule {}: {}", learned.len() + 1, format_lexical_rule(&rule)); }
+        match passes {
+            Some(passes) => {
+                learned.push(rule);
+                retag_unknown(corpus, &learned[passes.first_rule..], passes);
+            }
+            None => {
+                apply_to_unknown(corpus, &rule);
+                learned.push(rule);
+            }
+        }
+        count += 1;
+    }
+    count
+}
+
+
+/// Function to learn lexical rules for unknown words by transformation-based learning on the pseudo-unknown words of
+/// `corpus`, ranking rules by errors fixed minus errors introduced.
+///
+/// Words start from the default tag (see `default_tag_rules`). As the tagger only applies the rules without a source tag
+/// to untagged words, learning runs in two phases, matching the order the rules are written in:
+/// 1. rules without a source tag (`hassuf`, `addsuf`, `deletepref`, `char`, `goodright`, …), which tag the words the
+///    default would get wrong, scored as if the untagged words already had their default tag;
+/// 2. the default-tag rules, then the `f`-variants (`fhassuf`, …), which retag words by their current tag.
+///
+/// Each rule is scored by what it does the first time it applies. After each `f`-rule is learned, the words are retagged
+/// with the passes `tagger_settings.lexical_max_iterations` gives, so later rules are learned from the tags the tagger
+/// will actually assign. Rules are returned in the order they must be applied in.
+pub fn learn_lexical_rules(corpus: &mut UnknownWordCorpus, settings: &LexicalLearnerSettings, tagger_settings: &TaggerSettings) -> Vec<LexicalRulespec> {
+    let defaults = default_tag_rules(settings);
+    let (tagged_templates, untagged_templates): (Vec<LexicalRuleID>, Vec<LexicalRuleID>) =
+        settings.templates.iter().cloned().partition(is_source_tagged);
+
+    let mut learned: Vec<LexicalRulespec> = Vec::new();
+    let count = learn_phase(corpus, &untagged_templates, &defaults, settings, settings.max_rules, None, &mut learned);
+
+    for rule in defaults {
+        let before = corpus.errors(&[]);
+        apply_to_unknown(corpus, &rule);
+        let fixed = (before - corpus.errors(&[])) as u32;
+        learned.push(LexicalRulespec { provenance: Some(learned_provenance(fixed, 0, &settings.source_corpus)), ..rule });
+    }
+
+    let passes = TaggerPasses {
+        start_tags: corpus.unknown.iter().map(|&(s, i)| corpus.sentences[s][i].1.clone()).collect(),
+        first_rule: learned.len(),
+        max_iterations: tagger_settings.lexical_max_iterations,
+    };
+    let budget = settings.max_rules.map(|max_rules| max_rules - count);
+    learn_phase(corpus, &tagged_templates, &[], settings, budget, Some(&passes), &mut learned);
+    learned
+}
+
+
+/// Function to learn lexical rules from the gold tags (in `settings.tag_column`) of the CoNLL-U file at `conllu_path`, for
+/// a tagger run with `tagger_settings`.
+pub fn train_lexical_rules(conllu_path: &str, tagger_settings: &TaggerSettings, settings: &LexicalLearnerSettings) -> Result<Vec<LexicalRulespec>, Error> {
+    let gold = tagged_sentences(&parse_conllu_file(conllu_path)?, settings.tag_column);
+    let mut corpus = UnknownWordCorpus::new(&gold, settings.held_out_every);
+    if settings.trace { println!("pseudo-unknown words: {}, errors with default tags: {}", corpus.unknown.len(), corpus.errors(&default_tag_rules(settings))); }
+    let rules = learn_lexical_rules(&mut corpus, settings, tagger_settings);
+    if settings.trace { println!("errors after {} rules: {} of {} words", rules.len(), corpus.errors(&[]), corpus.unknown.len()); }
+    Ok(rules)
+}
+
+
+/// Function to build a small gold corpus whose held-out (odd) sentences have unknown adverbs in `-ly`, plurals in `-s`
+/// and capitalised names.
+#[cfg(test)]
+fn toy_unknown_word_corpus() -> Vec<Vec<(String, Wordclass)>> {
+    use Wordclass::*;
+    let tagged = |text: &str, tags: &[Wordclass]| text.split(' ').map(String::from).zip(tags.iter().cloned()).collect::<Vec<_>>();
+    vec![
+        tagged("the quick dog barked", &[DT, JJ, NN, VBD]),
+        tagged("the dogs barked quickly", &[DT, NNS, VBD, RB]),
+        tagged("a slow cat slept", &[DT, JJ, NN, VBD]),
+        tagged("the cats slept slowly", &[DT, NNS, VBD, RB]),
+        tagged("a quiet bird sang", &[DT, JJ, NN, VBD]),
+        tagged("Anna heard birds quietly", &[NNP, VBD, NNS, RB]),
+        tagged("the loud horse ran", &[DT, JJ, NN, VBD]),
+        tagged("Ben saw the horses loudly", &[NNP, VBD, DT, NNS, RB]),
+        tagged("the fox saw a goose", &[DT, NN, VBD, DT, NN]),
+        tagged("the owl heard a mouse", &[DT, NN, VBD, DT, NN]),
+    ]
+}
+
+
+#[test]
+fn test_learn_lexical_rules() {
+    use crate::rs_lexical_ruleset::parse_lexical_ruleset_from_str;
+    use crate::rs_ruleset_writer::lexical_ruleset_to_string;
+
+    let mut corpus = UnknownWordCorpus::new(&toy_unknown_word_corpus(), 2);
+    let words = |corpus: &UnknownWordCorpus| corpus.unknown.iter().map(|&(s, i)| corpus.sentences[s][i].0.clone()).collect::<Vec<_>>();
+    assert_eq!(words(&corpus), ["dogs", "quickly", "cats", "slowly", "Anna", "heard", "birds", "quietly", "Ben", "horses", "loudly", "owl", "heard", "mouse"]);
+
+    let settings = LexicalLearnerSettings { source_corpus: Some(String::from("toy")), ..LexicalLearnerSettings::default() };
+    assert_eq!(corpus.errors(&default_tag_rules(&settings)), 10);
+    let rules = learn_lexical_rules(&mut corpus, &settings, &TaggerSettings::default());
+    let written = lexical_ruleset_to_string(&rules);
+
+    // Adverbs and plurals reduce to known words, so they are tagged before the defaults; names and nouns keep the defaults.
+    let lines: Vec<&str> = written.lines().map(|line| line.split(" ##").next().unwrap()).collect();
+    assert_eq!(lines, ["ly deletesuf 2 RB x", "s deletesuf 1 NNS x", "ard hassuf 3 VBD x", r"^\p{Lu} regex NNP x", "^ regex NN x"]);
+    assert_eq!(rules[0].provenance.as_ref().unwrap().errors_fixed, Some(4));
+    assert_eq!(corpus.errors(&[]), 0);
+
+    // The written rules load back unchanged.
+    assert_eq!(parse_lexical_ruleset_from_str(&written).unwrap(), rules);
+}
+
+
+/// Test that the tagger, applying the learned rules to the held-out sentences with its default passes, tags the
+/// pseudo-unknown words exactly as the learner left them.
+#[test]
+fn test_learned_lexical_rules_match_tagger() {
+    use crate::rs_brill_tagger::initial_state_tags;
+
+    let gold: Vec<Vec<(String, Wordclass)>> = tagged_sentences(&parse_conllu_file("data/en_ewt-ud-test.conllu").unwrap(), TagColumn::Xpos)
+        .into_iter().take(400).collect();
+    let mut corpus = UnknownWordCorpus::new(&gold, 2);
+    let settings = LexicalLearnerSettings { max_rules: Some(12), ..LexicalLearnerSettings::default() };
+    let tagger_settings = TaggerSettings::default();
+    let rules = learn_lexical_rules(&mut corpus, &settings, &tagger_settings);
+    assert!(rules.len() > 4);
+    assert!(corpus.errors(&[]) < corpus.unknown.len() / 2);
+
+    let mut lexicon = corpus.lexicon.clone();
+    for (s, sentence) in corpus.sentences.iter().enumerate() {
+        let tokens: Vec<String> = sentence.iter().map(|(word, _)| word.clone()).collect();
+        let (tagged, _) = initial_state_tags(tokens, &rules, &mut lexicon, &tagger_settings);
+        for &(_, i) in corpus.unknown.iter().filter(|(us, _)| *us == s) {
+            assert_eq!(tagged[i], sentence[i], "sentence {}", s);
+        }
+    }
+}
@@ -2,18 +2,27 @@ use std::collections::{HashSet};
 use std::fs::read_to_string;
 use std::io;
 use std::io::Error;
-use crate::rs_lex_rulespec_id::{map_lexical_rule_id, LexicalRulespec};
+use crate::rs_lex_rulespec_id::{compile_lexical_pattern, map_lexical_rule_id, LexicalRulespec};
+use crate::rs_rule_provenance::split_provenance;
 use crate::rs_wordclass::{map_pos_tag, Wordclass};
 
-/// ...
+/// Function to parse a lexical rule file at `path` into a vector, preserving the order of the rules in the file.
 pub fn parse_lexical_ruleset(path: &str) -> Result<Vec<LexicalRulespec>, io::Error>
+{
+    parse_lexical_ruleset_from_str(&read_to_string(path)?)
+}
+
+
+/// Function to parse the `contents` of a lexical rule file into a vector, preserving the order of the rules.
+pub fn parse_lexical_ruleset_from_str(contents: &str) -> Result<Vec<LexicalRulespec>, io::Error>
 {
     let mut result: Vec<LexicalRulespec> = Vec::new();
-    for line in read_to_string(path)?.lines() {
+    for line in contents.lines() {
 
         //println!("Parsing line {0}", line);
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
+        // Anything after `##` is the rule's provenance (see `RuleProvenance`), not part of the rule.
+        let (parts, provenance) = split_provenance(&line.split_whitespace().collect::<Vec<&str>>())?;
 
         // Brill's original lexical rules come in a (somewhat weird) variety of forms, with each rule varying in syntactic structure.
         // The only common attributes are the `rulestring` and `target_tag`, as some rules are source-tag ambiguous. This processes it.
@@ -54,8 +63,10 @@ pub fn parse_lexical_ruleset(path: &str) -> Result<Vec<LexicalRulespec>, io::Err
             else { None } } ).collect();
 
         // Encapsulate the rule in the `LexicalRulespec` type, and push to the result vector.
+        // Regular expressions are compiled here, once, rather than each time the rule is checked.
+        let pattern = compile_lexical_pattern(&ruleset_id, &parameters)?;
         let new_rulespec = LexicalRulespec {
-            ruleset_id, target_tag, parameters
+            ruleset_id, target_tag, parameters, pattern, provenance
         };
         result.push(new_rulespec);
     }
@@ -1,6 +1,7 @@
 use crate::rs_wordclass::{map_pos_tag, Wordclass};
 use crate::{initialize_tagger, WordclassMap};
-use crate::rs_lex_rulespec_id::{LexicalRuleID, LexicalRulespec};
+use regex::Regex;
+use crate::rs_lex_rulespec_id::{compile_lexical_pattern, LexicalPattern, LexicalRuleID, LexicalRulespec};
 
 /// Function to check if the word at `current_index` has suffix `suffix` and is not yet tagged.
 pub fn has_suffix(sentence: &Vec<(String, Wordclass)>, current_index: i32, suffix: &str) -> bool {
@@ -206,31 +207,43 @@ pub fn f_appears_to_right(sentence: &Vec<(String, Wordclass)>, current_index: i3
 }
 
 
-/// Function to check if `word` appears in the Wordclass mappings retrieved from the lexicon.
+/// Function to check if the word at `current_index` matches the regular expression `pattern` and is not yet tagged.
+pub fn matches_pattern(sentence: &Vec<(String, Wordclass)>, current_index: i32, pattern: &Regex) -> bool {
+    match sentence.get(current_index as usize) {
+        Some((word, Wordclass::ANY)) => pattern.is_match(word),
+        _ => false,
+    }
+}
+
+
+/// Function to check if the word at `current_index` matches the regular expression `pattern` and is tagged as `source_tag`.
+pub fn f_matches_pattern(sentence: &Vec<(String, Wordclass)>, current_index: i32, pattern: &Regex, source_tag: Wordclass) -> bool {
+    match sentence.get(current_index as usize) {
+        Some((_, Wordclass::ANY)) => false,
+        Some((word, ref tag)) => pattern.is_match(word) && tag.to_owned() == source_tag,
+        _ => false,
+    }
+}
+
+
+/// Function to check if `word` appears in the Wordclass mappings retrieved from the lexicon. Unknown words the tagger
+/// has cached as `[ANY]` do not count.
 pub fn is_word_in_lexicon(word: String, wc_mapping: &WordclassMap) -> bool {
     match wc_mapping.get(&word) {
-        Some(_) => true,
+        Some(tags) => tags.as_slice() != [Wordclass::ANY],
         _ => false
     }
 }
 
 
-/// Checks a given lexical rule.
+/// Checks a given lexical rule. Rules without a source tag (`hassuf`, `char`, …) take their affix or word from the first
+/// parameter and only apply to words that are not yet tagged; the `f`-variants take the source tag first, then the affix.
 pub fn lexical_rule_holds(sentence: &mut Vec<(String, Wordclass)>, current_index: i32, rule: &LexicalRulespec, wc_mapping: &WordclassMap) -> Option<bool> {
 
     match rule.ruleset_id {
         LexicalRuleID::HASSUF => {
-
-            let suffix: &str = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
-
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(has_suffix(&sentence, current_index, suffix)) }
-                None => {
-                    Option::from(false)
-                }
-            }
+            let suffix = rule.parameters.get(0)?;
+            Option::from(has_suffix(&sentence, current_index, suffix))
         }
         LexicalRuleID::FCHAR => {
             let c = rule.parameters.get(1)?;
@@ -245,16 +258,8 @@ pub fn lexical_rule_holds(sentence: &mut Vec<(String, Wordclass)>, current_index
             }
         }
         LexicalRuleID::ADDSUF => {
-            let suffix = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
-
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(add_suffix(&sentence, current_index, suffix, wc_mapping)) }
-                None => {
-                    Option::from(false)
-                }
-            }
+            let suffix = rule.parameters.get(0)?;
+            Option::from(add_suffix(&sentence, current_index, suffix, wc_mapping))
         }
         LexicalRuleID::FGOODRIGHT => {
             let expected_word = rule.parameters.get(1)?;
@@ -269,16 +274,8 @@ pub fn lexical_rule_holds(sentence: &mut Vec<(String, Wordclass)>, current_index
             }
         }
         LexicalRuleID::DELETEPREF => {
-            let prefix = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
-
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(delete_prefix(&sentence, current_index, prefix, wc_mapping)) }
-                None => {
-                    Option::from(false)
-                }
-            }
+            let prefix = rule.parameters.get(0)?;
+            Option::from(delete_prefix(&sentence, current_index, prefix, wc_mapping))
         }
         LexicalRuleID::FGOODLEFT => {
             let expected_word = rule.parameters.get(1)?;
@@ -293,28 +290,12 @@ pub fn lexical_rule_holds(sentence: &mut Vec<(String, Wordclass)>, current_index
             }
         }
         LexicalRuleID::GOODLEFT => {
-            let expected_word = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
-
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(appears_to_left(&sentence, current_index, expected_word)) }
-                None => {
-                    Option::from(false)
-                }
-            }
+            let expected_word = rule.parameters.get(0)?;
+            Option::from(appears_to_left(&sentence, current_index, expected_word))
         }
         LexicalRuleID::GOODRIGHT => {
-            let expected_word = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
-
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(appears_to_right(&sentence, current_index, expected_word)) }
-                None => {
-                    Option::from(false)
-                }
-            }
+            let expected_word = rule.parameters.get(0)?;
+            Option::from(appears_to_right(&sentence, current_index, expected_word))
         }
         LexicalRuleID::FDELETESUF => {
             let suffix = rule.parameters.get(1)?;
@@ -329,16 +310,8 @@ pub fn lexical_rule_holds(sentence: &mut Vec<(String, Wordclass)>, current_index
             }
         }
         LexicalRuleID::CHAR => {
-            let c = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
-
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(has_char(&sentence, current_index, c.parse().unwrap())) }
-                None => {
-                    Option::from(false)
-                }
-            }
+            let c = rule.parameters.get(0)?;
+            Option::from(has_char(&sentence, current_index, c.parse().ok()?))
         }
         LexicalRuleID::FDELETEPREF => {
             let prefix = rule.parameters.get(1)?;
@@ -389,12 +362,26 @@ pub fn lexical_rule_holds(sentence: &mut Vec<(String, Wordclass)>, current_index
             }
         }
         LexicalRuleID::DELETESUF => {
-            let suffix = rule.parameters.get(1)?;
-            let source_tag= rule.parameters.get(0)?;
-            let source_tag_wc = map_pos_tag(source_tag);
+            let suffix = rule.parameters.get(0)?;
+            Option::from(delete_suffix(&sentence, current_index, suffix, wc_mapping))
+        }
+        LexicalRuleID::REGEX | LexicalRuleID::FREGEX => {
+            // Rules loaded from a file carry their compiled pattern; rules built in code are compiled here instead.
+            let compiled;
+            let pattern: &Regex = match &rule.pattern {
+                Some(LexicalPattern(regex)) => regex,
+                None => {
+                    compiled = compile_lexical_pattern(&rule.ruleset_id, &rule.parameters).ok()??;
+                    &compiled.0
+                }
+            };
 
-            match source_tag_wc {
-                Some(_wordclass) => { Option::from(delete_suffix(&sentence, current_index, suffix, wc_mapping)) }
+            if rule.ruleset_id == LexicalRuleID::REGEX {
+                return Option::from(matches_pattern(&sentence, current_index, pattern));
+            }
+            let source_tag = rule.parameters.get(0)?;
+            match map_pos_tag(source_tag) {
+                Some(_wordclass) => { Option::from(f_matches_pattern(&sentence, current_index, pattern, _wordclass)) }
                 None => {
                     Option::from(false)
                 }
@@ -439,6 +426,8 @@ fn test_lexical_rule_apply() {
         ruleset_id: LexicalRuleID::FHASSUF,
         target_tag: Wordclass::NN,
         parameters: vec![String::from("JJ"), "ick".parse().unwrap()],
+        pattern: None,
+        provenance: None,
     };
 
     assert!(lexical_rule_apply(&mut sentence, 1, &rule_fhassuf, &wc_mapping).unwrap());
@@ -938,4 +927,67 @@ fn test_f_has_char_not_found() {
     ];
     assert!(!f_has_char(&sentence, 1, 'q', Wordclass::ANY));
     assert!(!f_has_char(&sentence, 2, 'k', Wordclass::JJ));
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn test_regex_rule_apply() {
+    use crate::rs_lexical_ruleset::parse_lexical_ruleset_from_str;
+
+    let wc_mapping: WordclassMap = WordclassMap::new();
+    let rules = parse_lexical_ruleset_from_str("^(1[89]|20)[0-9]{2}$ regex CD x\nNN [iy][sz]e$ fregex VB x\n").unwrap();
+    assert!(rules.iter().all(|rule| rule.pattern.is_some()));
+
+    let mut sentence = vec![
+        (String::from("In"), Wordclass::IN),
+        (String::from("1999"), Wordclass::ANY),
+        (String::from("we"), Wordclass::PRPE),
+        (String::from("organise"), Wordclass::NN),
+        (String::from("2099x"), Wordclass::ANY),
+    ];
+    assert_eq!(lexical_rule_apply(&mut sentence, 1, &rules[0], &wc_mapping), Some(true));
+    assert_eq!(lexical_rule_apply(&mut sentence, 4, &rules[0], &wc_mapping), Some(false));
+    assert_eq!(lexical_rule_apply(&mut sentence, 3, &rules[1], &wc_mapping), Some(true));
+    assert_eq!(sentence[1].1, Wordclass::CD);
+    assert_eq!(sentence[3].1, Wordclass::VB);
+
+    // A rule built in code without a compiled pattern still works, and invalid expressions are refused at load time.
+    let uncompiled = LexicalRulespec { pattern: None, ..rules[0].clone() };
+    let mut year = vec![(String::from("1850"), Wordclass::ANY)];
+    assert_eq!(lexical_rule_apply(&mut year, 0, &uncompiled, &wc_mapping), Some(true));
+    assert!(parse_lexical_ruleset_from_str("[0-9 regex CD x\n").is_err());
+}
+
+
+#[test]
+fn test_unknown_word_rules() {
+    use std::collections::HashMap;
+    use crate::initialize_tagger_from_str;
+    use crate::rs_brill_tagger::{tag_sentence_with_model, TaggerModel, TaggerSettings};
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+    use crate::rs_lexical_ruleset::parse_lexical_ruleset_from_str;
+
+    // Rules with a source tag take it first, then the affix, as they always have.
+    let rules = parse_lexical_ruleset_from_str("NN ing fhassuf 3 VBG x\ning hassuf 3 VBG x\n").unwrap();
+    let mut sentence = vec![(String::from("running"), Wordclass::NN), (String::from("blorking"), Wordclass::ANY)];
+    assert_eq!(lexical_rule_apply(&mut sentence, 0, &rules[0], &WordclassMap::new()), Some(true));
+
+    // Rules without a source tag take the affix from their first parameter. They used to read the affix length (`3`) as
+    // the affix, so they never fired.
+    assert_eq!(lexical_rule_apply(&mut sentence, 1, &rules[1], &WordclassMap::new()), Some(true));
+    assert_eq!(sentence[1].1, Wordclass::VBG);
+
+    // Words the tagger has cached as unknown (`[ANY]`) do not count as being in the lexicon; they used to.
+    let mut lexicon = initialize_tagger_from_str("the DT\n");
+    lexicon.insert(String::from("blorkings"), vec![Wordclass::ANY]);
+    assert!(!is_word_in_lexicon(String::from("blorkings"), &lexicon));
+    assert!(is_word_in_lexicon(String::from("the"), &lexicon));
+
+    // An unknown word may be given any tag. Its possible tags `[ANY]` used to contain no rule's target tag, so neither the
+    // lexical nor the contextual rules could tag it and it kept the tag `ANY`.
+    let contextual = parse_contextual_rules_from_str("VBG NN PREVTAG DT\n").unwrap();
+    let settings = TaggerSettings::default();
+    let mut model = TaggerModel::new(lexicon, rules[1..].to_vec(), contextual, HashMap::new(), settings);
+    assert_eq!(tag_sentence_with_model("blorking", &mut model)[0].1, Wordclass::VBG);
+    assert_eq!(tag_sentence_with_model("the blorking", &mut model)[1].1, Wordclass::NN);
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::{self, Error, ErrorKind};
+use crate::rs_conllu_parser::{parse_conllu_file, tagged_sentences, TagColumn};
+use crate::rs_weighted_lexicon::WeightedLexicon;
+use crate::rs_wordclass::{map_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+// The lexicon file read by `initialize_tagger` has one word per line: the word, then the Penn tags it may take, separated
+// by whitespace and ordered most likely first, e.g.
+//
+//     run VB NN VBP VBN
+//
+// The first tag is the word's initial-state tag; the others bound the tags the rules may retag it to. Tags with no
+// `Wordclass` are dropped when the lexicon is read. `LexiconBuilder` produces this file from tagged corpora, or the weighted
+// format of `rs_weighted_lexicon`, which keeps the count of each tag.
+
+
+/// Cut-offs applied when building a lexicon from corpus counts.
+#[derive(Debug, Clone)]
+pub struct LexiconCutoffs {
+    /// Words seen fewer times than this are left out.
+    pub min_word_count: u32,
+    /// Tags seen fewer times than this with a word are left out of its entry, though its most frequent tag is always kept.
+    pub min_tag_count: u32,
+}
+
+impl Default for LexiconCutoffs {
+    fn default() -> Self {
+        LexiconCutoffs { min_word_count: 1, min_tag_count: 1 }
+    }
+}
+
+
+/// Counts of word/tag pairs collected from tagged corpora, from which a lexicon is built.
+#[derive(Debug, Clone, Default)]
+pub struct LexiconBuilder {
+    pub counts: HashMap<String, HashMap<Wordclass, u32>>,
+}
+
+impl LexiconBuilder {
+    pub fn new() -> LexiconBuilder {
+        LexiconBuilder::default()
+    }
+
+    /// Function to count the word/tag pairs of tagged `sentences`. Words tagged `Wordclass::ANY` (tags with no
+    /// `Wordclass`) are not counted.
+    pub fn add_sentences(&mut self, sentences: &[Vec<(String, Wordclass)>]) {
+        for (word, tag) in sentences.iter().flatten().filter(|(_, tag)| *tag != Wordclass::ANY) {
+            *self.counts.entry(word.clone()).or_default().entry(tag.clone()).or_default() += 1;
+        }
+    }
+
+    /// Function to count the words of the CoNLL-U file at `path` with their tags in `column`.
+    pub fn add_conllu_file(&mut self, path: &str, column: TagColumn) -> Result<(), Error> {
+        self.add_sentences(&tagged_sentences(&parse_conllu_file(path)?, column));
+        Ok(())
+    }
+
+    /// Function to count the words of the tagged text file at `path` (one sentence per line, as `word/TAG` tokens).
+    pub fn add_tagged_text_file(&mut self, path: &str) -> Result<(), Error> {
+        self.add_sentences(&parse_tagged_text(&read_to_string(path)?)?);
+        Ok(())
+    }
+
+    /// Function to count the words of the corpus at `path`: CoNLL-U (with the tags in `column`) if it ends in `.conllu`,
+    /// tagged text otherwise.
+    pub fn add_corpus_file(&mut self, path: &str, column: TagColumn) -> Result<(), Error> {
+        if path.ends_with(".conllu") { self.add_conllu_file(path, column) } else { self.add_tagged_text_file(path) }
+    }
+
+    /// Function to build the lexicon from the counts, applying the `cutoffs`. Each word's tags are ordered by how often
+    /// they were seen with it, most frequent first; ties go in Penn tag order.
+    pub fn build(&self, cutoffs: &LexiconCutoffs) -> WordclassMap {
+        self.build_weighted(cutoffs).to_wordclass_map()
+    }
+
+    /// Function to build the lexicon as `build` does, keeping the count of each tag.
+    pub fn build_weighted(&self, cutoffs: &LexiconCutoffs) -> WeightedLexicon {
+        let mut lexicon = WeightedLexicon::from_counts(&self.counts);
+        lexicon.entries.retain(|_, tags| tags.iter().map(|(_, count)| count.unwrap_or(0)).sum::<u32>() >= cutoffs.min_word_count);
+        for tags in lexicon.entries.values_mut() {
+            let mut rank = 0;
+            tags.retain(|(_, count)| {
+                rank += 1;
+                rank == 1 || count.unwrap_or(0) >= cutoffs.min_tag_count
+            });
+        }
+        lexicon
+    }
+}
+
+
+/// Function to parse tagged text: one sentence per line, each token written `word/TAG`. The tag follows the last `/`,
+/// so words may contain slashes (`1/2/CD`). Tags with no `Wordclass` become `Wordclass::ANY`.
+pub fn parse_tagged_text(contents: &str) -> Result<Vec<Vec<(String, Wordclass)>>, Error> {
+    contents.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split_whitespace().map(|token| {
+            let (word, tag) = token.rsplit_once('/')
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Expected word/TAG, found '{}'", token)))?;
+            Ok((word.to_string(), map_pos_tag(tag).unwrap_or(Wordclass::ANY)))
+        }).collect::<Result<Vec<_>, Error>>())
+        .collect()
+}
+
+
+/// Function to merge a lexicon `built` from corpus counts into an `existing` lexicon. The corpus decides the order of a
+/// word's tags; tags the existing lexicon lists but the corpus never showed are kept after them. Words only in one of
+/// the two are kept as they are.
+pub fn merge_lexicons(existing: &WordclassMap, built: &WordclassMap) -> WordclassMap {
+    let mut merged: WordclassMap = existing.clone();
+    for (word, tags) in built {
+        let mut entry = tags.clone();
+        for tag in existing.get(word).into_iter().flatten() {
+            if !entry.contains(tag) { entry.push(tag.clone()); }
+        }
+        merged.insert(word.clone(), entry);
+    }
+    merged
+}
+
+
+/// Function to build a lexicon from the corpora at `corpus_paths` (see `LexiconBuilder::add_corpus_file`), merged into
+/// the lexicon at `merge_with` if given.
+pub fn build_lexicon(corpus_paths: &[String], column: TagColumn, cutoffs: &LexiconCutoffs, merge_with: Option<&str>) -> Result<WordclassMap, io::Error> {
+    let mut builder = LexiconBuilder::new();
+    for path in corpus_paths {
+        builder.add_corpus_file(path, column)?;
+    }
+    let built = builder.build(cutoffs);
+    Ok(match merge_with {
+        Some(path) => merge_lexicons(&crate::initialize_tagger(path)?, &built),
+        None => built,
+    })
+}
+
+
+#[test]
+fn test_build_lexicon_from_counts() {
+    use crate::{initialize_tagger_from_str, lexicon_to_string};
+
+    let sentences = parse_tagged_text("I/PRP run/VBP ./.\nthe/DT run/NN was/VBD long/JJ\nthey/PRP run/VBP\n").unwrap();
+    let mut builder = LexiconBuilder::new();
+    builder.add_sentences(&sentences);
+    assert_eq!(builder.counts["run"][&Wordclass::VBP], 2);
+
+    let lexicon = builder.build(&LexiconCutoffs::default());
+    assert_eq!(lexicon["run"], vec![Wordclass::VBP, Wordclass::NN]);
+
+    // The written lexicon reads back as built.
+    let written = lexicon_to_string(&lexicon);
+    assert!(written.contains("run VBP NN\n"));
+    assert_eq!(initialize_tagger_from_str(&written), lexicon);
+
+    let cut = builder.build(&LexiconCutoffs { min_word_count: 2, min_tag_count: 2 });
+    assert_eq!(cut.len(), 1);
+    assert_eq!(cut["run"], vec![Wordclass::VBP]);
+    assert_eq!(builder.build_weighted(&LexiconCutoffs::default()).entries["run"], vec![(Wordclass::VBP, Some(2)), (Wordclass::NN, Some(1))]);
+
+    let existing = initialize_tagger_from_str("run VB NN\nwalk VB NN\n");
+    let merged = merge_lexicons(&existing, &cut);
+    assert_eq!(merged["run"], vec![Wordclass::VBP, Wordclass::VB, Wordclass::NN]);
+    assert_eq!(merged["walk"], vec![Wordclass::VB, Wordclass::NN]);
+
+    assert!(parse_tagged_text("run").is_err());
+}
+
+
+#[test]
+fn test_build_lexicon_from_conllu() {
+    let lexicon = build_lexicon(&[String::from("data/en_ewt-ud-test.conllu")], TagColumn::Xpos, &LexiconCutoffs::default(), None).unwrap();
+    assert_eq!(lexicon["the"][0], Wordclass::DT);
+    assert!(lexicon.values().all(|tags| !tags.is_empty() && !tags.contains(&Wordclass::ANY)));
+
+    let universal = build_lexicon(&[String::from("data/en_ewt-ud-test.conllu")], TagColumn::Upos, &LexiconCutoffs::default(), None).unwrap();
+    assert_eq!(universal["the"][0], Wordclass::DET);
+    assert!(universal["because"].contains(&Wordclass::SCONJ));
+}
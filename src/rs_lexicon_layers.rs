@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use crate::rs_lexicon_lookup::{lookup_sentence, LookupChain};
+use crate::rs_multiword::{is_tag_sequence, parse_multiword_entry};
+use crate::rs_wordclass::{map_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+// A lexicon layer changes the tags of some words of the layers below it. Each line names a word, an edit and tags:
+//
+//     Acme replace NNP        the word's tags become exactly these
+//     Acme add NN             these tags are added after the word's tags (or become its tags if it has none)
+//     Acme remove VB          these tags are removed from the word's tags
+//     Acme remove             the word is removed, and is unknown unless a later layer adds it back
+//     New_York remove         the multiword expression is removed
+//
+// A line with no edit (`Acme NNP`) replaces the word's tags, so an ordinary lexicon file is a layer that replaces every
+// word it lists. Edits are lower case, so they cannot be mistaken for tags. Multiword expressions are written as in a
+// lexicon (`New_York replace NNP+NNP`, see `rs_multiword`); they can be replaced or removed, but not added to. A word may
+// be edited on several lines of a layer, which are applied in order (`run add VBN` then `run remove NN`).
+
+
+/// An edit a layer makes to a word's tags.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerEdit {
+    Add(Vec<Wordclass>),
+    Replace(Vec<Wordclass>),
+    /// Remove these tags, or the whole word if there are none.
+    Remove(Vec<Wordclass>),
+}
+
+
+/// One layer of a lexicon stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexiconLayer {
+    pub name: String,
+    /// Each word's edits, in the order of their lines.
+    pub edits: HashMap<String, Vec<LayerEdit>>,
+}
+
+impl LexiconLayer {
+    /// Function to parse a layer from the `contents` of a layer file. Unknown edits and tags are errors, since a layer
+    /// that silently drops them would leave the base lexicon's tags in place.
+    pub fn parse(name: &str, contents: &str) -> Result<LexiconLayer, Error> {
+        let mut edits: HashMap<String, Vec<LayerEdit>> = HashMap::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let Some((word, rest)) = parts.split_first() else { continue };
+            let (edit, tag_names) = match rest.first() {
+                Some(&"add") | Some(&"replace") | Some(&"remove") => (rest[0], &rest[1..]),
+                Some(first) if first.chars().next().is_some_and(|c| c.is_lowercase()) => {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("{} line {}: unknown edit '{}'", name, line_number + 1, first)));
+                }
+                _ => ("replace", rest),
+            };
+            let (word, tags) = if is_tag_sequence(tag_names) {
+                if edit == "add" {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("{} line {}: multiword entries can only be replaced or removed", name, line_number + 1)));
+                }
+                let (key, tags) = parse_multiword_entry(word, tag_names[0])
+                    .map_err(|_| Error::new(ErrorKind::InvalidData, format!("{} line {}: bad multiword entry '{}'", name, line_number + 1, word)))?;
+                // Removing an expression's tag sequence removes the expression.
+                (key, if edit == "remove" { Vec::new() } else { tags })
+            } else {
+                let tags = tag_names.iter()
+                    .map(|tag| map_pos_tag(tag).ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} line {}: unknown tag '{}'", name, line_number + 1, tag))))
+                    .collect::<Result<Vec<Wordclass>, Error>>()?;
+                // Removing a word joined by `_` without its tag sequence removes the expression too.
+                let word = if tags.is_empty() && edit == "remove" { word.replace('_', " ") } else { word.to_string() };
+                (word, tags)
+            };
+            if tags.is_empty() && edit != "remove" {
+                return Err(Error::new(ErrorKind::InvalidData, format!("{} line {}: '{}' has no tags", name, line_number + 1, word)));
+            }
+            let edit = match edit {
+                "add" => LayerEdit::Add(tags),
+                "remove" => LayerEdit::Remove(tags),
+                _ => LayerEdit::Replace(tags),
+            };
+            edits.entry(word).or_default().push(edit);
+        }
+        Ok(LexiconLayer { name: name.to_string(), edits })
+    }
+
+    /// Function to read the layer file at `path`, naming the layer after the file.
+    pub fn from_file(path: &str) -> Result<LexiconLayer, Error> {
+        let name = Path::new(path).file_stem().map_or(path.to_string(), |stem| stem.to_string_lossy().to_string());
+        LexiconLayer::parse(&name, &fs::read_to_string(path)?)
+    }
+
+    /// Function to make a layer that replaces the tags of every word of `lexicon`, such as the base lexicon.
+    pub fn from_lexicon(name: &str, lexicon: &WordclassMap) -> LexiconLayer {
+        let edits = lexicon.iter().map(|(word, tags)| (word.clone(), vec![LayerEdit::Replace(tags.clone())])).collect();
+        LexiconLayer { name: name.to_string(), edits }
+    }
+}
+
+
+/// A tag of a word, with the index of the layer that supplied it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayeredTag {
+    pub tag: Wordclass,
+    pub layer: usize,
+}
+
+
+/// A stack of lexicon layers (for example base, then domain, then user), resolved bottom to top.
+#[derive(Debug, Clone, Default)]
+pub struct LexiconStack {
+    pub layers: Vec<LexiconLayer>,
+}
+
+impl LexiconStack {
+    pub fn new() -> LexiconStack {
+        LexiconStack::default()
+    }
+
+    /// Function to add `layer` on top of the stack.
+    pub fn push(&mut self, layer: LexiconLayer) {
+        self.layers.push(layer);
+    }
+
+    /// Function to resolve `word` through the layers in order, giving its tags (most likely first) and the layer that
+    /// supplied each, or `None` if no layer lists it or a layer removed it.
+    pub fn resolve(&self, word: &str) -> Option<Vec<LayeredTag>> {
+        let mut resolved: Option<Vec<LayeredTag>> = None;
+        let edits = self.layers.iter().enumerate()
+            .flat_map(|(index, layer)| layer.edits.get(word).into_iter().flatten().map(move |edit| (index, edit)));
+        for (layer, edit) in edits {
+            resolved = match edit {
+                LayerEdit::Replace(tags) => Some(tags.iter().map(|tag| LayeredTag { tag: tag.clone(), layer }).collect()),
+                LayerEdit::Add(tags) => {
+                    let mut current = resolved.unwrap_or_default();
+                    for tag in tags {
+                        if !current.iter().any(|layered| layered.tag == *tag) { current.push(LayeredTag { tag: tag.clone(), layer }); }
+                    }
+                    Some(current)
+                }
+                LayerEdit::Remove(tags) if tags.is_empty() => None,
+                LayerEdit::Remove(tags) => resolved
+                    .map(|current| current.into_iter().filter(|layered| !tags.contains(&layered.tag)).collect::<Vec<_>>())
+                    .filter(|current| !current.is_empty()),
+            };
+        }
+        resolved
+    }
+
+    /// Function to give the name of the layer that supplied `tag` for the lexicon `entry` (a word, or a multiword
+    /// expression kept under its words joined by spaces), if any did.
+    pub fn tag_source(&self, entry: &str, tag: &Wordclass) -> Option<&str> {
+        let layer = self.resolve(entry)?.into_iter().find(|layered| layered.tag == *tag)?.layer;
+        Some(&self.layers[layer].name)
+    }
+
+    /// Function to give, for each word of a `tagged` sentence, the name of the layer that supplied its tag. The words are
+    /// looked up in `lexicon` (the stack flattened by `to_wordclass_map`) with `chain`, as the tagger looks them up, so a
+    /// word found as a variant or as part of a multiword expression is credited to the layer that supplied that entry.
+    /// Words whose tag no layer supplied (unknown words, or tags the rules chose outside the lexicon) have `None`.
+    pub fn tag_sources(&self, tagged: &[(String, Wordclass)], lexicon: &WordclassMap, chain: &LookupChain) -> Vec<Option<String>> {
+        let tokens: Vec<String> = tagged.iter().map(|(word, _)| word.clone()).collect();
+        lookup_sentence(&tokens, lexicon, chain).into_iter().zip(tagged)
+            .map(|(lookup, (_, tag))| self.tag_source(lookup.entry.as_deref()?, tag).map(String::from))
+            .collect()
+    }
+
+    /// Function to flatten the stack into the `WordclassMap` the tagger uses.
+    pub fn to_wordclass_map(&self) -> WordclassMap {
+        let mut words: Vec<&String> = self.layers.iter().flat_map(|layer| layer.edits.keys()).collect();
+        words.sort();
+        words.dedup();
+        words.into_iter()
+            .filter_map(|word| self.resolve(word).map(|tags| (word.clone(), tags.into_iter().map(|layered| layered.tag).collect())))
+            .collect()
+    }
+}
+
+
+/// Function to build a stack from the lexicon at `base_path` and the layer files at `layer_paths`, in order.
+pub fn load_lexicon_stack(base_path: &str, layer_paths: &[String]) -> Result<LexiconStack, Error> {
+    let mut stack = LexiconStack::new();
+    stack.push(LexiconLayer::from_lexicon("base", &crate::initialize_tagger(base_path)?));
+    for path in layer_paths {
+        stack.push(LexiconLayer::from_file(path)?);
+    }
+    Ok(stack)
+}
+
+
+#[test]
+fn test_lexicon_stack() {
+    use crate::initialize_tagger_from_str;
+
+    let base = initialize_tagger_from_str("acme NN\nrun VB NN\nstat NN\nthe DT\n");
+    let mut stack = LexiconStack::new();
+    stack.push(LexiconLayer::from_lexicon("base", &base));
+    stack.push(LexiconLayer::parse("medical", "stat RB\nmg NN\nrun add VBN\n").unwrap());
+    stack.push(LexiconLayer::parse("user", "acme replace NNP\nrun remove NN\nthe remove\nmg add NNS\nNew_York NNP+NNP\n").unwrap());
+
+    assert_eq!(stack.resolve("stat").unwrap(), vec![LayeredTag { tag: Wordclass::RB, layer: 1 }]);
+    assert_eq!(stack.resolve("run").unwrap(), vec![LayeredTag { tag: Wordclass::VB, layer: 0 }, LayeredTag { tag: Wordclass::VBN, layer: 1 }]);
+    assert_eq!(stack.resolve("mg").unwrap().iter().map(|layered| layered.layer).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(stack.resolve("the"), None);
+    assert_eq!(stack.resolve("unlisted"), None);
+
+    let lexicon = stack.to_wordclass_map();
+    assert_eq!(lexicon["acme"], vec![Wordclass::NNP]);
+    assert!(!lexicon.contains_key("the"));
+    assert_eq!(lexicon["New York"], vec![Wordclass::NNP, Wordclass::NNP]);
+    assert_eq!(lexicon.len(), 5);
+
+    let tagged = vec![(String::from("acme"), Wordclass::NNP), (String::from("run"), Wordclass::VB), (String::from("stat"), Wordclass::NN)];
+    assert_eq!(stack.tag_sources(&tagged, &lexicon, &LookupChain::default()), [Some(String::from("user")), Some(String::from("base")), None]);
+
+    // With the lowercase step, `Acme` is found as `acme`, so its tag comes from the layer that supplied it for `acme`.
+    let tagged = vec![(String::from("Acme"), Wordclass::NNP)];
+    assert_eq!(stack.tag_sources(&tagged, &lexicon, &LookupChain::default()), [None]);
+    assert_eq!(stack.tag_sources(&tagged, &lexicon, &"exact,lowercase".parse().unwrap()), [Some(String::from("user"))]);
+
+    // The words of a multiword expression are credited to the layer that supplied the expression, not their own entries.
+    let tagged = vec![(String::from("New"), Wordclass::NNP), (String::from("York"), Wordclass::NNP)];
+    assert_eq!(stack.tag_sources(&tagged, &lexicon, &LookupChain::default()), [Some(String::from("user")), Some(String::from("user"))]);
+
+    // Several edits of a word in one layer are applied in order, rather than the last one replacing the others.
+    let mut edited = LexiconStack::new();
+    edited.push(LexiconLayer::from_lexicon("base", &base));
+    edited.push(LexiconLayer::parse("user", "run add VBN\nrun remove VB\n").unwrap());
+    assert_eq!(edited.resolve("run").unwrap(), vec![LayeredTag { tag: Wordclass::NN, layer: 0 }, LayeredTag { tag: Wordclass::VBN, layer: 1 }]);
+
+    assert!(LexiconLayer::parse("bad", "acme rename NNP\n").is_err());
+    assert!(LexiconLayer::parse("bad", "acme NNX\n").is_err());
+    assert!(LexiconLayer::parse("bad", "acme add\n").is_err());
+    assert!(LexiconLayer::parse("bad", "New_York add NNP+NNP\n").is_err());
+    assert_eq!(LexiconLayer::parse("user", "New_York remove NNP+NNP\n").unwrap().edits["New York"], [LayerEdit::Remove(Vec::new())]);
+    assert_eq!(LexiconLayer::parse("user", "New_York remove\n").unwrap().edits["New York"], [LayerEdit::Remove(Vec::new())]);
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use crate::rs_multiword::{is_tag_sequence, parse_multiword_entry, MultiwordError};
+use crate::rs_weighted_lexicon::split_tag_count;
+use crate::rs_wordclass::{map_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+
+/// What the lexicon loader does when a lexicon has problems.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Fail if the report finds any problem.
+    Strict,
+    /// Load what can be loaded: indented lines are loaded as if they were not indented, other malformed lines and unknown
+    /// tags are skipped, a duplicated word keeps its last line, and words left with no tags are left out, so the tagger
+    /// treats them as unknown.
+    #[default]
+    Lenient,
+}
+
+impl FromStr for LoadMode {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "strict" => Ok(LoadMode::Strict),
+            "lenient" => Ok(LoadMode::Lenient),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Unknown load mode '{}' (expected strict or lenient)", name))),
+        }
+    }
+}
+
+
+/// The problems found while loading a lexicon. Line numbers start at 1.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LexiconReport {
+    pub lines: usize,
+    pub words: usize,
+    /// Lines that are not a word followed by tags: indented lines (which lenient mode still loads), words with nothing
+    /// after them, and multiword entries whose words and tags do not pair up.
+    pub malformed_lines: Vec<(usize, String)>,
+    /// Each line's word and the tags on it that have no `Wordclass`.
+    pub unknown_tags: Vec<(usize, String, Vec<String>)>,
+    /// Each repeated word with the line it repeats and the line it first appeared on.
+    pub duplicate_words: Vec<(usize, String, usize)>,
+    /// Words none of whose tags were recognised, which are left out of the lexicon.
+    pub words_without_tags: Vec<(usize, String)>,
+}
+
+impl LexiconReport {
+    /// Function to tell whether the lexicon loaded without any problems.
+    pub fn is_clean(&self) -> bool {
+        self.malformed_lines.is_empty() && self.unknown_tags.is_empty() && self.duplicate_words.is_empty() && self.words_without_tags.is_empty()
+    }
+
+    /// Function to count each unknown tag over the whole lexicon, most frequent first.
+    pub fn unknown_tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, _, tags) in &self.unknown_tags {
+            for tag in tags { *counts.entry(tag).or_insert(0) += 1; }
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().map(|(tag, count)| (tag.to_string(), count)).collect();
+        counts.sort_by(|(tag_a, count_a), (tag_b, count_b)| count_b.cmp(count_a).then_with(|| tag_a.cmp(tag_b)));
+        counts
+    }
+}
+
+
+/// A lexicon line, as `parse_lexicon_line` reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexiconLine {
+    /// An empty line.
+    Blank,
+    /// A line that is not a word followed by tags, and cannot be loaded.
+    Malformed,
+    /// A word and its tags.
+    Entry(LexiconEntry),
+}
+
+/// A word read from a lexicon line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexiconEntry {
+    /// The word as written on the line.
+    pub word: String,
+    /// The key the word is kept under: multiword expressions are kept under their words joined by spaces.
+    pub key: String,
+    /// The recognised tags in lexicon order, with their counts where they have one.
+    pub tags: Vec<(Wordclass, Option<u32>)>,
+    /// The tags that have no `Wordclass`.
+    pub unknown: Vec<String>,
+    /// Whether the line was indented, which is reported but does not stop the word being loaded.
+    pub indented: bool,
+}
+
+
+/// Function to read one `line` of a lexicon. Every reader of lexicon files reads its lines with this, so they agree on
+/// what a line holds.
+pub fn parse_lexicon_line(line: &str) -> LexiconLine {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.is_empty() { return LexiconLine::Blank; }
+    if parts.len() < 2 { return LexiconLine::Malformed; }
+
+    let word = parts[0];
+    let (key, tags, unknown) = if is_tag_sequence(&parts[1..]) {
+        match parse_multiword_entry(word, parts[1]) {
+            Ok((key, tags)) => (key, tags.into_iter().map(|tag| (tag, None)).collect(), Vec::new()),
+            Err(MultiwordError::Malformed) => return LexiconLine::Malformed,
+            Err(MultiwordError::UnknownTags(unknown)) => (word.replace('_', " "), Vec::new(), unknown),
+        }
+    } else {
+        let mut tags = Vec::new();
+        let mut unknown = Vec::new();
+        for token in &parts[1..] {
+            let (tag, count) = split_tag_count(token);
+            match map_pos_tag(tag) {
+                Some(tag) => tags.push((tag, count)),
+                None => unknown.push(token.to_string()),
+            }
+        }
+        (word.to_string(), tags, unknown)
+    };
+    LexiconLine::Entry(LexiconEntry { word: word.to_string(), key, tags, unknown, indented: line.starts_with(char::is_whitespace) })
+}
+
+
+/// Function to load a lexicon from the `contents` of a lexicon file, reporting every problem found on the way. In strict
+/// mode any problem is an error, whose message is the formatted report.
+pub fn load_lexicon_from_str(contents: &str, mode: LoadMode) -> Result<(WordclassMap, LexiconReport), Error> {
+    let mut lexicon: WordclassMap = HashMap::new();
+    let mut report = LexiconReport::default();
+    let mut first_lines: HashMap<String, usize> = HashMap::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        report.lines = line_number;
+        let entry = match parse_lexicon_line(line) {
+            LexiconLine::Blank => continue,
+            LexiconLine::Malformed => {
+                report.malformed_lines.push((line_number, line.to_string()));
+                continue;
+            }
+            LexiconLine::Entry(entry) => entry,
+        };
+        if entry.indented { report.malformed_lines.push((line_number, line.to_string())); }
+
+        let LexiconEntry { word, key, tags, unknown, .. } = entry;
+        match first_lines.get(&key) {
+            Some(first_line) => report.duplicate_words.push((line_number, word.clone(), *first_line)),
+            None => { first_lines.insert(key.clone(), line_number); }
+        }
+        if !unknown.is_empty() { report.unknown_tags.push((line_number, word.clone(), unknown)); }
+        if tags.is_empty() {
+            report.words_without_tags.push((line_number, word));
+            lexicon.remove(&key);
+        } else {
+            lexicon.insert(key, tags.into_iter().map(|(tag, _)| tag).collect());
+        }
+    }
+    report.words = lexicon.len();
+
+    if mode == LoadMode::Strict && !report.is_clean() {
+        return Err(Error::new(ErrorKind::InvalidData, format_lexicon_report(&report)));
+    }
+    Ok((lexicon, report))
+}
+
+
+/// Function to load the lexicon at `path`, as `load_lexicon_from_str` does.
+pub fn load_lexicon(path: &str, mode: LoadMode) -> Result<(WordclassMap, LexiconReport), Error> {
+    load_lexicon_from_str(&fs::read_to_string(path)?, mode)
+}
+
+
+/// Function to format a lexicon report for the terminal: a summary line per kind of problem, and the first few lines of
+/// each.
+pub fn format_lexicon_report(report: &LexiconReport) -> String {
+    const SHOWN: usize = 5;
+    let mut text = format!("{} lines, {} words loaded\n", report.lines, report.words);
+
+    text += &format!("{} malformed lines\n", report.malformed_lines.len());
+    for (line, contents) in report.malformed_lines.iter().take(SHOWN) {
+        text += &format!("    line {}: {:?}\n", line, contents);
+    }
+
+    let unknown_counts: Vec<String> = report.unknown_tag_counts().iter().map(|(tag, count)| format!("{} ({})", tag, count)).collect();
+    text += &format!("{} lines with unknown tags: {}\n", report.unknown_tags.len(), unknown_counts.join(", "));
+    for (line, word, tags) in report.unknown_tags.iter().take(SHOWN) {
+        text += &format!("    line {}: {} {}\n", line, word, tags.join(" "));
+    }
+
+    text += &format!("{} duplicate words\n", report.duplicate_words.len());
+    for (line, word, first_line) in report.duplicate_words.iter().take(SHOWN) {
+        text += &format!("    line {}: {} (first on line {})\n", line, word, first_line);
+    }
+
+    text += &format!("{} words without tags\n", report.words_without_tags.len());
+    for (line, word) in report.words_without_tags.iter().take(SHOWN) {
+        text += &format!("    line {}: {}\n", line, word);
+    }
+    text
+}
+
+
+#[test]
+fn test_lexicon_report() {
+    use crate::rs_wordclass::Wordclass;
+
+    let contents = "    sat VBD\nwalk VB NN:3\n-LRB- (\nstray\nrun NN VB\nrun VB :\n\ncat NN\n";
+    let (lexicon, report) = load_lexicon_from_str(contents, LoadMode::Lenient).unwrap();
+    assert_eq!(report.lines, 8);
+    assert_eq!(report.malformed_lines, [(1, String::from("    sat VBD")), (4, String::from("stray"))]);
+    assert_eq!(report.unknown_tags, [(3, String::from("-LRB-"), vec![String::from("(")]), (6, String::from("run"), vec![String::from(":")])]);
+    assert_eq!(report.duplicate_words, [(6, String::from("run"), 5)]);
+    assert_eq!(report.words_without_tags, [(3, String::from("-LRB-"))]);
+    assert_eq!(report.unknown_tag_counts(), [(String::from("("), 1), (String::from(":"), 1)]);
+
+    // The last line for a word wins, and words with no tags are left out rather than loaded with none. The indented line
+    // is reported, but still loaded.
+    assert_eq!(lexicon["run"], vec![Wordclass::VB]);
+    assert_eq!(lexicon["walk"], vec![Wordclass::VB, Wordclass::NN]);
+    assert!(!lexicon.contains_key("-LRB-"));
+    assert_eq!(lexicon["sat"], vec![Wordclass::VBD]);
+    assert_eq!(report.words, 4);
+
+    let error = load_lexicon_from_str(contents, LoadMode::Strict).unwrap_err();
+    assert!(error.to_string().contains("2 malformed lines"));
+    let (_, clean) = load_lexicon_from_str("walk VB NN\ncat NN\n", LoadMode::Strict).unwrap();
+    assert!(clean.is_clean());
+
+    let (lexicon, report) = load_lexicon_from_str("in_spite_of IN+NN+IN\nNew_York NNP+NNP+NNP\nas_well RB+XX\n", LoadMode::Lenient).unwrap();
+    assert_eq!(lexicon["in spite of"], vec![Wordclass::IN, Wordclass::NN, Wordclass::IN]);
+    assert_eq!(report.malformed_lines.len(), 1);
+    assert_eq!(report.unknown_tags, [(3, String::from("as_well"), vec![String::from("XX")])]);
+    assert!("loose".parse::<LoadMode>().is_err());
+}
@@ -0,0 +1,198 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::rs_multiword::match_multiwords;
+use crate::rs_wordclass::Wordclass;
+use crate::WordclassMap;
+
+
+/// One way of finding a word in the lexicon. Each step looks up a single variant of the word as it appears in the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LookupStep {
+    /// The word as it is.
+    Exact,
+    /// The word in lower case (`The` → `the`).
+    Lowercase,
+    /// The word with its first letter in upper case and the rest in lower case (`NASA` → `Nasa`).
+    Titlecase,
+    /// The word with its hyphens removed (`e-mail` → `email`).
+    Dehyphenated,
+    /// The word without its trailing punctuation (`etc.` → `etc`).
+    TrailingPunctuation,
+    /// The part of the word after its last hyphen (`search-engine` → `engine`).
+    LastHyphenComponent,
+    /// The word is part of a multiword expression (see `rs_multiword`), which is matched before any other step and is
+    /// not part of a chain.
+    Multiword,
+}
+
+impl LookupStep {
+    /// Every step, in the order the full chain tries them.
+    pub const ALL: [LookupStep; 6] = [LookupStep::Exact, LookupStep::Lowercase, LookupStep::Titlecase, LookupStep::Dehyphenated,
+                                      LookupStep::TrailingPunctuation, LookupStep::LastHyphenComponent];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LookupStep::Exact => "exact",
+            LookupStep::Lowercase => "lowercase",
+            LookupStep::Titlecase => "titlecase",
+            LookupStep::Dehyphenated => "dehyphenated",
+            LookupStep::TrailingPunctuation => "trailing-punctuation",
+            LookupStep::LastHyphenComponent => "last-hyphen-component",
+            LookupStep::Multiword => "multiword",
+        }
+    }
+
+    /// Function to give the variant of `word` this step looks up, or `None` if the step does not apply to it (it would
+    /// look up the word unchanged, or nothing at all).
+    pub fn variant<'a>(&self, word: &'a str) -> Option<Cow<'a, str>> {
+        let variant: Cow<str> = match self {
+            LookupStep::Exact => return Some(Cow::Borrowed(word)),
+            LookupStep::Multiword => return None,
+            LookupStep::Lowercase => Cow::Owned(word.to_lowercase()),
+            LookupStep::Titlecase => {
+                let mut chars = word.chars();
+                let first = chars.next()?;
+                Cow::Owned(first.to_uppercase().chain(chars.as_str().to_lowercase().chars()).collect())
+            }
+            LookupStep::Dehyphenated => Cow::Owned(word.replace('-', "")),
+            LookupStep::TrailingPunctuation => Cow::Borrowed(word.trim_end_matches(|c: char| c.is_ascii_punctuation())),
+            LookupStep::LastHyphenComponent => Cow::Borrowed(word.rsplit_once('-').map_or(word, |(_, last)| last)),
+        };
+        (!variant.is_empty() && variant != word).then_some(variant)
+    }
+}
+
+impl fmt::Display for LookupStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for LookupStep {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        LookupStep::ALL.into_iter().find(|step| step.name() == name.to_ascii_lowercase())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Unknown lookup step '{}'", name)))
+    }
+}
+
+
+/// The steps tried, in order, to find a word's tags in the lexicon; the first step that finds the word supplies its tags.
+/// By default only the exact word is looked up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LookupChain {
+    pub steps: Vec<LookupStep>,
+}
+
+impl Default for LookupChain {
+    fn default() -> Self {
+        LookupChain { steps: vec![LookupStep::Exact] }
+    }
+}
+
+impl LookupChain {
+    /// Function to give the chain trying every step, in the order of `LookupStep::ALL`.
+    pub fn full() -> LookupChain {
+        LookupChain { steps: LookupStep::ALL.to_vec() }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == LookupChain::default()
+    }
+}
+
+impl FromStr for LookupChain {
+    type Err = Error;
+
+    /// Function to read a chain as `full`, or as step names separated by commas (`exact,lowercase`).
+    fn from_str(names: &str) -> Result<Self, Self::Err> {
+        if names.eq_ignore_ascii_case("full") { return Ok(LookupChain::full()); }
+        Ok(LookupChain { steps: names.split(',').map(|name| name.trim().parse()).collect::<Result<_, _>>()? })
+    }
+}
+
+
+/// A token's tags as found in the lexicon, with the step that found them and the entry they were found under. Tokens no
+/// step found have the tags `[ANY]`, no step and no entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenLookup {
+    pub word: String,
+    pub tags: Vec<Wordclass>,
+    pub step: Option<LookupStep>,
+    /// The lexicon entry that supplied the tags: the variant of the word the step looked up, or the multiword
+    /// expression the token is part of.
+    pub entry: Option<String>,
+}
+
+
+/// Function to look `word` up in `lexicon` with each step of `chain` in turn. Entries of `[ANY]` (unknown words cached
+/// by the tagger, or tags with no `Wordclass`) do not count as found.
+pub fn lookup_word(word: &str, lexicon: &WordclassMap, chain: &LookupChain) -> TokenLookup {
+    for step in &chain.steps {
+        let Some(variant) = step.variant(word) else { continue };
+        if let Some(tags) = lexicon.get(variant.as_ref()).filter(|tags| !tags.is_empty() && tags.as_slice() != [Wordclass::ANY]) {
+            return TokenLookup { word: word.to_string(), tags: tags.clone(), step: Some(*step), entry: Some(variant.into_owned()) };
+        }
+    }
+    TokenLookup { word: word.to_string(), tags: vec![Wordclass::ANY], step: None, entry: None }
+}
+
+
+/// Function to look up the tokens of a sentence in `lexicon`: first the multiword expressions, matched longest first
+/// (also in lower case if `chain` has the lowercase step), whose tokens take their tag from the expression; then each
+/// other token with `chain`.
+pub fn lookup_sentence(tokens: &[String], lexicon: &WordclassMap, chain: &LookupChain) -> Vec<TokenLookup> {
+    let multiwords = match_multiwords(tokens, lexicon, chain.steps.contains(&LookupStep::Lowercase));
+    tokens.iter().zip(multiwords).map(|(token, multiword)| match multiword {
+        Some((key, tag)) => TokenLookup { word: token.clone(), tags: vec![tag], step: Some(LookupStep::Multiword), entry: Some(key) },
+        None => lookup_word(token, lexicon, chain),
+    }).collect()
+}
+
+
+/// Function to look up each of `tokens` in `lexicon`, as `lookup_sentence` does. Tokens no step finds are cached in the
+/// lexicon as `[ANY]`, as the tagger has always done.
+pub fn lookup_tokens(tokens: &[String], lexicon: &mut WordclassMap, chain: &LookupChain) -> Vec<TokenLookup> {
+    let lookups = lookup_sentence(tokens, lexicon, chain);
+    for lookup in lookups.iter().filter(|lookup| lookup.step.is_none()) {
+        lexicon.entry(lookup.word.clone()).or_insert_with(|| vec![Wordclass::ANY]);
+    }
+    lookups
+}
+
+
+#[test]
+fn test_lookup_chain() {
+    use crate::initialize_tagger_from_str;
+
+    let mut lexicon = initialize_tagger_from_str("the DT\nNasa NNP\nemail NN VB\netc FW\nengine NN\nsearch VB NN\n");
+    let chain = LookupChain::full();
+    let steps = |words: &[&str], lexicon: &mut WordclassMap, chain: &LookupChain| -> Vec<Option<LookupStep>> {
+        lookup_tokens(&words.iter().map(|word| word.to_string()).collect::<Vec<_>>(), lexicon, chain).into_iter().map(|lookup| lookup.step).collect()
+    };
+
+    assert_eq!(steps(&["the", "The", "NASA", "e-mail", "etc.", "search-engine", "blorp"], &mut lexicon, &chain), [
+        Some(LookupStep::Exact), Some(LookupStep::Lowercase), Some(LookupStep::Titlecase), Some(LookupStep::Dehyphenated),
+        Some(LookupStep::TrailingPunctuation), Some(LookupStep::LastHyphenComponent), None,
+    ]);
+    assert_eq!(lookup_word("search-engine", &lexicon, &chain).tags, vec![Wordclass::NN]);
+    assert_eq!(lookup_word("search-engine", &lexicon, &chain).entry.as_deref(), Some("engine"));
+
+    // The default chain only looks up the exact word; words it cannot find are cached as unknown, but a cached entry
+    // does not stop a longer chain from finding the word later.
+    assert_eq!(steps(&["The", "blorp"], &mut lexicon, &LookupChain::default()), [None, None]);
+    assert_eq!(lexicon["The"], vec![Wordclass::ANY]);
+    assert_eq!(lookup_word("The", &lexicon, &chain).step, Some(LookupStep::Lowercase));
+
+    assert_eq!("exact,lowercase".parse::<LookupChain>().unwrap().steps, [LookupStep::Exact, LookupStep::Lowercase]);
+    assert_eq!("full".parse::<LookupChain>().unwrap(), chain);
+    assert!("exact,stem".parse::<LookupChain>().is_err());
+    assert_eq!(LookupStep::Titlecase.variant("Nasa"), None);
+    assert_eq!(LookupStep::LastHyphenComponent.variant("well-"), None);
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use crate::rs_brill_tagger::{TaggerModel, TaggerSettings};
+use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+use crate::rs_contractions::parse_contractions;
+use crate::rs_lexical_ruleset::parse_lexical_ruleset_from_str;
+use crate::rs_ruleset_writer::{contextual_ruleset_to_string, lexical_ruleset_to_string};
+use crate::{initialize_tagger_from_str, lexicon_to_string};
+
+
+/// Version of the bundle layout written by this build. Bundles of any other version are refused when loading.
+pub const MODEL_BUNDLE_FORMAT_VERSION: u32 = 1;
+
+
+/// A single-file model: the checksummed `payload`, tagged with the layout version it was written in. The payload is kept
+/// as raw JSON, so the checksum is taken over the same bytes that are written and read.
+#[derive(Debug, Serialize, Deserialize)]
+struct ModelBundle {
+    format_version: u32,
+    checksum: String,
+    payload: Box<RawValue>,
+}
+
+
+/// The contents of a model bundle. The lexicon and rulesets are kept in their text formats, so a bundle can be inspected
+/// (and unpacked) with nothing more than a JSON viewer.
+#[derive(Debug, Serialize, Deserialize)]
+struct ModelPayload {
+    settings: TaggerSettings,
+    lexicon: String,
+    lexical_rules: String,
+    contextual_rules: String,
+    contractions: BTreeMap<String, Vec<String>>,
+}
+
+
+/// Function to compute the 64-bit FNV-1a hash of `data`, written as 16 hex digits.
+fn fnv1a_checksum(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+
+/// Function to wrap a `payload` in a bundle, with the current format version and the checksum of its serialisation.
+fn bundle_payload(payload: ModelPayload) -> Result<String, Error> {
+    let serialised = serde_json::to_string_pretty(&payload).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let checksum = fnv1a_checksum(serialised.as_bytes());
+    let payload = RawValue::from_string(serialised).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let bundle = ModelBundle { format_version: MODEL_BUNDLE_FORMAT_VERSION, checksum, payload };
+    serde_json::to_string_pretty(&bundle).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+
+/// Function to build a bundle from the separate lexicon, rule and contraction files the tagger is usually loaded from.
+/// The files are stored as they are, so nothing is lost even where the tag mapping is lossy (e.g. punctuation tags).
+pub fn create_model_bundle(lexicon_path: &str, lexical_path: &str, contextual_path: &str, contractions_path: &str, settings: TaggerSettings) -> Result<String, Error> {
+    let contractions = parse_contractions(&fs::read_to_string(contractions_path)?)?;
+    bundle_payload(ModelPayload {
+        settings,
+        lexicon: fs::read_to_string(lexicon_path)?,
+        lexical_rules: fs::read_to_string(lexical_path)?,
+        contextual_rules: fs::read_to_string(contextual_path)?,
+        contractions: contractions.into_iter().collect(),
+    })
+}
+
+
+/// Function to build a bundle from an in-memory `model`, e.g. after rules have been learned or edited.
+pub fn model_to_bundle(model: &TaggerModel) -> Result<String, Error> {
+    bundle_payload(ModelPayload {
+        settings: model.settings.clone(),
+        lexicon: lexicon_to_string(&model.lexicon),
+        lexical_rules: lexical_ruleset_to_string(&model.lexical_ruleset),
+        contextual_rules: contextual_ruleset_to_string(&model.contextual_rules),
+        contractions: model.contractions.clone().into_iter().collect(),
+    })
+}
+
+
+/// Function to write an in-memory `model` to a bundle file at `path`.
+pub fn save_model_bundle(path: &str, model: &TaggerModel) -> Result<(), Error> {
+    fs::write(path, model_to_bundle(model)?)
+}
+
+
+/// Function to load a tagger model from the `contents` of a bundle, checking its version and checksum first.
+pub fn load_model_bundle_from_str(contents: &str) -> Result<TaggerModel, Error> {
+    let bundle: ModelBundle = serde_json::from_str(contents).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    if bundle.format_version != MODEL_BUNDLE_FORMAT_VERSION {
+        return Err(Error::new(ErrorKind::InvalidData, format!(
+            "Model bundle format version {} is not the supported version {}", bundle.format_version, MODEL_BUNDLE_FORMAT_VERSION)));
+    }
+    let checksum = fnv1a_checksum(bundle.payload.get().as_bytes());
+    if checksum != bundle.checksum {
+        return Err(Error::new(ErrorKind::InvalidData, format!(
+            "Model bundle checksum mismatch: expected {}, computed {}", bundle.checksum, checksum)));
+    }
+    let payload: ModelPayload = serde_json::from_str(bundle.payload.get()).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    Ok(TaggerModel::new(
+        initialize_tagger_from_str(&payload.lexicon),
+        parse_lexical_ruleset_from_str(&payload.lexical_rules)?,
+        parse_contextual_rules_from_str(&payload.contextual_rules)?,
+        payload.contractions.into_iter().collect(),
+        payload.settings,
+    ))
+}
+
+
+/// Function to load a tagger model from the bundle file at `path`.
+pub fn load_model_bundle(path: &str) -> Result<TaggerModel, Error> {
+    load_model_bundle_from_str(&fs::read_to_string(path)?)
+}
+
+
+/// Test that a bundle built from the data files loads into the same model as the separate files.
+#[test]
+fn test_model_bundle_round_trip() {
+    use crate::rs_brill_tagger::tag_sentence_with_model;
+
+    let bundle = create_model_bundle("data/lexicon.txt", "data/rulefile_lexical.txt", "data/rulefile_contextual.txt",
+                                     "data/contractions.json", TaggerSettings::default()).unwrap();
+    let mut bundled = load_model_bundle_from_str(&bundle).unwrap();
+    let mut separate = TaggerModel::from_files("data/lexicon.txt", "data/rulefile_lexical.txt",
+                                               "data/rulefile_contextual.txt", "data/contractions.json").unwrap();
+
+    assert_eq!(bundled.lexicon, separate.lexicon);
+    assert_eq!(bundled.lexical_ruleset, separate.lexical_ruleset);
+    assert_eq!(bundled.contextual_rules, separate.contextual_rules);
+    assert_eq!(bundled.contractions, separate.contractions);
+
+    let sentence = "It's a little confusing";
+    assert_eq!(tag_sentence_with_model(sentence, &mut bundled), tag_sentence_with_model(sentence, &mut separate));
+
+    // Re-bundling the loaded model keeps the rules and settings.
+    let rebundled = load_model_bundle_from_str(&model_to_bundle(&bundled).unwrap()).unwrap();
+    assert_eq!(rebundled.contextual_rules, bundled.contextual_rules);
+    assert_eq!(rebundled.settings, bundled.settings);
+}
+
+
+/// Test that tampered bundles, and bundles of another version, are refused.
+#[test]
+fn test_model_bundle_rejects_bad_bundles() {
+    let model = TaggerModel::new(initialize_tagger_from_str("walk VB NN\n"), Vec::new(),
+                                 parse_contextual_rules_from_str("NN VB PREVTAG TO\n").unwrap(),
+                                 Default::default(), TaggerSettings::default());
+    let bundle = model_to_bundle(&model).unwrap();
+    assert!(load_model_bundle_from_str(&bundle).is_ok());
+
+    let tampered = bundle.replace("PREVTAG TO", "PREVTAG MD");
+    let error = load_model_bundle_from_str(&tampered).err().unwrap();
+    assert!(error.to_string().contains("checksum"), "{}", error);
+
+    // The checksum covers the payload's bytes as written, so even a change serde would read back the same is caught.
+    let reformatted = bundle.replacen("\"settings\": {", "\"settings\":  {", 1);
+    assert_ne!(reformatted, bundle);
+    assert!(load_model_bundle_from_str(&reformatted).err().unwrap().to_string().contains("checksum"));
+
+    for version in [0, 2] {
+        let other = bundle.replace("\"format_version\": 1", &format!("\"format_version\": {}", version));
+        assert_ne!(other, bundle);
+        let error = load_model_bundle_from_str(&other).err().unwrap();
+        assert!(error.to_string().contains("not the supported version"), "{}", error);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::rs_wordclass::{map_pos_tag, wordclass_to_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+// A multiword expression is written in a lexicon as its words joined by `_`, then one tag per word joined by `+`:
+//
+//     in_spite_of IN+NN+IN
+//     New_York NNP+NNP
+//
+// In a `WordclassMap` it is kept under its words joined by spaces (`"in spite of"`), with the tag sequence as its tags.
+// Tokens never contain spaces, so an expression cannot be mistaken for a single word. Before the rules run, the tagger
+// matches expressions over the tokens of a sentence, longest first, and gives each token its tag from the sequence; the
+// tokens stay separate, so the rules see them (and their neighbours) one by one.
+
+
+/// The most words an expression may have. Longer entries are rejected when a lexicon is loaded.
+pub const MAX_MULTIWORD_LEN: usize = 8;
+
+
+/// Why a lexicon entry could not be read as a multiword expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultiwordError {
+    /// The words and tags do not pair up, or there are too few or too many of them.
+    Malformed,
+    /// These tags have no `Wordclass`.
+    UnknownTags(Vec<String>),
+}
+
+
+/// Function to tell whether a lexicon line's tags are a multiword tag sequence (`IN+NN+IN`) rather than a word's tags.
+pub fn is_tag_sequence(tags: &[&str]) -> bool {
+    tags.len() == 1 && tags[0].contains('+')
+}
+
+
+/// Function to read the multiword entry `words` (joined by `_`) with the tag `sequence` (joined by `+`), giving its
+/// `WordclassMap` key and tags.
+pub fn parse_multiword_entry(words: &str, sequence: &str) -> Result<(String, Vec<Wordclass>), MultiwordError> {
+    let words: Vec<&str> = words.split('_').collect();
+    let tag_names: Vec<&str> = sequence.split('+').collect();
+    if words.len() < 2 || words.len() > MAX_MULTIWORD_LEN || words.len() != tag_names.len() || words.iter().any(|word| word.is_empty()) {
+        return Err(MultiwordError::Malformed);
+    }
+    let unknown: Vec<String> = tag_names.iter().filter(|tag| map_pos_tag(tag).is_none()).map(|tag| tag.to_string()).collect();
+    if !unknown.is_empty() { return Err(MultiwordError::UnknownTags(unknown)); }
+    Ok((words.join(" "), tag_names.iter().filter_map(|tag| map_pos_tag(tag)).collect()))
+}
+
+
+/// Function to write the multiword entry kept under `key` with `tags` as a lexicon line's word and tags.
+pub fn format_multiword_entry(key: &str, tags: &[Wordclass]) -> String {
+    format!("{} {}", key.replace(' ', "_"), tags.iter().map(wordclass_to_pos_tag).collect::<Vec<_>>().join("+"))
+}
+
+
+/// Function to match the multiword expressions of `lexicon` over `tokens`, greedily from the left, taking the longest
+/// expression starting at each token. Gives each token the key of the expression it is part of, if any, and its tag from
+/// the expression. With `lowercase`, an expression also matches the tokens in lower case (`In spite of`).
+pub fn match_multiwords(tokens: &[String], lexicon: &WordclassMap, lowercase: bool) -> Vec<Option<(String, Wordclass)>> {
+    let mut matched = vec![None; tokens.len()];
+    let mut start = 0;
+    while start < tokens.len() {
+        let longest = (2..=MAX_MULTIWORD_LEN.min(tokens.len() - start)).rev().find_map(|len| {
+            let key = tokens[start..start + len].join(" ");
+            let (key, tags) = match lexicon.get(&key) {
+                Some(tags) => (key, tags),
+                None if lowercase => {
+                    let key = key.to_lowercase();
+                    let tags = lexicon.get(&key)?;
+                    (key, tags)
+                }
+                None => return None,
+            };
+            (tags.len() == len).then_some((key, tags))
+        });
+        match longest {
+            Some((key, tags)) => {
+                for (offset, tag) in tags.iter().enumerate() { matched[start + offset] = Some((key.clone(), tag.clone())); }
+                start += tags.len();
+            }
+            None => start += 1,
+        }
+    }
+    matched
+}
+
+
+#[test]
+fn test_match_multiwords() {
+    use crate::initialize_tagger_from_str;
+
+    let lexicon = initialize_tagger_from_str("as RB IN\nas_well RB+RB\nas_well_as CC+RB+IN\nNew_York NNP+NNP\nin_spite_of IN+NN+IN\n");
+    assert_eq!(lexicon["as well as"], vec![Wordclass::CC, Wordclass::RB, Wordclass::IN]);
+    assert_eq!(format_multiword_entry("in spite of", &lexicon["in spite of"]), "in_spite_of IN+NN+IN");
+    assert_eq!(initialize_tagger_from_str(&crate::lexicon_to_string(&lexicon)), lexicon);
+
+    let tokens: Vec<String> = "cats as well as dogs as well in spite of it".split(' ').map(String::from).collect();
+    let matched: Vec<Option<Wordclass>> = match_multiwords(&tokens, &lexicon, false).into_iter().map(|matched| matched.map(|(_, tag)| tag)).collect();
+    assert_eq!(matched[..5], [None, Some(Wordclass::CC), Some(Wordclass::RB), Some(Wordclass::IN), None]);
+    assert_eq!(matched[5..], [Some(Wordclass::RB), Some(Wordclass::RB), Some(Wordclass::IN), Some(Wordclass::NN), Some(Wordclass::IN), None]);
+    assert_eq!(match_multiwords(&tokens, &lexicon, false)[5], Some((String::from("as well"), Wordclass::RB)));
+
+    let tokens: Vec<String> = ["In", "spite", "of"].iter().map(|word| word.to_string()).collect();
+    assert_eq!(match_multiwords(&tokens, &lexicon, false), [None, None, None]);
+    assert_eq!(match_multiwords(&tokens, &lexicon, true)[0], Some((String::from("in spite of"), Wordclass::IN)));
+
+    assert_eq!(parse_multiword_entry("New_York", "NNP"), Err(MultiwordError::Malformed));
+    assert_eq!(parse_multiword_entry("New__York", "NNP+NNP+NNP"), Err(MultiwordError::Malformed));
+    assert_eq!(parse_multiword_entry("New_York", "NNP+NPX"), Err(MultiwordError::UnknownTags(vec![String::from("NPX")])));
+}
+
+
+#[test]
+fn test_multiwords_tagged_before_rules() {
+    use std::collections::HashMap;
+    use crate::initialize_tagger_from_str;
+    use crate::rs_brill_tagger::{tag_sentence_with_model, TaggerModel, TaggerSettings};
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let lexicon = initialize_tagger_from_str("cats NNS\nas RB IN\nwell RB NN\nas_well_as CC+RB+IN\nrun NN VB\n");
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG IN\nCC RB NEXTTAG RB\nRB IN PREVTAG RB\n").unwrap();
+    let settings = TaggerSettings::default();
+    let mut model = TaggerModel::new(lexicon, Vec::new(), rules, HashMap::new(), settings);
+
+    // The expression's tokens keep their tags from the sequence (the first `as` stays CC before RB), while the rules still
+    // see them one by one: the last `as` is tagged IN, so `run` after it is retagged VB. Outside the expression, `as` is
+    // free to be retagged.
+    let tagged = tag_sentence_with_model("cats as well as run", &mut model);
+    assert_eq!(tagged.iter().map(|(_, tag)| tag.clone()).collect::<Vec<_>>(),
+               [Wordclass::NNS, Wordclass::CC, Wordclass::RB, Wordclass::IN, Wordclass::VB]);
+    let tagged = tag_sentence_with_model("well as run", &mut model);
+    assert_eq!(tagged.iter().map(|(_, tag)| tag.clone()).collect::<Vec<_>>(), [Wordclass::RB, Wordclass::IN, Wordclass::VB]);
+}
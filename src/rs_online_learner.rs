@@ -0,0 +1,154 @@
+use std::io::{Error, ErrorKind};
+use crate::rs_brill_tagger::{tag_tokens_with_possible_tags, PossibleTags, TaggerModel};
+use crate::rs_contextual_learner::{learn_contextual_rules, ContextualLearnerSettings, TrainingCorpus};
+use crate::rs_contextual_rulespec::ContextualRulespec;
+use crate::rs_wordclass::Wordclass;
+
+
+/// One sentence as the tagger tagged it and as an annotator corrected it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Correction {
+    pub words: Vec<String>,
+    pub predicted: Vec<Wordclass>,
+    /// The tags the tagger could give each word (as its lookup chain and multiword expressions found them), which bound
+    /// the rules proposed for it.
+    pub possible: Vec<Vec<Wordclass>>,
+    pub corrected: Vec<Wordclass>,
+}
+
+
+/// The corrections collected so far. Sentences the annotator left as they were are kept too, as evidence of what the
+/// tagger already gets right.
+#[derive(Debug, Clone, Default)]
+pub struct CorrectionLog {
+    pub corrections: Vec<Correction>,
+}
+
+impl CorrectionLog {
+    pub fn new() -> CorrectionLog {
+        CorrectionLog::default()
+    }
+
+    /// Function to record a sentence with the tags the tagger `predicted`, the tags it could have given each word, and the
+    /// tags it was `corrected` to.
+    pub fn record(&mut self, words: Vec<String>, predicted: Vec<Wordclass>, possible: Vec<Vec<Wordclass>>, corrected: Vec<Wordclass>) -> Result<(), Error> {
+        if words.len() != predicted.len() || words.len() != possible.len() || words.len() != corrected.len() {
+            return Err(Error::new(ErrorKind::InvalidInput, format!(
+                "Correction has {} words but {} predicted, {} possible and {} corrected tags", words.len(), predicted.len(), possible.len(), corrected.len())));
+        }
+        self.corrections.push(Correction { words, predicted, possible, corrected });
+        Ok(())
+    }
+
+    /// Function to record an `annotated` sentence: `model` tags its words for the prediction and their possible tags, and
+    /// the annotated tags are the correction.
+    pub fn record_annotated(&mut self, model: &mut TaggerModel, annotated: &[(String, Wordclass)]) -> Result<(), Error> {
+        let words: Vec<String> = annotated.iter().map(|(word, _)| word.clone()).collect();
+        let (tagged, possible) = tag_tokens_with_possible_tags(words.clone(), &model.lexical_ruleset, &model.contextual_ruleset, &mut model.lexicon, &model.settings);
+        self.record(words, tagged.into_iter().map(|(_, tag)| tag).collect(), possible.into_iter().map(|(_, tags)| tags).collect(),
+                    annotated.iter().map(|(_, tag)| tag.clone()).collect())
+    }
+
+    /// Function to count the words whose prediction was corrected.
+    pub fn corrected_words(&self) -> usize {
+        self.corrections.iter()
+            .map(|correction| correction.predicted.iter().zip(&correction.corrected).filter(|(predicted, corrected)| predicted != corrected).count())
+            .sum()
+    }
+
+    /// Function to build a training corpus from the history: the predictions as the current tags, the corrections as the
+    /// gold tags, and the possible tags the tagger found for each word.
+    fn corpus(&self) -> TrainingCorpus {
+        let gold: Vec<Vec<(String, Wordclass)>> = self.corrections.iter()
+            .map(|correction| correction.words.iter().cloned().zip(correction.corrected.iter().cloned()).collect())
+            .collect();
+        let mut predictions = self.corrections.iter().map(|correction| {
+            let tagged: Vec<(String, Wordclass)> = correction.words.iter().cloned().zip(correction.predicted.iter().cloned()).collect();
+            let possible: PossibleTags = correction.words.iter().cloned().zip(correction.possible.iter().cloned()).collect();
+            (tagged, possible)
+        });
+        TrainingCorpus::from_tagger(&gold, |_| predictions.next().unwrap_or_default())
+    }
+
+    /// Function to give the default settings for proposing rules: a rule must account for at least two net corrections,
+    /// and may not break any word of the history that is tagged right.
+    pub fn default_settings() -> ContextualLearnerSettings {
+        ContextualLearnerSettings {
+            max_errors_introduced: Some(0),
+            source_corpus: Some(String::from("corrections")),
+            ..ContextualLearnerSettings::default()
+        }
+    }
+
+    /// Function to propose contextual rules that would fix recurring corrections, in the order they should be applied.
+    /// Each rule is scored against the whole history with the proposals before it applied, so the words it would break
+    /// are counted against it (and limited by `settings.max_errors_introduced`).
+    pub fn propose_rules(&self, settings: &ContextualLearnerSettings) -> Vec<ContextualRulespec> {
+        learn_contextual_rules(&mut self.corpus(), settings)
+    }
+
+    /// Function to append accepted `rules` to the live `model`, and apply them to the recorded predictions so the
+    /// corrections they account for are not proposed again.
+    pub fn accept_rules(&mut self, model: &mut TaggerModel, rules: &[ContextualRulespec]) {
+        model.append_contextual_rules(rules);
+        let mut corpus = self.corpus();
+        for rule in rules {
+            corpus.apply_rule(rule);
+        }
+        for (correction, sentence) in self.corrections.iter_mut().zip(corpus.sentences) {
+            correction.predicted = sentence.into_iter().map(|(_, tag)| tag).collect();
+        }
+    }
+}
+
+
+#[test]
+fn test_propose_rules_from_corrections() {
+    use std::collections::HashMap;
+    use crate::initialize_tagger_from_str;
+    use crate::rs_brill_tagger::{tag_sentence_with_model, TaggerSettings};
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let lexicon = initialize_tagger_from_str("the DT\nto TO\nrun NN VB\nwalk NN VB\nI PRP\nwant VBP\nlike VBP\n");
+    let settings = TaggerSettings::default();
+    let mut model = TaggerModel::new(lexicon, Vec::new(), Vec::new(), HashMap::new(), settings);
+
+    // The annotator corrects the verb after `to` three times, but confirms the noun in `I like to run`.
+    let mut log = CorrectionLog::new();
+    for (text, corrected) in [("I want to run", Wordclass::VB), ("I like to walk", Wordclass::VB), ("I want to walk", Wordclass::VB), ("I like to run", Wordclass::NN)] {
+        let mut annotated = tag_sentence_with_model(text, &mut model);
+        annotated[3].1 = corrected;
+        log.record_annotated(&mut model, &annotated).unwrap();
+    }
+    assert_eq!(log.corrected_words(), 3);
+    assert_eq!(log.corrections[0].possible[3], [Wordclass::NN, Wordclass::VB]);
+    assert!(log.record(vec![String::from("run")], Vec::new(), Vec::new(), Vec::new()).is_err());
+
+    // `NN VB PREVTAG TO` would fix all three but break the confirmed noun, so it is only proposed if that is allowed.
+    let proposals = log.propose_rules(&CorrectionLog::default_settings());
+    // Instead, the two corrections after `want` are proposed as a rule of their own.
+    assert_eq!(proposals, parse_contextual_rules_from_str("NN VB PREV1OR2WD want\n").unwrap());
+    let lenient = ContextualLearnerSettings { max_errors_introduced: Some(1), ..CorrectionLog::default_settings() };
+    let first = &log.propose_rules(&lenient)[0];
+    assert_eq!((first.ruleset_id.clone(), first.parameters.clone()), (crate::rs_rulespec_id::RulespecID::PREVTAG, vec![String::from("TO")]));
+
+    // Once accepted, the rules tag new sentences, the confirmed noun stays right, and nothing more is proposed.
+    log.accept_rules(&mut model, &proposals);
+    assert_eq!(model.contextual_rules, proposals);
+    assert_eq!(tag_sentence_with_model("I want to run", &mut model)[3].1, Wordclass::VB);
+    assert_eq!(log.corrections[3].predicted[3], Wordclass::NN);
+    assert!(log.propose_rules(&CorrectionLog::default_settings()).is_empty());
+
+    // An annotated sentence is recorded with the model's own prediction, which now includes the accepted rule.
+    let annotated: Vec<(String, Wordclass)> = [("I", Wordclass::PRPE), ("want", Wordclass::VBP), ("to", Wordclass::TO), ("walk", Wordclass::VB)]
+        .into_iter().map(|(word, tag)| (word.to_string(), tag)).collect();
+    log.record_annotated(&mut model, &annotated).unwrap();
+    assert_eq!(log.corrections[4].predicted, log.corrections[4].corrected);
+
+    // The possible tags are the ones the tagger found, here through its lowercase lookup step.
+    model.settings.lookup = "exact,lowercase".parse().unwrap();
+    let mut annotated = tag_sentence_with_model("I want to Run", &mut model);
+    annotated[3].1 = Wordclass::VB;
+    log.record_annotated(&mut model, &annotated).unwrap();
+    assert_eq!(log.corrections[5].possible[3], [Wordclass::NN, Wordclass::VB]);
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use crate::rs_contextual_rulespec::{rule_window_alternatives, window_condition_holds, ContextualRulespec, WindowAlternatives, WindowCondition, WindowTest};
+use crate::rs_ruleset_writer::format_contextual_rule;
+use crate::rs_wordclass::Wordclass;
+
+
+/// What a finding says about a rule, relative to an earlier rule in the same ruleset.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FindingKind {
+    /// The rule is an exact copy of the earlier rule.
+    Duplicate,
+    /// The earlier rule makes the same change wherever this rule would, so this rule never adds anything.
+    Subsumed,
+    /// The earlier rule retags the source tag to something else wherever this rule would apply, so this rule never fires
+    /// (unless the earlier rule's target is not a possible tag of the word).
+    Shadowed,
+    /// The rule undoes the earlier rule (`A B …` followed by `B A …`) where their conditions overlap. `exact` is set when
+    /// the conditions are identical, so every change made by the earlier rule is undone.
+    Inverse { exact: bool },
+}
+
+
+/// How a finding was established.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Evidence {
+    /// Proven from the rule conditions alone.
+    Symbolic,
+    /// Observed at `occurrences` positions of a tagged corpus; the conditions alone could not decide it.
+    Corpus { occurrences: usize },
+}
+
+
+/// A problem found with the rule at `rule_index`, caused by the earlier rule at `other_index` (0-based indices into the
+/// flat rule list).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleFinding {
+    pub kind: FindingKind,
+    pub rule_index: usize,
+    pub other_index: usize,
+    pub evidence: Evidence,
+}
+
+
+/// The result of analysing a ruleset: the findings, plus the indices of rules that could not be analysed (e.g. rules
+/// using the `STAART` boundary tag or malformed parameters).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RuleAnalysis {
+    pub findings: Vec<RuleFinding>,
+    pub unanalysed: Vec<usize>,
+}
+
+
+/// Function to view a tag test as the set of tags it accepts.
+fn tag_set(test: &WindowTest) -> Option<Vec<Wordclass>> {
+    match test {
+        WindowTest::Tag(tag) => Some(vec![tag.clone()]),
+        WindowTest::Class(tags) => Some(tags.clone()),
+        WindowTest::Word(_) => None,
+    }
+}
+
+
+/// Function to check whether condition `a` holding guarantees that condition `b` holds at the same position.
+fn condition_implies(a: &WindowCondition, b: &WindowCondition) -> bool {
+    if a.offset != b.offset { return false; }
+    match (&a.test, &b.test) {
+        (WindowTest::Word(word_a), WindowTest::Word(word_b)) => match (a.negated, b.negated) {
+            (false, false) | (true, true) => word_a == word_b,
+            (false, true) => word_a != word_b,
+            (true, false) => false,
+        },
+        (test_a, test_b) => match (tag_set(test_a), tag_set(test_b)) {
+            (Some(set_a), Some(set_b)) => match (a.negated, b.negated) {
+                (false, false) => set_a.iter().all(|tag| set_b.contains(tag)),
+                (false, true) => set_a.iter().all(|tag| !set_b.contains(tag)),
+                (true, true) => set_b.iter().all(|tag| set_a.contains(tag)),
+                (true, false) => false,
+            },
+            _ => false,
+        },
+    }
+}
+
+
+/// Function to check whether every condition of `b` is guaranteed by some condition of `a`.
+fn conjunction_implies(a: &[WindowCondition], b: &[WindowCondition]) -> bool {
+    b.iter().all(|condition_b| a.iter().any(|condition_a| condition_implies(condition_a, condition_b)))
+}
+
+
+/// Function to check whether `a` and `b` can never hold together, i.e. some condition of `a` rules out one of `b`.
+fn conjunctions_contradict(a: &[WindowCondition], b: &[WindowCondition]) -> bool {
+    a.iter().any(|condition_a| b.iter().any(|condition_b| {
+        condition_implies(condition_a, &WindowCondition { negated: !condition_b.negated, ..condition_b.clone() })
+    }))
+}
+
+
+/// Function to check whether alternatives `a` holding guarantees that `b` holds.
+fn alternatives_imply(a: &WindowAlternatives, b: &WindowAlternatives) -> bool {
+    a.iter().all(|alternative_a| b.iter().any(|alternative_b| conjunction_implies(alternative_a, alternative_b)))
+}
+
+
+/// Function to check whether `a` and `b` can never hold at the same position.
+fn alternatives_disjoint(a: &WindowAlternatives, b: &WindowAlternatives) -> bool {
+    a.iter().all(|alternative_a| b.iter().all(|alternative_b| conjunctions_contradict(alternative_a, alternative_b)))
+}
+
+
+/// Function to list, for each rule, the corpus positions (sentence, token) where its conditions hold. The current tag is
+/// ignored, since the corpus tags are not the tags the rule would see at that point of tagging.
+fn corpus_matches(alternatives: &[Option<WindowAlternatives>], corpus: &[Vec<(String, Wordclass)>]) -> Vec<Vec<(usize, usize)>> {
+    alternatives.iter().map(|rule_alternatives| match rule_alternatives {
+        None => Vec::new(),
+        Some(rule_alternatives) => corpus.iter().enumerate().flat_map(|(sentence_index, sentence)| {
+            (0..sentence.len()).filter(move |&index| rule_alternatives.iter().any(|alternative| {
+                alternative.iter().filter(|condition| condition.offset != 0 || matches!(condition.test, WindowTest::Word(_)))
+                    .all(|condition| window_condition_holds(sentence, index as i32, condition))
+            })).map(move |index| (sentence_index, index))
+        }).collect(),
+    }).collect()
+}
+
+
+/// Function to count the positions in both sorted lists `a` and `b`.
+fn count_shared(a: &[(usize, usize)], b: &[(usize, usize)]) -> usize {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => { shared += 1; i += 1; j += 1; }
+        }
+    }
+    shared
+}
+
+
+/// Function to find contextual rules that are duplicated, subsumed or shadowed by an earlier rule, or that undo an earlier
+/// rule. Each pair is first decided from the rule conditions; where that is inconclusive and a tagged `corpus` is given,
+/// the positions where both rules' conditions hold in the corpus are used as evidence instead.
+///
+/// The analysis treats each rule in isolation, on the context it would see: it does not model the tagger's repeated
+/// passes, in which an earlier rule can change the context a later rule sees.
+pub fn analyse_contextual_rules(rules: &[ContextualRulespec], corpus: Option<&[Vec<(String, Wordclass)>]>) -> RuleAnalysis {
+    let alternatives: Vec<Option<WindowAlternatives>> = rules.iter().map(rule_window_alternatives).collect();
+    let matches = corpus.map(|corpus| corpus_matches(&alternatives, corpus));
+    let mut analysis = RuleAnalysis {
+        unanalysed: alternatives.iter().enumerate().filter(|(_, a)| a.is_none()).map(|(index, _)| index).collect(),
+        ..RuleAnalysis::default()
+    };
+
+    // Only rules with the same source tag can shadow each other, and only rules with swapped tags can be inverses.
+    let mut by_source: HashMap<&Wordclass, Vec<usize>> = HashMap::new();
+    for (index, rule) in rules.iter().enumerate() {
+        by_source.entry(&rule.source_tag).or_default().push(index);
+    }
+
+    for (rule_index, rule) in rules.iter().enumerate() {
+        let Some(rule_alternatives) = &alternatives[rule_index] else { continue };
+
+        // The first earlier rule with the same source tag that covers this rule shadows it.
+        for &other_index in by_source[&rule.source_tag].iter().take_while(|&&other_index| other_index < rule_index) {
+            let Some(other_alternatives) = &alternatives[other_index] else { continue };
+            let other = &rules[other_index];
+            let kind = if other.ruleset_id == rule.ruleset_id && other.parameters == rule.parameters && other.target_tag == rule.target_tag { FindingKind::Duplicate }
+                       else if other.target_tag == rule.target_tag { FindingKind::Subsumed }
+                       else { FindingKind::Shadowed };
+            let evidence = if alternatives_imply(rule_alternatives, other_alternatives) {
+                Some(Evidence::Symbolic)
+            } else if alternatives_disjoint(rule_alternatives, other_alternatives) {
+                None
+            } else {
+                // Shadowed on the corpus: the rule's conditions held somewhere, and the earlier rule's held there too.
+                matches.as_ref().and_then(|matches| {
+                    let occurrences = matches[rule_index].len();
+                    (occurrences > 0 && count_shared(&matches[rule_index], &matches[other_index]) == occurrences)
+                        .then_some(Evidence::Corpus { occurrences })
+                })
+            };
+            if let Some(evidence) = evidence {
+                analysis.findings.push(RuleFinding { kind, rule_index, other_index, evidence });
+                break;
+            }
+        }
+
+        // Every earlier rule this rule undoes, where their conditions overlap.
+        for &other_index in by_source.get(&rule.target_tag).into_iter().flatten().take_while(|&&other_index| other_index < rule_index) {
+            let Some(other_alternatives) = &alternatives[other_index] else { continue };
+            if rules[other_index].target_tag != rule.source_tag { continue; }
+            let exact = alternatives_imply(rule_alternatives, other_alternatives) && alternatives_imply(other_alternatives, rule_alternatives);
+            let evidence = if exact || alternatives_imply(rule_alternatives, other_alternatives) || alternatives_imply(other_alternatives, rule_alternatives) {
+                Some(Evidence::Symbolic)
+            } else if alternatives_disjoint(rule_alternatives, other_alternatives) {
+                None
+            } else {
+                matches.as_ref().and_then(|matches| {
+                    let occurrences = count_shared(&matches[rule_index], &matches[other_index]);
+                    (occurrences > 0).then_some(Evidence::Corpus { occurrences })
+                })
+            };
+            if let Some(evidence) = evidence {
+                analysis.findings.push(RuleFinding { kind: FindingKind::Inverse { exact }, rule_index, other_index, evidence });
+            }
+        }
+    }
+    analysis
+}
+
+
+/// Function to write an `analysis` of `rules` as a report, one finding per line with both rules and their line numbers
+/// (1-based), followed by the rules that could not be analysed.
+pub fn format_rule_analysis(rules: &[ContextualRulespec], analysis: &RuleAnalysis) -> String {
+    let mut report = String::new();
+    for finding in &analysis.findings {
+        let kind = match finding.kind {
+            FindingKind::Duplicate => "duplicate of",
+            FindingKind::Subsumed => "subsumed by",
+            FindingKind::Shadowed => "shadowed by",
+            FindingKind::Inverse { exact: true } => "exactly undoes",
+            FindingKind::Inverse { exact: false } => "partly undoes",
+        };
+        let evidence = match finding.evidence {
+            Evidence::Symbolic => String::from("symbolic"),
+            Evidence::Corpus { occurrences } => format!("corpus: {} positions", occurrences),
+        };
+        report.push_str(&format!("{:>5}: {}  {}  {:>5}: {}  [{}]\n",
+                                 finding.rule_index + 1, format_contextual_rule(&rules[finding.rule_index]), kind,
+                                 finding.other_index + 1, format_contextual_rule(&rules[finding.other_index]), evidence));
+    }
+    for &index in &analysis.unanalysed {
+        report.push_str(&format!("{:>5}: {}  not analysed\n", index + 1, format_contextual_rule(&rules[index])));
+    }
+    report
+}
+
+
+#[test]
+fn test_analyse_contextual_rules_symbolic() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let rules = parse_contextual_rules_from_str("\
+NN VB PREV1OR2TAG MD
+NN VB PREVTAG MD
+NN JJ PREV2TAG MD
+NN VB PREV1OR2TAG MD
+VBD VBN PREVTAG VBZ
+VBN VBD PREVTAG VBZ
+VBN VBD NEXTTAG DT
+NN VBP NEXTTAG DT
+").unwrap();
+    let analysis = analyse_contextual_rules(&rules, None);
+    let found = |kind: FindingKind, rule_index: usize, other_index: usize| analysis.findings.contains(
+        &RuleFinding { kind, rule_index, other_index, evidence: Evidence::Symbolic });
+
+    assert!(found(FindingKind::Subsumed, 1, 0));
+    assert!(found(FindingKind::Shadowed, 2, 0));
+    assert!(found(FindingKind::Duplicate, 3, 0));
+    assert!(found(FindingKind::Inverse { exact: true }, 5, 4));
+    // Different context positions, so neither shadowing nor an inverse can be decided without a corpus.
+    assert!(!analysis.findings.iter().any(|finding| finding.rule_index == 6 || finding.rule_index == 7));
+    assert_eq!(analysis.findings.len(), 4);
+}
+
+
+#[test]
+fn test_analyse_contextual_rules_corpus() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let rules = parse_contextual_rules_from_str("\
+VBD VBN PREVTAG VBZ
+VBN VBD NEXTTAG DT
+NN VB PREVTAG TO
+NN VBP NEXTWD the
+").unwrap();
+    let tagged = |words: &str, tags: &[Wordclass]| words.split(' ').map(String::from).zip(tags.iter().cloned()).collect::<Vec<_>>();
+    let corpus = vec![
+        tagged("it is done a", &[Wordclass::PRPE, Wordclass::VBZ, Wordclass::VBN, Wordclass::DT]),
+        tagged("to run the", &[Wordclass::TO, Wordclass::NN, Wordclass::DT]),
+    ];
+    let analysis = analyse_contextual_rules(&rules, Some(&corpus));
+    assert!(analysis.findings.contains(&RuleFinding {
+        kind: FindingKind::Inverse { exact: false }, rule_index: 1, other_index: 0, evidence: Evidence::Corpus { occurrences: 1 } }));
+    assert!(analysis.findings.contains(&RuleFinding {
+        kind: FindingKind::Shadowed, rule_index: 3, other_index: 2, evidence: Evidence::Corpus { occurrences: 1 } }));
+
+    let report = format_rule_analysis(&rules, &analysis);
+    assert!(report.contains("partly undoes") && report.contains("[corpus: 1 positions]"), "{}", report);
+}
+
+
+#[test]
+fn test_analyse_shipped_rules() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules;
+    use crate::rs_rulespec_id::RulespecID;
+
+    let rules = parse_contextual_rules("data/rulefile_contextual.txt").unwrap();
+    let analysis = analyse_contextual_rules(&rules, None);
+    // `NN VB PREVTAG TO` appears twice in the shipped file (lines 1 and 115).
+    assert!(analysis.findings.contains(&RuleFinding { kind: FindingKind::Duplicate, rule_index: 114, other_index: 0, evidence: Evidence::Symbolic }));
+    // Rules comparing with the `STAART` boundary tag cannot be expressed as window conditions.
+    assert!(analysis.unanalysed.iter().any(|&index| rules[index].ruleset_id == RulespecID::SURROUNDTAG && rules[index].parameters[0] == "STAART"));
+    assert!(analysis.findings.iter().all(|finding| finding.other_index < finding.rule_index));
+}
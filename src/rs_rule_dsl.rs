@@ -0,0 +1,736 @@
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind};
+use crate::rs_contextual_ruleset::parse_contextual_rules;
+use crate::rs_contextual_rulespec::{compile_window_conditions, format_window_condition, ContextualRulespec, WindowCondition, WindowTest};
+use crate::rs_lex_rulespec_id::{compile_lexical_pattern, has_length_parameter, is_source_tagged, LexicalRuleID, LexicalRulespec, LEXICAL_RULE_IDS};
+use crate::rs_lexical_ruleset::parse_lexical_ruleset;
+use crate::rs_rulespec_id::{RulespecID, RULESPEC_IDS};
+use crate::rs_rule_provenance::{RuleProvenance, PROVENANCE_KEYS};
+use crate::rs_wordclass::{map_pos_tag, wordclass_to_pos_tag, Wordclass};
+
+
+/// A rule read from (or written to) the rule DSL, along with its optional name and metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DslRule {
+    pub name: Option<String>,
+    pub metadata: Vec<(String, String)>,
+    pub rule: DslRuleKind,
+}
+
+
+/// The Brill rule a DSL rule compiles into.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DslRuleKind {
+    Contextual(ContextualRulespec),
+    Lexical(LexicalRulespec),
+}
+
+
+/// A field of a token that a contextual condition can test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field { Tag, Word }
+
+
+/// A position (or set of positions) relative to the current token, as named in the DSL. `Offset` holds positions written as
+/// `tag[-3]` that have no name; offsets from -2 to +2 are always normalised to their named positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position { Current, Prev, Next, Prev2, Next2, Prev1or2, Prev1or2or3, Next1or2, Next1or2or3, Offset(i32) }
+
+
+/// Function to map a single relative `offset` to its `Position`, using the named position where there is one.
+fn position_from_offset(offset: i32) -> Position {
+    match offset {
+        0 => Position::Current,
+        -1 => Position::Prev,
+        1 => Position::Next,
+        -2 => Position::Prev2,
+        2 => Position::Next2,
+        _ => Position::Offset(offset),
+    }
+}
+
+
+/// Function to map a `Position` to its relative offset, or `None` for positions covering several tokens (e.g. `prev1or2`).
+fn position_offset(position: Position) -> Option<i32> {
+    match position {
+        Position::Current => Some(0),
+        Position::Prev => Some(-1),
+        Position::Next => Some(1),
+        Position::Prev2 => Some(-2),
+        Position::Next2 => Some(2),
+        Position::Offset(offset) => Some(offset),
+        _ => None,
+    }
+}
+
+
+/// A parsed contextual condition. `==` gives a single value, `in {..}` several; `!=` and `not in` set `negated`.
+#[derive(Debug, Clone, PartialEq)]
+struct ContextualCondition {
+    field: Field,
+    position: Position,
+    negated: bool,
+    values: Vec<String>,
+}
+
+
+/// The DSL name of each `Position`, e.g. `prev.tag == TO` tests the tag at index - 1.
+const POSITION_NAMES: [(Position, &str); 9] = [
+    (Position::Current, "cur"), (Position::Prev, "prev"), (Position::Next, "next"),
+    (Position::Prev2, "prev2"), (Position::Next2, "next2"), (Position::Prev1or2, "prev1or2"),
+    (Position::Prev1or2or3, "prev1or2or3"), (Position::Next1or2, "next1or2"), (Position::Next1or2or3, "next1or2or3"),
+];
+
+
+/// Function to map a contextual template to the conditions it tests, in the order its parameters appear in Brill's rule files.
+fn contextual_template(id: &RulespecID) -> &'static [(Field, Position)] {
+    match id {
+        RulespecID::PREVTAG => &[(Field::Tag, Position::Prev)],
+        RulespecID::PREVWD => &[(Field::Word, Position::Prev)],
+        RulespecID::PREV1OR2TAG => &[(Field::Tag, Position::Prev1or2)],
+        RulespecID::PREV1OR2OR3TAG => &[(Field::Tag, Position::Prev1or2or3)],
+        RulespecID::NEXT1OR2OR3TAG => &[(Field::Tag, Position::Next1or2or3)],
+        RulespecID::WDAND2TAGAFT => &[(Field::Word, Position::Current), (Field::Tag, Position::Next2)],
+        RulespecID::WDAND2AFT => &[(Field::Word, Position::Current), (Field::Word, Position::Next2)],
+        RulespecID::PREV1OR2WD => &[(Field::Word, Position::Prev1or2)],
+        RulespecID::NEXT1OR2TAG => &[(Field::Tag, Position::Next1or2)],
+        RulespecID::NEXTTAG => &[(Field::Tag, Position::Next)],
+        RulespecID::PREV2TAG => &[(Field::Tag, Position::Prev2)],
+        RulespecID::NEXTWD => &[(Field::Word, Position::Next)],
+        RulespecID::WDNEXTTAG => &[(Field::Word, Position::Current), (Field::Tag, Position::Next)],
+        RulespecID::SURROUNDTAG => &[(Field::Tag, Position::Prev), (Field::Tag, Position::Next)],
+        RulespecID::WDAND2TAGBFR => &[(Field::Tag, Position::Prev2), (Field::Word, Position::Current)],
+        RulespecID::RBIGRAM => &[(Field::Word, Position::Current), (Field::Word, Position::Next)],
+        RulespecID::PREVBIGRAM => &[(Field::Tag, Position::Prev2), (Field::Tag, Position::Prev)],
+        RulespecID::CURWD => &[(Field::Word, Position::Current)],
+        RulespecID::WDPREVTAG => &[(Field::Tag, Position::Prev), (Field::Word, Position::Current)],
+        RulespecID::NEXTBIGRAM => &[(Field::Tag, Position::Next), (Field::Tag, Position::Next2)],
+        RulespecID::NEXT2TAG => &[(Field::Tag, Position::Next2)],
+        RulespecID::LBIGRAM => &[(Field::Word, Position::Prev), (Field::Word, Position::Current)],
+        // Generalised rules have no fixed slots; their conditions are compiled by `compile_conjunction`.
+        RulespecID::CONJUNCTION => &[],
+    }
+}
+
+
+/// Function to map a lexical template to its DSL condition name. The `f`-variants share a name with their untagged
+/// counterparts, and are told apart by whether the rule has a source tag or `*`.
+fn lexical_condition_name(id: &LexicalRuleID) -> &'static str {
+    match id {
+        LexicalRuleID::HASSUF | LexicalRuleID::FHASSUF => "suffix",
+        LexicalRuleID::FHASPREF => "prefix",
+        LexicalRuleID::CHAR | LexicalRuleID::FCHAR => "contains",
+        LexicalRuleID::ADDSUF | LexicalRuleID::FADDSUF => "add_suffix",
+        LexicalRuleID::DELETESUF | LexicalRuleID::FDELETESUF => "delete_suffix",
+        LexicalRuleID::DELETEPREF | LexicalRuleID::FDELETEPREF => "delete_prefix",
+        LexicalRuleID::GOODLEFT | LexicalRuleID::FGOODLEFT => "left_of",
+        LexicalRuleID::GOODRIGHT | LexicalRuleID::FGOODRIGHT => "right_of",
+        LexicalRuleID::REGEX | LexicalRuleID::FREGEX => "matches",
+    }
+}
+
+
+/// A token of a single DSL line.
+#[derive(Debug, Clone, PartialEq)]
+enum DslToken {
+    Bare(String),
+    Quoted(String),
+    Symbol(&'static str),
+}
+
+
+/// Symbols recognised by the DSL tokeniser. Longer symbols come first so `==` is not read as two `=`.
+const SYMBOLS: [&str; 12] = ["->", "==", "!=", ":", ",", "=", ".", "*", "[", "]", "{", "}"];
+
+
+/// Function to check whether `c` can appear in an unquoted value (tags such as `PRP$`, numbers and plain words).
+fn is_bare_char(c: char) -> bool {
+    c.is_alphanumeric() || "_$|'-+&%/@".contains(c)
+}
+
+
+/// Function to split a DSL `line` into tokens, dropping anything after a `#` comment marker.
+fn tokenize_dsl_line(line: &str) -> Result<Vec<DslToken>, String> {
+    let mut tokens: Vec<DslToken> = Vec::new();
+    let mut rest = line;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else { break };
+        if c == '#' { break; }
+
+        // Quoted values run to the next unescaped `"`, and may contain `\"` and `\\`.
+        if c == '"' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let mut end = None;
+            while let Some((i, ch)) = chars.next() {
+                match ch {
+                    '\\' => value.push(chars.next().map(|(_, escaped)| escaped).ok_or("unterminated escape")?),
+                    '"' => { end = Some(i + 2); break; }
+                    _ => value.push(ch),
+                }
+            }
+            tokens.push(DslToken::Quoted(value));
+            rest = &rest[end.ok_or("unterminated string")?..];
+            continue;
+        }
+
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(DslToken::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+            continue;
+        }
+
+        // Bare values run until a character that cannot appear in them, or the start of a `->`.
+        let end = rest.char_indices()
+            .find(|(i, ch)| !is_bare_char(*ch) || rest[*i..].starts_with("->"))
+            .map_or(rest.len(), |(i, _)| i);
+        if end == 0 { return Err(format!("unexpected character '{}'", c)); }
+        tokens.push(DslToken::Bare(rest[..end].to_string()));
+        rest = &rest[end..];
+    }
+    Ok(tokens)
+}
+
+
+/// Cursor over the tokens of a single DSL line.
+struct DslCursor {
+    tokens: Vec<DslToken>,
+    index: usize,
+}
+
+impl DslCursor {
+    fn peek(&self) -> Option<&DslToken> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<DslToken> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(DslToken::Bare(word)) if word == keyword)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(DslToken::Symbol(s)) if *s == symbol)
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next() {
+            Some(DslToken::Symbol(s)) if s == symbol => Ok(()),
+            other => Err(format!("expected '{}', found {}", symbol, describe_token(other.as_ref()))),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        match self.next() {
+            Some(DslToken::Bare(word)) if word == keyword => Ok(()),
+            other => Err(format!("expected '{}', found {}", keyword, describe_token(other.as_ref()))),
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(DslToken::Bare(word)) => Ok(word),
+            other => Err(format!("expected a name, found {}", describe_token(other.as_ref()))),
+        }
+    }
+
+    fn expect_value(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(DslToken::Bare(value)) | Some(DslToken::Quoted(value)) => Ok(value),
+            other => Err(format!("expected a value, found {}", describe_token(other.as_ref()))),
+        }
+    }
+}
+
+
+/// Function to describe a token for error messages.
+fn describe_token(token: Option<&DslToken>) -> String {
+    match token {
+        Some(DslToken::Bare(word)) => format!("'{}'", word),
+        Some(DslToken::Quoted(value)) => format!("\"{}\"", value),
+        Some(DslToken::Symbol(symbol)) => format!("'{}'", symbol),
+        None => String::from("end of line"),
+    }
+}
+
+
+/// Function to map a tag written in the DSL to a `Wordclass`.
+fn parse_dsl_tag(tag: &str) -> Result<Wordclass, String> {
+    map_pos_tag(tag).ok_or_else(|| format!("unknown tag '{}'", tag))
+}
+
+
+/// Function to parse a contextual condition such as `prev.tag == TO`, `word == "up"`, `tag[-3] != VB` or
+/// `next.tag in {NN, NNS}`.
+fn parse_contextual_condition(cursor: &mut DslCursor) -> Result<ContextualCondition, String> {
+    let selector = cursor.expect_identifier()?;
+    let (position, field_name) = if cursor.is_symbol(".") {
+        cursor.next();
+        let position = POSITION_NAMES.iter().find(|(_, name)| *name == selector)
+            .map(|(position, _)| *position)
+            .ok_or_else(|| format!("unknown position '{}'", selector))?;
+        (position, cursor.expect_identifier()?)
+    } else if cursor.is_symbol("[") {
+        cursor.next();
+        let offset = cursor.expect_value()?;
+        let offset: i32 = offset.strip_prefix('+').unwrap_or(&offset).parse().map_err(|_| format!("invalid offset '{}'", offset))?;
+        cursor.expect_symbol("]")?;
+        (position_from_offset(offset), selector)
+    } else {
+        (Position::Current, selector)
+    };
+    let field = match field_name.as_str() {
+        "tag" => Field::Tag,
+        "word" => Field::Word,
+        _ => return Err(format!("unknown field '{}'", field_name)),
+    };
+
+    let negated = if cursor.is_symbol("==") || cursor.is_symbol("!=") {
+        let negated = cursor.is_symbol("!=");
+        cursor.next();
+        return Ok(ContextualCondition { field, position, negated, values: vec![cursor.expect_value()?] });
+    } else if cursor.is_keyword("not") {
+        cursor.next();
+        true
+    } else {
+        false
+    };
+    cursor.expect_keyword("in")?;
+    cursor.expect_symbol("{")?;
+    let mut values = vec![cursor.expect_value()?];
+    while cursor.is_symbol(",") {
+        cursor.next();
+        values.push(cursor.expect_value()?);
+    }
+    cursor.expect_symbol("}")?;
+    Ok(ContextualCondition { field, position, negated, values })
+}
+
+
+/// Function to compile contextual `conditions` into the matching `RulespecID` template and its parameters. Conditions that
+/// fit none of Brill's templates are compiled into a generalised `CONJUNCTION` rule instead.
+fn compile_contextual_conditions(conditions: &[ContextualCondition]) -> Result<(RulespecID, Vec<String>), String> {
+    let plain = conditions.iter().all(|condition| !condition.negated && condition.values.len() == 1);
+    for id in RULESPEC_IDS.iter().filter(|_| plain) {
+        let template = contextual_template(id);
+        if template.len() != conditions.len() { continue; }
+
+        // Each slot of the template must be filled by exactly one condition, in any order.
+        let parameters: Option<Vec<String>> = template.iter()
+            .map(|(field, position)| conditions.iter()
+                .find(|condition| condition.field == *field && condition.position == *position)
+                .map(|condition| condition.values[0].clone()))
+            .collect();
+        if let Some(parameters) = parameters {
+            return Ok((id.clone(), parameters));
+        }
+    }
+    Ok((RulespecID::CONJUNCTION, compile_conjunction(conditions)?))
+}
+
+
+/// Function to compile `conditions` into the parameter tokens of a `CONJUNCTION` rule (see `format_window_condition`).
+fn compile_conjunction(conditions: &[ContextualCondition]) -> Result<Vec<String>, String> {
+    conditions.iter().map(|condition| {
+        let offset = position_offset(condition.position)
+            .ok_or("conditions over several positions (e.g. prev1or2) only fit Brill's templates")?;
+        let test = match (condition.field, condition.values.as_slice()) {
+            (Field::Tag, [tag]) => WindowTest::Tag(parse_dsl_tag(tag)?),
+            (Field::Tag, tags) => WindowTest::Class(tags.iter().map(|tag| parse_dsl_tag(tag)).collect::<Result<_, _>>()?),
+            (Field::Word, [word]) if !word.is_empty() => WindowTest::Word(word.clone()),
+            (Field::Word, [_]) => return Err(String::from("word conditions need a word")),
+            (Field::Word, _) => return Err(String::from("word conditions cannot use 'in'")),
+        };
+        Ok(format_window_condition(&WindowCondition { negated: condition.negated, offset, test }))
+    }).collect()
+}
+
+
+/// Function to compile a lexical condition `name == value` into the matching `LexicalRuleID` and its parameters.
+fn compile_lexical_condition(source: Option<&str>, name: &str, value: &str) -> Result<(LexicalRuleID, Vec<String>), String> {
+    let id = LEXICAL_RULE_IDS.iter()
+        .find(|id| lexical_condition_name(id) == name && is_source_tagged(id) == source.is_some())
+        .ok_or_else(|| match source {
+            Some(_) => format!("'{}' cannot be used with a source tag", name),
+            None => format!("'{}' needs a source tag rather than '*'", name),
+        })?;
+
+    let mut parameters: Vec<String> = source.map(String::from).into_iter().collect();
+    parameters.push(value.to_string());
+    if has_length_parameter(id) { parameters.push(value.chars().count().to_string()); }
+    parameters.push(String::from("x"));
+    Ok((id.clone(), parameters))
+}
+
+
+/// Function to parse metadata after the `with` keyword, e.g. `with score=412, corpus=wsj`.
+fn parse_metadata(cursor: &mut DslCursor) -> Result<Vec<(String, String)>, String> {
+    let mut metadata: Vec<(String, String)> = Vec::new();
+    while cursor.peek().is_some() {
+        let key = cursor.expect_identifier()?;
+        cursor.expect_symbol("=")?;
+        metadata.push((key, cursor.expect_value()?));
+        if cursor.is_symbol(",") { cursor.next(); }
+    }
+    Ok(metadata)
+}
+
+
+/// Function to collect the provenance keys (`score`, `source_corpus`, …) among a rule's `metadata`, or `None` if it has none.
+/// Other metadata is kept on the `DslRule` only.
+fn provenance_from_metadata(metadata: &[(String, String)]) -> Result<Option<RuleProvenance>, String> {
+    let mut provenance: Option<RuleProvenance> = None;
+    for (key, value) in metadata.iter().filter(|(key, _)| PROVENANCE_KEYS.contains(&key.as_str())) {
+        provenance.get_or_insert_with(RuleProvenance::default).set(key, value).map_err(|e| e.to_string())?;
+    }
+    Ok(provenance)
+}
+
+
+/// Function to parse a single rule from the tokens of one line.
+fn parse_dsl_rule(cursor: &mut DslCursor) -> Result<DslRule, String> {
+    let keyword = cursor.expect_identifier()?;
+    let name = if cursor.is_symbol(":") { None } else { Some(cursor.expect_identifier()?) };
+    cursor.expect_symbol(":")?;
+
+    let source = if cursor.is_symbol("*") { cursor.next(); None } else { Some(cursor.expect_value()?) };
+    cursor.expect_symbol("->")?;
+    let target_tag = parse_dsl_tag(&cursor.expect_value()?)?;
+    cursor.expect_keyword("when")?;
+
+    let mut rule = match keyword.as_str() {
+        "rule" => {
+            let source_tag = parse_dsl_tag(&source.ok_or("contextual rules need a source tag rather than '*'")?)?;
+            let mut conditions = vec![parse_contextual_condition(cursor)?];
+            while cursor.is_keyword("and") {
+                cursor.next();
+                conditions.push(parse_contextual_condition(cursor)?);
+            }
+            let (ruleset_id, parameters) = compile_contextual_conditions(&conditions)?;
+            let conditions = compile_window_conditions(&ruleset_id, &parameters).map_err(|e| e.to_string())?;
+            DslRuleKind::Contextual(ContextualRulespec { source_tag, target_tag, ruleset_id, parameters, conditions, provenance: None })
+        }
+        "lexrule" => {
+            if let Some(tag) = &source { parse_dsl_tag(tag)?; }
+            let condition = cursor.expect_identifier()?;
+            cursor.expect_symbol("==")?;
+            let value = cursor.expect_value()?;
+            let (ruleset_id, parameters) = compile_lexical_condition(source.as_deref(), &condition, &value)?;
+            let pattern = compile_lexical_pattern(&ruleset_id, &parameters).map_err(|e| e.to_string())?;
+            DslRuleKind::Lexical(LexicalRulespec { ruleset_id, target_tag, parameters, pattern, provenance: None })
+        }
+        _ => return Err(format!("expected 'rule' or 'lexrule', found '{}'", keyword)),
+    };
+
+    let metadata = if cursor.is_keyword("with") { cursor.next(); parse_metadata(cursor)? } else { Vec::new() };
+    if cursor.peek().is_some() {
+        return Err(format!("unexpected {}", describe_token(cursor.peek())));
+    }
+    let provenance = provenance_from_metadata(&metadata)?;
+    match &mut rule {
+        DslRuleKind::Contextual(contextual) => contextual.provenance = provenance,
+        DslRuleKind::Lexical(lexical) => lexical.provenance = provenance,
+    }
+    Ok(DslRule { name, metadata, rule })
+}
+
+
+/// Function to parse the `contents` of a rule DSL file. Each non-empty line holds one rule, and `#` starts a comment:
+///
+/// ```text
+/// # Contextual rules test tags and words around the current token.
+/// rule r12: NN -> VB when prev.tag == TO with score=412   # from the WSJ run
+/// rule: VBD -> VBN when prev2.tag == VBZ and word == "been"
+/// # Lexical rules test the word form; `*` means the word has no tag yet.
+/// lexrule: * -> RB when suffix == "ly"
+/// lexrule l3: NN -> NNS when suffix == "s"
+/// ```
+///
+/// Contextual positions are `cur` (or no prefix), `prev`, `next`, `prev2`, `next2`, `prev1or2`, `prev1or2or3`, `next1or2`
+/// and `next1or2or3`, each with a `tag` or `word` field; any single position can also be written as `tag[-3]` or `word[+1]`.
+/// Conditions compare with `==`, `!=`, `in {..}` or `not in {..}`. Rules that fit one of Brill's templates compile to it,
+/// and anything else compiles to a generalised `CONJUNCTION` rule, e.g. `rule: NN -> VB when tag[-1] == MD and tag[-2] != VB`. Lexical conditions are `suffix`, `prefix`, `contains`,
+/// `add_suffix`, `delete_suffix`, `delete_prefix`, `left_of` and `right_of`, mirroring Brill's lexical templates, plus
+/// `matches` for a regular expression over the word form (e.g. `lexrule: * -> CD when matches == "^[0-9]{4}$"`).
+/// The metadata keys `score`, `errors_fixed`, `errors_introduced`, `source_corpus` and `learner_version` also set the
+/// rule's `RuleProvenance`.
+pub fn parse_rule_dsl(contents: &str) -> Result<Vec<DslRule>, Error> {
+    let mut rules: Vec<DslRule> = Vec::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let to_error = |message: String| Error::new(ErrorKind::InvalidData, format!("line {}: {}", line_number + 1, message));
+        let tokens = tokenize_dsl_line(line).map_err(to_error)?;
+        if tokens.is_empty() { continue; }
+        rules.push(parse_dsl_rule(&mut DslCursor { tokens, index: 0 }).map_err(to_error)?);
+    }
+    Ok(rules)
+}
+
+
+/// Function to parse a rule DSL file at `path`.
+pub fn parse_rule_dsl_file(path: &str) -> Result<Vec<DslRule>, Error> {
+    parse_rule_dsl(&read_to_string(path)?)
+}
+
+
+/// Function to split compiled DSL `rules` into the lexical and contextual rulesets used by the tagger, keeping their order.
+pub fn split_dsl_rules(rules: &[DslRule]) -> (Vec<LexicalRulespec>, Vec<ContextualRulespec>) {
+    let mut lexical: Vec<LexicalRulespec> = Vec::new();
+    let mut contextual: Vec<ContextualRulespec> = Vec::new();
+    for rule in rules {
+        match &rule.rule {
+            DslRuleKind::Lexical(lexical_rule) => lexical.push(lexical_rule.clone()),
+            DslRuleKind::Contextual(contextual_rule) => contextual.push(contextual_rule.clone()),
+        }
+    }
+    (lexical, contextual)
+}
+
+
+/// Function to write a DSL value, quoting it unless it is made up only of characters allowed in bare values.
+fn format_dsl_value(value: &str, always_quote: bool) -> String {
+    let reserved = matches!(value, "and" | "when" | "with");
+    if !always_quote && !reserved && !value.is_empty() && value.chars().all(is_bare_char) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+
+/// Function to write a selector such as `prev.tag`, `word` or `tag[-3]`.
+fn format_selector(field: Field, position: Position) -> String {
+    let field_name = match field { Field::Tag => "tag", Field::Word => "word" };
+    match position {
+        Position::Current => field_name.to_string(),
+        Position::Offset(offset) => format!("{}[{:+}]", field_name, offset),
+        _ => format!("{}.{}", POSITION_NAMES.iter().find(|(p, _)| *p == position).map_or("cur", |(_, name)| *name), field_name),
+    }
+}
+
+
+/// Function to write the conditions of a `CONJUNCTION` rule, e.g. `tag[-3] != VB and next.tag in {NN, NNS}`.
+fn format_conjunction(rule: &ContextualRulespec) -> Result<String, Error> {
+    let parsed = match &rule.conditions {
+        Some(conditions) => conditions.clone(),
+        None => compile_window_conditions(&rule.ruleset_id, &rule.parameters)?.unwrap_or_default(),
+    };
+    let conditions: Vec<String> = parsed.iter().map(|condition| {
+        let position = position_from_offset(condition.offset);
+        match &condition.test {
+            WindowTest::Tag(tag) => format!("{} {} {}", format_selector(Field::Tag, position),
+                                            if condition.negated { "!=" } else { "==" }, format_dsl_value(wordclass_to_pos_tag(tag), false)),
+            WindowTest::Word(word) => format!("{} {} {}", format_selector(Field::Word, position),
+                                              if condition.negated { "!=" } else { "==" }, format_dsl_value(word, true)),
+            WindowTest::Class(tags) => format!("{} {}in {{{}}}", format_selector(Field::Tag, position),
+                                               if condition.negated { "not " } else { "" },
+                                               tags.iter().map(|tag| format_dsl_value(wordclass_to_pos_tag(tag), false)).collect::<Vec<String>>().join(", ")),
+        }
+    }).collect();
+    Ok(conditions.join(" and "))
+}
+
+
+/// Function to write the condition of a contextual rule, e.g. `prev.tag == TO and word == "up"`.
+fn format_contextual_conditions(rule: &ContextualRulespec) -> Result<String, Error> {
+    if rule.ruleset_id == RulespecID::CONJUNCTION {
+        return format_conjunction(rule);
+    }
+    let template = contextual_template(&rule.ruleset_id);
+    if template.len() != rule.parameters.len() {
+        return Err(Error::new(ErrorKind::InvalidData, format!("{} expects {} parameters", rule.ruleset_id, template.len())));
+    }
+    let conditions: Vec<String> = template.iter().zip(rule.parameters.iter())
+        .map(|((field, position), value)| format!("{} == {}", format_selector(*field, *position), format_dsl_value(value, *field == Field::Word)))
+        .collect();
+    Ok(conditions.join(" and "))
+}
+
+
+/// Function to write the condition of a lexical rule, together with its source tag (or `*`).
+fn format_lexical_condition(rule: &LexicalRulespec) -> Result<(String, String), Error> {
+    let source_tagged = is_source_tagged(&rule.ruleset_id);
+    let value_index = if source_tagged { 1 } else { 0 };
+    let value = rule.parameters.get(value_index)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} rule is missing its parameter", rule.ruleset_id)))?;
+    let source = if source_tagged { format_dsl_value(&rule.parameters[0], false) } else { String::from("*") };
+    Ok((source, format!("{} == {}", lexical_condition_name(&rule.ruleset_id), format_dsl_value(value, true))))
+}
+
+
+/// Function to write a single `rule` as a line of the rule DSL.
+pub fn format_dsl_rule(rule: &DslRule) -> Result<String, Error> {
+    let (keyword, source, target, condition) = match &rule.rule {
+        DslRuleKind::Contextual(contextual) => ("rule",
+            format_dsl_value(wordclass_to_pos_tag(&contextual.source_tag), false),
+            &contextual.target_tag,
+            format_contextual_conditions(contextual)?),
+        DslRuleKind::Lexical(lexical) => {
+            let (source, condition) = format_lexical_condition(lexical)?;
+            ("lexrule", source, &lexical.target_tag, condition)
+        }
+    };
+
+    let mut line = match &rule.name {
+        Some(name) => format!("{} {}: ", keyword, name),
+        None => format!("{}: ", keyword),
+    };
+    line += &format!("{} -> {} when {}", source, format_dsl_value(wordclass_to_pos_tag(target), false), condition);
+    // Provenance carried by the rule itself is written as metadata too, unless the metadata already has that key.
+    let provenance = match &rule.rule {
+        DslRuleKind::Contextual(contextual) => contextual.provenance.as_ref(),
+        DslRuleKind::Lexical(lexical) => lexical.provenance.as_ref(),
+    };
+    let mut metadata: Vec<(String, String)> = rule.metadata.clone();
+    for (key, value) in provenance.map(|provenance| provenance.to_pairs()).unwrap_or_default() {
+        if !metadata.iter().any(|(existing, _)| existing == key) { metadata.push((key.to_string(), value)); }
+    }
+    if !metadata.is_empty() {
+        let metadata: Vec<String> = metadata.iter().map(|(key, value)| format!("{}={}", key, format_dsl_value(value, false))).collect();
+        line += &format!(" with {}", metadata.join(", "));
+    }
+    Ok(line)
+}
+
+
+/// Function to write `rules` in the rule DSL, one per line.
+pub fn rules_to_dsl(rules: &[DslRule]) -> Result<String, Error> {
+    rules.iter().map(|rule| format_dsl_rule(rule).map(|line| line + "\n")).collect()
+}
+
+
+/// Function to convert Brill's contextual and lexical rule files into a single DSL document. Rules are named after their
+/// kind and position in the original file (`c1`, `c2`, …, `l1`, `l2`, …) so they can be traced back.
+pub fn convert_brill_rulesets_to_dsl(contextual_path: &str, lexical_path: &str) -> Result<String, Error> {
+    let lexical = parse_lexical_ruleset(lexical_path)?.into_iter().enumerate()
+        .map(|(index, rule)| DslRule { name: Some(format!("l{}", index + 1)), metadata: Vec::new(), rule: DslRuleKind::Lexical(rule) });
+    let contextual = parse_contextual_rules(contextual_path)?.into_iter().enumerate()
+        .map(|(index, rule)| DslRule { name: Some(format!("c{}", index + 1)), metadata: Vec::new(), rule: DslRuleKind::Contextual(rule) });
+    let rules: Vec<DslRule> = lexical.chain(contextual).collect();
+
+    let mut output = format!("# Converted from {} and {}\n", lexical_path, contextual_path);
+    output += &rules_to_dsl(&rules)?;
+    Ok(output)
+}
+
+
+/// Test that rules written in the DSL compile into the same structures as Brill's rule files.
+#[test]
+fn test_parse_rule_dsl() {
+    let rules = parse_rule_dsl(r#"
+        # A comment line, followed by rules with and without names.
+        rule r12: NN -> VB when prev.tag == TO with score=412   # score from the original run
+        rule: RB -> RP when word == "up" and prev.tag == VB
+        lexrule l1: NN -> NNS when suffix == "s" with corpus="wsj 1989", rank=1
+        lexrule: * -> RB when suffix == "ly"
+        lexrule: NN -> VB when matches == "[iy][sz]e$"
+    "#).unwrap();
+    assert_eq!(rules.len(), 5);
+
+    assert_eq!(rules[0].name, Some(String::from("r12")));
+    assert_eq!(rules[0].metadata, vec![(String::from("score"), String::from("412"))]);
+    assert_eq!(rules[0].rule, DslRuleKind::Contextual(ContextualRulespec {
+        source_tag: Wordclass::NN, target_tag: Wordclass::VB,
+        ruleset_id: RulespecID::PREVTAG, parameters: vec![String::from("TO")], conditions: None,
+        provenance: Some(RuleProvenance { score: Some(412), ..RuleProvenance::default() }),
+    }));
+    assert_eq!(rules[1].rule, DslRuleKind::Contextual(ContextualRulespec {
+        source_tag: Wordclass::RB, target_tag: Wordclass::RP,
+        ruleset_id: RulespecID::WDPREVTAG, parameters: vec![String::from("VB"), String::from("up")],
+        conditions: None, provenance: None,
+    }));
+    assert_eq!(rules[2].metadata[0], (String::from("corpus"), String::from("wsj 1989")));
+    assert_eq!(rules[2].rule, DslRuleKind::Lexical(LexicalRulespec {
+        ruleset_id: LexicalRuleID::FHASSUF, target_tag: Wordclass::NNS,
+        parameters: vec![String::from("NN"), String::from("s"), String::from("1"), String::from("x")],
+        pattern: None, provenance: None,
+    }));
+    let DslRuleKind::Lexical(rule) = &rules[4].rule else {
+        panic!("expected a lexical rule, got {:?}", rules[4].rule)
+    };
+    assert_eq!(rule.ruleset_id, LexicalRuleID::FREGEX);
+    assert_eq!(rule.parameters, vec![String::from("NN"), String::from("[iy][sz]e$"), String::from("x")]);
+    assert!(rule.pattern.is_some());
+    assert_eq!(rules[3].name, None);
+    assert_eq!(rules[3].rule, DslRuleKind::Lexical(LexicalRulespec {
+        ruleset_id: LexicalRuleID::HASSUF, target_tag: Wordclass::RB,
+        parameters: vec![String::from("ly"), String::from("2"), String::from("x")],
+        pattern: None, provenance: None,
+    }));
+}
+
+
+/// Test that malformed DSL lines are reported with their line number.
+#[test]
+fn test_parse_rule_dsl_errors() {
+    let error = parse_rule_dsl("rule: NN -> VB when prev.tag == TO\nrule: NN -> VB when prev3.tag == TO").unwrap_err();
+    assert!(error.to_string().contains("line 2"), "{}", error);
+    assert!(parse_rule_dsl("rule: NN -> XYZ when prev.tag == TO").is_err());
+    assert!(parse_rule_dsl("rule: NN -> VB when prev1or2.tag == TO and next2.word == \"a\"").is_err());
+    assert!(parse_rule_dsl("lexrule: * -> JJ when prefix == \"un\"").is_err());
+    assert!(parse_rule_dsl("rule: NN -> VB when word == \"unterminated").is_err());
+}
+
+
+/// Test that converting the shipped Brill rule files to the DSL and back yields the same rules, in the same order.
+#[test]
+fn test_convert_brill_rulesets_round_trip() {
+    let dsl = convert_brill_rulesets_to_dsl("data/rulefile_contextual.txt", "data/rulefile_lexical.txt").unwrap();
+    let (lexical, contextual) = split_dsl_rules(&parse_rule_dsl(&dsl).unwrap());
+    assert_eq!(lexical, parse_lexical_ruleset("data/rulefile_lexical.txt").unwrap());
+    assert_eq!(contextual, parse_contextual_rules("data/rulefile_contextual.txt").unwrap());
+}
+
+
+/// Test that conditions outside Brill's templates compile to `CONJUNCTION` rules, and that those are written back.
+#[test]
+fn test_parse_rule_dsl_conjunction() {
+    let rules = parse_rule_dsl(r#"
+        rule: NN -> VB when prev.tag == MD and word[+1] == "the" and tag[-2] != VB
+        rule: NN -> VB when tag[-1] == TO
+        rule: NN -> NNS when tag[-3] not in {DT, PRP$}
+    "#).unwrap();
+    let parameters = vec![String::from("tag@-1=MD"), String::from("word@+1=the"), String::from("!tag@-2=VB")];
+    assert_eq!(rules[0].rule, DslRuleKind::Contextual(ContextualRulespec {
+        source_tag: Wordclass::NN, target_tag: Wordclass::VB, ruleset_id: RulespecID::CONJUNCTION,
+        conditions: compile_window_conditions(&RulespecID::CONJUNCTION, &parameters).unwrap(),
+        parameters, provenance: None,
+    }));
+
+    // A single-offset condition that fits a template still compiles to it.
+    let DslRuleKind::Contextual(rule) = &rules[1].rule else {
+        panic!("expected a contextual rule, got {:?}", rules[1].rule)
+    };
+    assert_eq!(rule.ruleset_id, RulespecID::PREVTAG);
+    let DslRuleKind::Contextual(rule) = &rules[2].rule else {
+        panic!("expected a contextual rule, got {:?}", rules[2].rule)
+    };
+    assert_eq!(rule.parameters, vec![String::from("!class@-3=DT|PRP$")]);
+
+    let written = rules_to_dsl(&rules).unwrap();
+    assert!(written.contains("tag[-3] not in {DT, PRP$}"), "{}", written);
+    assert_eq!(parse_rule_dsl(&written).unwrap(), rules);
+
+    assert!(parse_rule_dsl("rule: NN -> VB when prev1or2.tag != MD").is_err());
+    assert!(parse_rule_dsl("rule: NN -> VB when word[-1] in {a, the}").is_err());
+}
+
+
+/// Test that provenance survives a Brill file → DSL → Brill file round trip, alongside other metadata.
+#[test]
+fn test_rule_dsl_provenance() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG TO ## score=120 source_corpus=en_ewt\n").unwrap();
+    let dsl = format_dsl_rule(&DslRule { name: None, metadata: vec![(String::from("note"), String::from("kept"))],
+                                         rule: DslRuleKind::Contextual(rules[0].clone()) }).unwrap();
+    assert_eq!(dsl, "rule: NN -> VB when prev.tag == TO with note=kept, score=120, source_corpus=en_ewt");
+    let (_, contextual) = split_dsl_rules(&parse_rule_dsl(&dsl).unwrap());
+    assert_eq!(contextual, rules);
+
+    assert!(parse_rule_dsl("rule: NN -> VB when prev.tag == TO with errors_fixed=many").is_err());
+}
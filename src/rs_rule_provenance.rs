@@ -0,0 +1,114 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+
+/// Token separating a rule from its provenance on a line of a rule file. `#` alone is a Penn tag, so a doubled `##` is used.
+pub const PROVENANCE_MARKER: &str = "##";
+
+
+/// Where a rule came from and how well it did when it was learned. Every field is optional, since hand-written rules (and
+/// Brill's original files) have none of them.
+///
+/// In rule files the provenance follows the rule after `##` as `key=value` tokens, e.g.
+/// `NN VB PREVTAG TO ## score=120 errors_fixed=131 errors_introduced=11 source_corpus=en_ewt-ud-train learner_version=0.1.0`.
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+pub struct RuleProvenance {
+    /// Errors fixed minus errors introduced, when the rule was chosen.
+    pub score: Option<i64>,
+    pub errors_fixed: Option<u32>,
+    pub errors_introduced: Option<u32>,
+    pub source_corpus: Option<String>,
+    pub learner_version: Option<String>,
+}
+
+
+/// The provenance keys, in the order they are written.
+pub const PROVENANCE_KEYS: [&str; 5] = ["score", "errors_fixed", "errors_introduced", "source_corpus", "learner_version"];
+
+
+impl RuleProvenance {
+    /// Function to list the fields that are set, as (key, value) pairs in `PROVENANCE_KEYS` order.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let values = [
+            self.score.map(|v| v.to_string()),
+            self.errors_fixed.map(|v| v.to_string()),
+            self.errors_introduced.map(|v| v.to_string()),
+            self.source_corpus.clone(),
+            self.learner_version.clone(),
+        ];
+        PROVENANCE_KEYS.iter().zip(values).filter_map(|(key, value)| value.map(|value| (*key, value))).collect()
+    }
+
+    /// Function to set the field named `key` from its text `value`. Unknown keys and malformed numbers are errors.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let invalid = |e: std::num::ParseIntError| Error::new(ErrorKind::InvalidData, format!("Invalid {} '{}': {}", key, value, e));
+        match key {
+            "score" => self.score = Some(value.parse().map_err(invalid)?),
+            "errors_fixed" => self.errors_fixed = Some(value.parse().map_err(invalid)?),
+            "errors_introduced" => self.errors_introduced = Some(value.parse().map_err(invalid)?),
+            "source_corpus" => self.source_corpus = Some(value.to_string()),
+            "learner_version" => self.learner_version = Some(value.to_string()),
+            _ => return Err(Error::new(ErrorKind::InvalidData, format!("Unknown provenance key '{}'", key))),
+        }
+        Ok(())
+    }
+}
+
+
+/// Implementation to show provenance in tagging traces, e.g. `score 120, errors_fixed 131, source_corpus en_ewt`.
+impl fmt::Display for RuleProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs: Vec<String> = self.to_pairs().into_iter().map(|(key, value)| format!("{} {}", key, value)).collect();
+        write!(f, "{}", pairs.join(", "))
+    }
+}
+
+
+/// Function to split the whitespace-separated `parts` of a rule file line into the rule tokens and the provenance
+/// written after `##`, if there is any.
+pub fn split_provenance<'a>(parts: &[&'a str]) -> Result<(Vec<&'a str>, Option<RuleProvenance>), Error> {
+    let Some(marker) = parts.iter().position(|part| *part == PROVENANCE_MARKER) else {
+        return Ok((parts.to_vec(), None));
+    };
+    let mut provenance = RuleProvenance::default();
+    for pair in &parts[marker + 1..] {
+        let (key, value) = pair.split_once('=')
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Expected key=value in rule provenance, found '{}'", pair)))?;
+        provenance.set(key, value)?;
+    }
+    Ok((parts[..marker].to_vec(), Some(provenance)))
+}
+
+
+/// Function to write `provenance` as the ` ## key=value …` suffix of a rule file line. Whitespace inside values is
+/// replaced with `_`, since values are single tokens.
+pub fn format_provenance(provenance: &RuleProvenance) -> String {
+    let pairs: Vec<String> = provenance.to_pairs().into_iter()
+        .map(|(key, value)| format!("{}={}", key, value.split_whitespace().collect::<Vec<&str>>().join("_")))
+        .collect();
+    format!(" {} {}", PROVENANCE_MARKER, pairs.join(" "))
+}
+
+
+#[test]
+fn test_provenance_round_trip() {
+    let provenance = RuleProvenance {
+        score: Some(-3),
+        errors_fixed: Some(4),
+        errors_introduced: Some(7),
+        source_corpus: Some(String::from("en ewt")),
+        learner_version: None,
+    };
+    let suffix = format_provenance(&provenance);
+    assert_eq!(suffix, " ## score=-3 errors_fixed=4 errors_introduced=7 source_corpus=en_ewt");
+
+    let line = format!("NN VB PREVTAG TO{}", suffix);
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let (rule, parsed) = split_provenance(&parts).unwrap();
+    assert_eq!(rule, vec!["NN", "VB", "PREVTAG", "TO"]);
+    assert_eq!(parsed, Some(RuleProvenance { source_corpus: Some(String::from("en_ewt")), ..provenance }));
+
+    assert_eq!(split_provenance(&["NN", "VB", "PREVTAG", "#"]).unwrap(), (vec!["NN", "VB", "PREVTAG", "#"], None));
+    assert!(split_provenance(&["NN", "##", "rank=1"]).is_err());
+    assert!(split_provenance(&["NN", "##", "score=high"]).is_err());
+}
@@ -0,0 +1,169 @@
+use crate::rs_brill_tagger::{initial_state_tags, TaggerSettings};
+use crate::rs_contextual_learner::TrainingCorpus;
+use crate::rs_contextual_rulespec::ContextualRulespec;
+use crate::rs_lex_rulespec_id::LexicalRulespec;
+use crate::rs_lexical_rulespec::lexical_rule_apply;
+use crate::rs_wordclass::Wordclass;
+use crate::WordclassMap;
+
+
+/// What one rule of a ruleset did to a development corpus, applied in order after the kept rules before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleContribution {
+    pub rule_index: usize,
+    /// Words the rule retagged from a wrong tag to the gold tag.
+    pub errors_fixed: u32,
+    /// Words the rule retagged away from the gold tag.
+    pub errors_introduced: u32,
+}
+
+impl RuleContribution {
+    /// Function to give the rule's net effect, errors fixed minus errors introduced.
+    pub fn net(&self) -> i64 {
+        self.errors_fixed as i64 - self.errors_introduced as i64
+    }
+}
+
+
+/// Function to tally a retagging from `before` to `after` of a word whose gold tag is `gold`.
+fn tally(contribution: &mut RuleContribution, before: &Wordclass, after: &Wordclass, gold: &Wordclass) {
+    if before == after || *gold == Wordclass::ANY { return; }
+    if after == gold { contribution.errors_fixed += 1; } else if before == gold { contribution.errors_introduced += 1; }
+}
+
+
+/// Function to measure the contribution of each contextual rule in `rules` to `corpus`, applying them one after another
+/// in order, as transformation-based learning does. A rule whose net effect is not positive is undone before the next
+/// rule is measured, so each rule is measured as it would apply in the pruned ruleset. `corpus` is left tagged with the
+/// kept rules applied.
+///
+/// Each rule is applied once, in a single pass over the corpus, which is how the rules were learned. The tagger instead
+/// repeats its pass over the contextual rules until nothing changes (up to `contextual_max_iterations` passes), so the
+/// measurements approximate what a rule does when tagging: a rule may fire again on what later rules did.
+pub fn contextual_rule_contributions(corpus: &mut TrainingCorpus, rules: &[ContextualRulespec]) -> Vec<RuleContribution> {
+    rules.iter().enumerate().map(|(rule_index, rule)| {
+        let mut contribution = RuleContribution { rule_index, errors_fixed: 0, errors_introduced: 0 };
+        let changed = corpus.apply_rule(rule);
+        for (sentence, index) in &changed {
+            tally(&mut contribution, &rule.source_tag, &rule.target_tag, &corpus.gold[*sentence][*index]);
+        }
+        if contribution.net() <= 0 {
+            for (sentence, index) in changed { corpus.sentences[sentence][index].1 = rule.source_tag.clone(); }
+        }
+        contribution
+    }).collect()
+}
+
+
+/// Function to measure the contribution of each lexical rule in `rules` to `gold_sentences`, starting from each word's
+/// most likely tag in the `lexicon` (looked up as `tagger_settings` does) and applying the rules one after another in
+/// order. As for contextual rules, a rule whose net effect is not positive is undone before the next rule is measured.
+///
+/// Each rule is applied once, in a single pass over the corpus. The tagger makes `lexical_max_iterations` more passes
+/// over the lexical rules after the first, so the measurements approximate what a rule does when tagging: a rule may fire
+/// again on a word a later rule retagged.
+pub fn lexical_rule_contributions(gold_sentences: &[Vec<(String, Wordclass)>], rules: &[LexicalRulespec], lexicon: &mut WordclassMap, tagger_settings: &TaggerSettings) -> Vec<RuleContribution> {
+    let mut sentences: Vec<_> = gold_sentences.iter().map(|gold_sentence| {
+        let tokens: Vec<String> = gold_sentence.iter().map(|(word, _)| word.clone()).collect();
+        let (tagged, possible) = initial_state_tags(tokens, &Vec::new(), lexicon, tagger_settings);
+        (tagged, possible.into_iter().map(|(_, tags)| tags).collect::<Vec<_>>())
+    }).collect();
+
+    rules.iter().enumerate().map(|(rule_index, rule)| {
+        let mut contribution = RuleContribution { rule_index, errors_fixed: 0, errors_introduced: 0 };
+        let mut changed: Vec<(usize, usize, Wordclass)> = Vec::new();
+        for (sentence, ((tagged, possible), gold_sentence)) in sentences.iter_mut().zip(gold_sentences).enumerate() {
+            for index in 0..tagged.len() {
+                if !possible[index].contains(&rule.target_tag) && !possible[index].contains(&Wordclass::ANY) { continue; }
+                let before = tagged[index].1.clone();
+                if lexical_rule_apply(tagged, index as i32, rule, lexicon) == Some(true) {
+                    tally(&mut contribution, &before, &rule.target_tag, &gold_sentence[index].1);
+                    changed.push((sentence, index, before));
+                }
+            }
+        }
+        if contribution.net() <= 0 {
+            for (sentence, index, before) in changed { sentences[sentence].0[index].1 = before; }
+        }
+        contribution
+    }).collect()
+}
+
+
+/// Function to keep the rules whose contribution is positive, in their original order.
+pub fn prune_rules<T: Clone>(rules: &[T], contributions: &[RuleContribution]) -> Vec<T> {
+    contributions.iter().filter(|contribution| contribution.net() > 0).map(|contribution| rules[contribution.rule_index].clone()).collect()
+}
+
+
+/// Function to format a per-rule report: each rule's errors fixed, errors introduced and net effect, and whether it is
+/// kept, followed by the number of rules kept and their net effect.
+pub fn format_pruning_report<T>(rules: &[T], contributions: &[RuleContribution], format_rule: fn(&T) -> String) -> String {
+    let mut report = String::new();
+    for contribution in contributions {
+        report += &format!("{:>5} fixed {:>5} introduced {:>5} net {:>+6} {}  {}\n",
+                           contribution.rule_index + 1, contribution.errors_fixed, contribution.errors_introduced, contribution.net(),
+                           if contribution.net() > 0 { "keep" } else { "drop" }, format_rule(&rules[contribution.rule_index]));
+    }
+    let kept: Vec<&RuleContribution> = contributions.iter().filter(|contribution| contribution.net() > 0).collect();
+    let net: i64 = kept.iter().map(|contribution| contribution.net()).sum();
+    report += &format!("kept {} of {} rules; net effect of the kept rules {:+}\n", kept.len(), contributions.len(), net);
+    report
+}
+
+
+#[test]
+fn test_prune_contextual_rules() {
+    use crate::rs_contextual_learner::toy_training_corpus;
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+    use crate::rs_ruleset_writer::format_contextual_rule;
+
+    let (gold, mut lexicon) = toy_training_corpus();
+    let mut corpus = TrainingCorpus::new(&gold, &Vec::new(), &mut lexicon, &TaggerSettings::default());
+    // The first rule fixes the three verbs after `to`; the second then undoes one of them, and the third never fires.
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG TO\nVB NN PREV1OR2WD like\nJJ NN PREVTAG DT\n").unwrap();
+    let contributions = contextual_rule_contributions(&mut corpus, &rules);
+    assert_eq!(contributions.iter().map(RuleContribution::net).collect::<Vec<_>>(), [3, -1, 0]);
+    assert_eq!(contributions[1].errors_introduced, 1);
+
+    assert_eq!(prune_rules(&rules, &contributions), rules[..1]);
+    let report = format_pruning_report(&rules, &contributions, format_contextual_rule);
+    assert!(report.lines().nth(1).unwrap().ends_with("drop  VB NN PREV1OR2WD like"));
+    assert!(report.ends_with("kept 1 of 3 rules; net effect of the kept rules +3\n"));
+}
+
+
+#[test]
+fn test_dropped_rule_not_applied() {
+    use crate::rs_contextual_learner::toy_training_corpus;
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    let (gold, mut lexicon) = toy_training_corpus();
+    let mut corpus = TrainingCorpus::new(&gold, &Vec::new(), &mut lexicon, &TaggerSettings::default());
+    // The first rule retags the two nouns after a determiner as verbs, and the second only fires on what the first did:
+    // measured with the first rule applied it would seem to fix two errors. Once the first rule is dropped it does
+    // nothing, so it is dropped too, and the corpus is left as it was.
+    let rules = parse_contextual_rules_from_str("NN VB PREVTAG DT\nVB NN PREVTAG DT\nNN VB PREVTAG TO\n").unwrap();
+    let contributions = contextual_rule_contributions(&mut corpus, &rules);
+    assert_eq!(contributions.iter().map(RuleContribution::net).collect::<Vec<_>>(), [-2, 0, 3]);
+    assert_eq!(prune_rules(&rules, &contributions), rules[2..]);
+    assert_eq!(corpus.errors(), 0);
+}
+
+
+#[test]
+fn test_prune_lexical_rules() {
+    use crate::initialize_tagger_from_str;
+    use crate::rs_lexical_ruleset::parse_lexical_ruleset_from_str;
+
+    let mut lexicon = initialize_tagger_from_str("the DT\ncat NN\nsat VBD\nquickly RB\n");
+    let tagged = |text: &str, tags: &[Wordclass]| text.split(' ').map(String::from).zip(tags.iter().cloned()).collect::<Vec<_>>();
+    let gold = vec![
+        tagged("the dogs sat", &[Wordclass::DT, Wordclass::NNS, Wordclass::VBD]),
+        tagged("the cats ran slowly", &[Wordclass::DT, Wordclass::NNS, Wordclass::VBD, Wordclass::RB]),
+    ];
+    let rules = parse_lexical_ruleset_from_str("s hassuf 1 NNS x\nly hassuf 2 RB x\nNNS s fhassuf 1 VBZ x\n").unwrap();
+    let contributions = lexical_rule_contributions(&gold, &rules, &mut lexicon, &TaggerSettings::default());
+    assert_eq!(contributions.iter().map(|c| (c.errors_fixed, c.errors_introduced)).collect::<Vec<_>>(), [(2, 0), (1, 0), (0, 2)]);
+    assert_eq!(prune_rules(&rules, &contributions), rules[..2]);
+}
@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+
+/// A rule together with how many identical rules precede it in the same list, so repeated rules (Brill's files contain a
+/// few) are matched one-to-one rather than all collapsing into one.
+type RuleKey<T> = (T, usize);
+
+
+/// A single difference between two versions of a ruleset. Indices are 0-based positions in the old and new rule lists.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleChange<T> {
+    Added { rule: T, new_index: usize },
+    Removed { rule: T, old_index: usize },
+    /// The rule is in both lists but its order relative to the other rules has changed, which can change what it does.
+    Moved { rule: T, old_index: usize, new_index: usize },
+}
+
+
+/// The branch of a three-way merge a change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeBranch { Ours, Theirs }
+
+
+/// Why the merge could not decide where (or whether) a rule belongs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeConflictKind {
+    /// Both branches moved the rule, to different places. The merged ruleset keeps our placement.
+    MovedDifferently,
+    /// One branch removed the rule while the other moved it. The merged ruleset keeps the moved rule.
+    RemovedAndMoved { removed_in: MergeBranch },
+}
+
+
+/// A rule the three-way merge could not reconcile, with its index in each version (if present there).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict<T> {
+    pub rule: T,
+    pub kind: MergeConflictKind,
+    pub base_index: Option<usize>,
+    pub ours_index: Option<usize>,
+    pub theirs_index: Option<usize>,
+}
+
+
+/// The merged rules, in order, and any conflicts found along the way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeResult<T> {
+    pub rules: Vec<T>,
+    pub conflicts: Vec<MergeConflict<T>>,
+}
+
+
+/// Function to key each rule of `rules` by its value and occurrence count.
+fn rule_keys<T: Clone + Eq + Hash>(rules: &[T]) -> Vec<RuleKey<T>> {
+    let mut seen: HashMap<&T, usize> = HashMap::new();
+    rules.iter().map(|rule| {
+        let occurrence = seen.entry(rule).or_insert(0);
+        *occurrence += 1;
+        (rule.clone(), *occurrence - 1)
+    }).collect()
+}
+
+
+/// Function to find the keys on a longest common subsequence of `old` and `new`: the rules whose relative order is unchanged.
+fn stable_keys<T: Clone + Eq + Hash>(old: &[RuleKey<T>], new: &[RuleKey<T>]) -> HashSet<RuleKey<T>> {
+    // `lengths[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+        }
+    }
+
+    let mut stable: HashSet<RuleKey<T>> = HashSet::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            stable.insert(old[i].clone());
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    stable
+}
+
+
+/// Function to compute the semantic difference between two versions of a ruleset, `old` and `new`. Rules are compared by
+/// value, not by line, and a rule only counts as moved when its order relative to the other rules changed (a rule pushed
+/// down by an insertion above it is not reported).
+pub fn diff_rulesets<T: Clone + Eq + Hash>(old: &[T], new: &[T]) -> Vec<RuleChange<T>> {
+    let old_keys = rule_keys(old);
+    let new_keys = rule_keys(new);
+    let stable = stable_keys(&old_keys, &new_keys);
+    let old_indices: HashMap<&RuleKey<T>, usize> = old_keys.iter().enumerate().map(|(index, key)| (key, index)).collect();
+    let new_indices: HashMap<&RuleKey<T>, usize> = new_keys.iter().enumerate().map(|(index, key)| (key, index)).collect();
+
+    let mut changes: Vec<RuleChange<T>> = Vec::new();
+    for (old_index, key) in old_keys.iter().enumerate() {
+        if !new_indices.contains_key(key) {
+            changes.push(RuleChange::Removed { rule: key.0.clone(), old_index });
+        }
+    }
+    for (new_index, key) in new_keys.iter().enumerate() {
+        match old_indices.get(key) {
+            None => changes.push(RuleChange::Added { rule: key.0.clone(), new_index }),
+            Some(&old_index) if !stable.contains(key) => changes.push(RuleChange::Moved { rule: key.0.clone(), old_index, new_index }),
+            _ => {}
+        }
+    }
+    changes
+}
+
+
+/// Function to find, for each rule of `branch`, the nearest preceding rule that is also in `base`. Two branches moved a
+/// rule to the same place when they give it the same anchor.
+fn anchors<T: Clone + Eq + Hash>(branch: &[RuleKey<T>], base: &HashSet<&RuleKey<T>>) -> HashMap<RuleKey<T>, Option<RuleKey<T>>> {
+    let mut previous: Option<RuleKey<T>> = None;
+    branch.iter().map(|key| {
+        let anchor = (key.clone(), previous.clone());
+        if base.contains(key) { previous = Some(key.clone()); }
+        anchor
+    }).collect()
+}
+
+
+/// Function to merge two branches, `ours` and `theirs`, of a common `base` ruleset. Our version is taken as the starting
+/// point and their additions, removals and moves are replayed onto it, each placed after the rule that precedes it in
+/// their version. Rules both branches moved to different places, or that one branch removed while the other moved them,
+/// are reported as conflicts.
+pub fn merge_rulesets<T: Clone + Eq + Hash>(base: &[T], ours: &[T], theirs: &[T]) -> MergeResult<T> {
+    let (base_keys, our_keys, their_keys) = (rule_keys(base), rule_keys(ours), rule_keys(theirs));
+    let index_of = |keys: &[RuleKey<T>]| -> HashMap<RuleKey<T>, usize> {
+        keys.iter().enumerate().map(|(index, key)| (key.clone(), index)).collect()
+    };
+    let (base_index, our_index, their_index) = (index_of(&base_keys), index_of(&our_keys), index_of(&their_keys));
+    let our_stable = stable_keys(&base_keys, &our_keys);
+    let their_stable = stable_keys(&base_keys, &their_keys);
+    let our_moves: HashSet<&RuleKey<T>> = our_keys.iter().filter(|key| base_index.contains_key(*key) && !our_stable.contains(*key)).collect();
+    let their_moves: HashSet<&RuleKey<T>> = their_keys.iter().filter(|key| base_index.contains_key(*key) && !their_stable.contains(*key)).collect();
+
+    let shared: HashSet<&RuleKey<T>> = base_keys.iter().collect();
+    let our_anchors = anchors(&our_keys, &shared);
+    let their_anchors = anchors(&their_keys, &shared);
+
+    let mut merged: Vec<RuleKey<T>> = our_keys.clone();
+    let mut conflicts: Vec<MergeConflict<T>> = Vec::new();
+    let conflict = |key: &RuleKey<T>, kind: MergeConflictKind| MergeConflict {
+        rule: key.0.clone(), kind,
+        base_index: base_index.get(key).copied(),
+        ours_index: our_index.get(key).copied(),
+        theirs_index: their_index.get(key).copied(),
+    };
+
+    // Their removals: rules in the base that they dropped. If we moved the rule, the move is kept and flagged.
+    for key in base_keys.iter().filter(|key| !their_index.contains_key(*key)) {
+        if our_moves.contains(key) {
+            conflicts.push(conflict(key, MergeConflictKind::RemovedAndMoved { removed_in: MergeBranch::Theirs }));
+        } else {
+            merged.retain(|merged_key| merged_key != key);
+        }
+    }
+
+    // Our removals of rules they moved are restored at their placement and flagged.
+    let mut replay: HashSet<&RuleKey<T>> = their_moves.iter().filter(|key| !our_moves.contains(**key)).copied().collect();
+    for key in their_moves.iter().filter(|key| !our_index.contains_key(**key)) {
+        conflicts.push(conflict(key, MergeConflictKind::RemovedAndMoved { removed_in: MergeBranch::Ours }));
+    }
+    for key in their_moves.iter().filter(|key| our_moves.contains(**key)) {
+        if our_anchors.get(*key) != their_anchors.get(*key) {
+            conflicts.push(conflict(key, MergeConflictKind::MovedDifferently));
+        }
+    }
+
+    // Their additions (rules in neither the base nor our version) are replayed along with their moves, in their order.
+    replay.extend(their_keys.iter().filter(|key| !base_index.contains_key(*key) && !our_index.contains_key(*key)));
+    let mut previous: Option<&RuleKey<T>> = None;
+    for key in their_keys.iter() {
+        if replay.contains(key) {
+            merged.retain(|merged_key| merged_key != key);
+            let position = previous
+                .and_then(|previous_key| merged.iter().position(|merged_key| merged_key == previous_key))
+                .map_or(0, |index| index + 1);
+            merged.insert(position, key.clone());
+        }
+        if merged.contains(key) { previous = Some(key); }
+    }
+
+    conflicts.sort_by_key(|conflict| conflict.base_index);
+    MergeResult { rules: merged.into_iter().map(|(rule, _)| rule).collect(), conflicts }
+}
+
+
+/// Function to write a list of `changes` as a human-readable report, one change per line, using `format_rule` to print each
+/// rule (e.g. `format_contextual_rule`). Line numbers in the report are 1-based.
+pub fn format_ruleset_diff<T>(changes: &[RuleChange<T>], format_rule: impl Fn(&T) -> String) -> String {
+    changes.iter().map(|change| match change {
+        RuleChange::Added { rule, new_index } => format!("+ {:>5}        {}\n", new_index + 1, format_rule(rule)),
+        RuleChange::Removed { rule, old_index } => format!("- {:>5}        {}\n", old_index + 1, format_rule(rule)),
+        RuleChange::Moved { rule, old_index, new_index } =>
+            format!("~ {:>5} → {:<5} {}  ({:+})\n", old_index + 1, new_index + 1, format_rule(rule), *new_index as i64 - *old_index as i64),
+    }).collect()
+}
+
+
+/// Function to write the `conflicts` of a merge as a human-readable report, one conflict per line.
+pub fn format_merge_conflicts<T>(conflicts: &[MergeConflict<T>], format_rule: impl Fn(&T) -> String) -> String {
+    let line = |index: Option<usize>| index.map_or(String::from("-"), |index| (index + 1).to_string());
+    conflicts.iter().map(|conflict| {
+        let description = match &conflict.kind {
+            MergeConflictKind::MovedDifferently => String::from("moved differently in both branches"),
+            MergeConflictKind::RemovedAndMoved { removed_in: MergeBranch::Ours } => String::from("removed in ours, moved in theirs"),
+            MergeConflictKind::RemovedAndMoved { removed_in: MergeBranch::Theirs } => String::from("moved in ours, removed in theirs"),
+        };
+        format!("! {} (base {}, ours {}, theirs {}): {}\n", format_rule(&conflict.rule),
+                line(conflict.base_index), line(conflict.ours_index), line(conflict.theirs_index), description)
+    }).collect()
+}
+
+
+#[test]
+fn test_diff_rulesets() {
+    let old = vec!["a", "b", "c", "d", "e"];
+    let new = vec!["a", "x", "c", "b", "e"];
+    let changes = diff_rulesets(&old, &new);
+
+    // "b" and "c" swapped; only one of them has to move to explain it. Everything after "x" shifting down is not reported.
+    assert!(changes.contains(&RuleChange::Removed { rule: "d", old_index: 3 }));
+    assert!(changes.contains(&RuleChange::Added { rule: "x", new_index: 1 }));
+    assert_eq!(changes.iter().filter(|change| matches!(change, RuleChange::Moved { .. })).count(), 1);
+    assert_eq!(changes.len(), 3);
+
+    assert!(diff_rulesets(&old, &old).is_empty());
+}
+
+
+#[test]
+fn test_diff_rulesets_repeated_rules() {
+    // A repeated rule is only reported once when one copy is removed.
+    let changes = diff_rulesets(&["a", "b", "a"], &["a", "b"]);
+    assert_eq!(changes, vec![RuleChange::Removed { rule: "a", old_index: 2 }]);
+}
+
+
+#[test]
+fn test_merge_rulesets() {
+    let base = vec!["a", "b", "c", "d"];
+    let ours = vec!["a", "b", "n", "c", "d"];       // adds "n"
+    let theirs = vec!["b", "a", "c", "t"];          // swaps "a"/"b", removes "d", adds "t"
+    let merged = merge_rulesets(&base, &ours, &theirs);
+    assert!(merged.conflicts.is_empty(), "{:?}", merged.conflicts);
+    assert_eq!(merged.rules, vec!["b", "a", "n", "c", "t"]);
+}
+
+
+#[test]
+fn test_merge_rulesets_conflicts() {
+    let base = vec!["a", "b", "c", "d"];
+    let ours = vec!["b", "c", "a", "d"];            // moves "a" after "c"
+    let theirs = vec!["b", "c", "d", "a"];          // moves "a" after "d"
+    let merged = merge_rulesets(&base, &ours, &theirs);
+    assert_eq!(merged.rules, ours);
+    assert_eq!(merged.conflicts.len(), 1);
+    assert_eq!(merged.conflicts[0].kind, MergeConflictKind::MovedDifferently);
+    assert_eq!((merged.conflicts[0].ours_index, merged.conflicts[0].theirs_index), (Some(2), Some(3)));
+
+    // The same move in both branches is not a conflict.
+    assert!(merge_rulesets(&base, &ours, &ours).conflicts.is_empty());
+
+    // A rule removed in one branch and moved in the other is kept and flagged.
+    let removed = vec!["b", "c", "d"];
+    let merged = merge_rulesets(&base, &removed, &theirs);
+    assert_eq!(merged.rules, theirs);
+    assert_eq!(merged.conflicts[0].kind, MergeConflictKind::RemovedAndMoved { removed_in: MergeBranch::Ours });
+}
+
+
+#[test]
+fn test_diff_contextual_rulesets() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+    use crate::rs_ruleset_writer::format_contextual_rule;
+
+    let old = parse_contextual_rules_from_str("NN VB PREVTAG TO\nVBP VB PREV1OR2OR3TAG MD\nNN VB PREV1OR2TAG MD\n").unwrap();
+    let new = parse_contextual_rules_from_str("NN VB PREV1OR2TAG MD\nNN VB PREVTAG TO\nVB NN PREV1OR2TAG DT\n").unwrap();
+    let report = format_ruleset_diff(&diff_rulesets(&old, &new), format_contextual_rule);
+    // The two kept rules swapped order, but only one of them is reported as moved: `PREV1OR2TAG MD` stays on the longest
+    // common subsequence.
+    assert_eq!(report.lines().collect::<Vec<_>>(), [
+        "-     2        VBP VB PREV1OR2OR3TAG MD",
+        "~     1 → 2     NN VB PREVTAG TO  (+1)",
+        "+     3        VB NN PREV1OR2TAG DT",
+    ]);
+}
+
+
+#[test]
+fn test_diff_ignores_provenance() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+
+    // The same rules, relearned on another corpus: only the second rule's provenance differs.
+    let base = parse_contextual_rules_from_str("NN VB PREVTAG TO\nVB NN PREVTAG DT ## score=12 source_corpus=wsj\n").unwrap();
+    let relearned = parse_contextual_rules_from_str("NN VB PREVTAG TO\nVB NN PREVTAG DT ## score=40 source_corpus=en_ewt\n").unwrap();
+    assert_ne!(base[1].provenance, relearned[1].provenance);
+    assert_eq!(diff_rulesets(&base, &relearned), []);
+
+    // Merging the relearned branch with one that added a rule neither duplicates the rule nor reports a conflict, and keeps
+    // our version of it.
+    let ours = parse_contextual_rules_from_str("NN VB PREVTAG TO\nVB NN PREVTAG DT ## score=12 source_corpus=wsj\nNN VB PREVTAG MD\n").unwrap();
+    let merged = merge_rulesets(&base, &ours, &relearned);
+    assert!(merged.conflicts.is_empty());
+    assert_eq!(merged.rules, ours);
+    assert_eq!(merged.rules[1].provenance, base[1].provenance);
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::io;
+use crate::rs_contextual_rulespec::ContextualRulespec;
+use crate::rs_lex_rulespec_id::{is_source_tagged, lexical_rule_id_to_string, LexicalRulespec};
+use crate::rs_rule_provenance::format_provenance;
+use crate::rs_rulespec_id::rulespec_id_to_string;
+use crate::rs_wordclass::wordclass_to_pos_tag;
+
+
+/// Function to format a contextual `rule` as a line of Brill's contextual rule file, e.g. `NN VB PREVTAG TO`, followed by
+/// its provenance if it has any.
+pub fn format_contextual_rule(rule: &ContextualRulespec) -> String {
+    let mut parts: Vec<&str> = vec![
+        wordclass_to_pos_tag(&rule.source_tag),
+        wordclass_to_pos_tag(&rule.target_tag),
+        rulespec_id_to_string(&rule.ruleset_id),
+    ];
+    parts.extend(rule.parameters.iter().map(|p| p.as_str()));
+    parts.join(" ") + &rule.provenance.as_ref().map(format_provenance).unwrap_or_default()
+}
+
+
+/// Function to format a lexical `rule` as a line of Brill's lexical rule file, e.g. `NN s fhassuf 1 NNS x`, followed by its
+/// provenance if it has any.
+pub fn format_lexical_rule(rule: &LexicalRulespec) -> String {
+    // `parse_lexical_ruleset` removes the rulestring (2nd token, or 3rd for the `f`-variants) and the target tag (always the
+    // second to final token) and keeps everything else as parameters. Writing a rule re-inserts both tokens at those positions.
+    let mut parts: Vec<&str> = rule.parameters.iter().map(|p| p.as_str()).collect();
+    let rulestring_index = if is_source_tagged(&rule.ruleset_id) { 2 } else { 1 };
+    parts.insert(rulestring_index.min(parts.len()), lexical_rule_id_to_string(&rule.ruleset_id));
+    parts.insert(parts.len().saturating_sub(1), wordclass_to_pos_tag(&rule.target_tag));
+    parts.join(" ") + &rule.provenance.as_ref().map(format_provenance).unwrap_or_default()
+}
+
+
+/// Function to format contextual `rules` in the contextual rule file format, one rule per line, in the given order.
+pub fn contextual_ruleset_to_string(rules: &[ContextualRulespec]) -> String {
+    rules.iter().map(|rule| format_contextual_rule(rule) + "\n").collect()
+}
+
+
+/// Function to format lexical `rules` in the lexical rule file format, one rule per line, in the given order.
+pub fn lexical_ruleset_to_string(rules: &[LexicalRulespec]) -> String {
+    rules.iter().map(|rule| format_lexical_rule(rule) + "\n").collect()
+}
+
+
+/// Function to write contextual `rules` to `path`, in a form that `parse_contextual_rules` reads back unchanged.
+pub fn write_contextual_ruleset(path: &str, rules: &[ContextualRulespec]) -> Result<(), io::Error> {
+    fs::write(path, contextual_ruleset_to_string(rules))
+}
+
+
+/// Function to write lexical `rules` to `path`, in a form that `parse_lexical_ruleset` reads back unchanged.
+pub fn write_lexical_ruleset(path: &str, rules: &[LexicalRulespec]) -> Result<(), io::Error> {
+    fs::write(path, lexical_ruleset_to_string(rules))
+}
+
+
+/// Test that the shipped contextual rule file survives a parse → write → parse round trip, in order.
+#[test]
+fn test_contextual_ruleset_round_trip() {
+    use crate::rs_contextual_ruleset::{parse_contextual_rules, parse_contextual_rules_from_str};
+
+    let rules = parse_contextual_rules("data/rulefile_contextual.txt").unwrap();
+    let written = contextual_ruleset_to_string(&rules);
+    let reparsed = parse_contextual_rules_from_str(&written).unwrap();
+    assert_eq!(rules, reparsed);
+    assert_eq!(written.lines().next(), Some("NN VB PREVTAG TO"));
+}
+
+
+/// Test that the shipped lexical rule file survives a parse → write → parse round trip, in order.
+#[test]
+fn test_lexical_ruleset_round_trip() {
+    use crate::rs_lexical_ruleset::{parse_lexical_ruleset, parse_lexical_ruleset_from_str};
+
+    let rules = parse_lexical_ruleset("data/rulefile_lexical.txt").unwrap();
+    let written = lexical_ruleset_to_string(&rules);
+    let reparsed = parse_lexical_ruleset_from_str(&written).unwrap();
+    assert_eq!(rules, reparsed);
+
+    // Every line of the shipped file uses tags that map one-to-one, so the written text should match it exactly.
+    let original = fs::read_to_string("data/rulefile_lexical.txt").unwrap();
+    for (original_line, written_line) in original.lines().zip(written.lines()) {
+        assert_eq!(original_line.split_whitespace().collect::<Vec<&str>>().join(" "), written_line);
+    }
+}
+
+
+/// Test that rules built in code are written in the layout each rule kind expects.
+#[test]
+fn test_format_rules() {
+    use crate::rs_lex_rulespec_id::LexicalRuleID;
+    use crate::rs_rulespec_id::RulespecID;
+    use crate::rs_wordclass::Wordclass;
+
+    let contextual = ContextualRulespec {
+        source_tag: Wordclass::PRPO,
+        target_tag: Wordclass::WPO,
+        ruleset_id: RulespecID::SURROUNDTAG,
+        parameters: vec![String::from("DT"), String::from("NN")],
+        conditions: None,
+        provenance: None,
+    };
+    assert_eq!(format_contextual_rule(&contextual), "PRP$ WP$ SURROUNDTAG DT NN");
+
+    let hassuf = LexicalRulespec {
+        ruleset_id: LexicalRuleID::HASSUF,
+        target_tag: Wordclass::RB,
+        parameters: vec![String::from("ly"), String::from("2"), String::from("x")],
+        pattern: None,
+        provenance: None,
+    };
+    assert_eq!(format_lexical_rule(&hassuf), "ly hassuf 2 RB x");
+
+    let fgoodright = LexicalRulespec {
+        ruleset_id: LexicalRuleID::FGOODRIGHT,
+        target_tag: Wordclass::CD,
+        parameters: vec![String::from("NN"), String::from("$"), String::from("x")],
+        pattern: None,
+        provenance: None,
+    };
+    assert_eq!(format_lexical_rule(&fgoodright), "NN $ fgoodright CD x");
+
+    let regex = LexicalRulespec {
+        ruleset_id: LexicalRuleID::REGEX,
+        target_tag: Wordclass::CD,
+        parameters: vec![String::from("^[0-9]{4}$"), String::from("x")],
+        pattern: None,
+        provenance: None,
+    };
+    assert_eq!(format_lexical_rule(&regex), "^[0-9]{4}$ regex CD x");
+}
+
+
+/// Test that rule provenance is written after the rule and read back with it.
+#[test]
+fn test_provenance_round_trip() {
+    use crate::rs_contextual_ruleset::parse_contextual_rules_from_str;
+    use crate::rs_lexical_ruleset::parse_lexical_ruleset_from_str;
+
+    let contextual = "NN VB PREVTAG TO ## score=120 errors_fixed=131 errors_introduced=11 source_corpus=en_ewt learner_version=0.1.0\nVB NN PREVTAG DT\n";
+    let rules = parse_contextual_rules_from_str(contextual).unwrap();
+    assert_eq!(rules[0].parameters, vec![String::from("TO")]);
+    let provenance = rules[0].provenance.as_ref().unwrap();
+    assert_eq!((provenance.score, provenance.errors_fixed, provenance.errors_introduced), (Some(120), Some(131), Some(11)));
+    assert_eq!(provenance.learner_version.as_deref(), Some("0.1.0"));
+    assert_eq!(rules[1].provenance, None);
+    assert_eq!(contextual_ruleset_to_string(&rules), contextual);
+
+    let lexical = "NN s fhassuf 1 NNS x ## score=42 source_corpus=en_ewt\n";
+    let rules = parse_lexical_ruleset_from_str(lexical).unwrap();
+    assert_eq!(rules[0].parameters, vec![String::from("NN"), String::from("s"), String::from("1"), String::from("x")]);
+    assert_eq!(rules[0].provenance.as_ref().unwrap().score, Some(42));
+    assert_eq!(lexical_ruleset_to_string(&rules), lexical);
+}
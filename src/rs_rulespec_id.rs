@@ -26,9 +26,21 @@ pub enum RulespecID {
     NEXTBIGRAM,
     NEXT2TAG,
     LBIGRAM,
+    CONJUNCTION,
 }
 
 
+/// Every `RulespecID` template, in the order they are declared.
+pub const RULESPEC_IDS: [RulespecID; 23] = [
+    RulespecID::PREVTAG, RulespecID::PREVWD, RulespecID::PREV1OR2TAG, RulespecID::PREV1OR2OR3TAG,
+    RulespecID::NEXT1OR2OR3TAG, RulespecID::WDAND2TAGAFT, RulespecID::WDAND2AFT, RulespecID::PREV1OR2WD,
+    RulespecID::NEXT1OR2TAG, RulespecID::NEXTTAG, RulespecID::PREV2TAG, RulespecID::NEXTWD,
+    RulespecID::WDNEXTTAG, RulespecID::SURROUNDTAG, RulespecID::WDAND2TAGBFR, RulespecID::RBIGRAM,
+    RulespecID::PREVBIGRAM, RulespecID::CURWD, RulespecID::WDPREVTAG, RulespecID::NEXTBIGRAM,
+    RulespecID::NEXT2TAG, RulespecID::LBIGRAM, RulespecID::CONJUNCTION,
+];
+
+
 
 /// Implementation to display RulespecIDs in CLI.
 impl fmt::Display for RulespecID {
@@ -56,6 +68,7 @@ impl fmt::Display for RulespecID {
             RulespecID::NEXTBIGRAM => "Next Bigram",
             RulespecID::NEXT2TAG => "Next 2 Tags",
             RulespecID::LBIGRAM => "Left Bigram",
+            RulespecID::CONJUNCTION => "Conjunction of Conditions",
         };
         write!(f, "{}", name)
     }
@@ -87,6 +100,37 @@ pub fn map_rulespec_id(string: &str) -> Result<RulespecID, Error> {
         "NEXTBIGRAM" => Ok(RulespecID::NEXTBIGRAM),
         "LBIGRAM" => Ok(RulespecID::LBIGRAM),
         "CURWD" => Ok(RulespecID::CURWD),
+        "CONJUNCTION" => Ok(RulespecID::CONJUNCTION),
         _ => Err(Error::new(ErrorKind::InvalidData, format!("Invalid RulespecID Identifier: {}", string))),
     }
-}
\ No newline at end of file
+}
+
+
+/// Function to map a `RulespecID` back to the identifier used in Brill's contextual rule files (inverse of `map_rulespec_id`).
+pub fn rulespec_id_to_string(id: &RulespecID) -> &'static str {
+    match id {
+        RulespecID::PREVTAG => "PREVTAG",
+        RulespecID::PREVWD => "PREVWD",
+        RulespecID::PREV1OR2TAG => "PREV1OR2TAG",
+        RulespecID::PREV1OR2OR3TAG => "PREV1OR2OR3TAG",
+        RulespecID::NEXT1OR2OR3TAG => "NEXT1OR2OR3TAG",
+        RulespecID::WDAND2TAGAFT => "WDAND2TAGAFT",
+        RulespecID::WDAND2AFT => "WDAND2AFT",
+        RulespecID::PREV1OR2WD => "PREV1OR2WD",
+        RulespecID::NEXT1OR2TAG => "NEXT1OR2TAG",
+        RulespecID::NEXTTAG => "NEXTTAG",
+        RulespecID::PREV2TAG => "PREV2TAG",
+        RulespecID::NEXTWD => "NEXTWD",
+        RulespecID::WDNEXTTAG => "WDNEXTTAG",
+        RulespecID::SURROUNDTAG => "SURROUNDTAG",
+        RulespecID::WDAND2TAGBFR => "WDAND2TAGBFR",
+        RulespecID::RBIGRAM => "RBIGRAM",
+        RulespecID::PREVBIGRAM => "PREVBIGRAM",
+        RulespecID::CURWD => "CURWD",
+        RulespecID::WDPREVTAG => "WDPREVTAG",
+        RulespecID::NEXTBIGRAM => "NEXTBIGRAM",
+        RulespecID::NEXT2TAG => "NEXT2TAG",
+        RulespecID::LBIGRAM => "LBIGRAM",
+        RulespecID::CONJUNCTION => "CONJUNCTION",
+    }
+}
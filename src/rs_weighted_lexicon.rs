@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Error;
+use crate::rs_lexicon_loader::{parse_lexicon_line, LexiconLine};
+use crate::rs_multiword::format_multiword_entry;
+use crate::rs_wordclass::{wordclass_to_pos_tag, Wordclass};
+use crate::WordclassMap;
+
+// A weighted lexicon line gives each tag with the number of times the word was seen with it, most frequent first:
+//
+//     run VB:120 NN:45 VBP:3
+//
+// Plain lines (`run VB NN VBP`) may be mixed in; their tags carry no counts. `initialize_tagger` reads either kind of
+// line, ignoring the counts, so a weighted lexicon can be used wherever a plain one is.
+
+
+/// Function to split a lexicon tag token into the tag and its count, if it has one (`NN:45`). The count follows the last
+/// `:`, so the Penn `:` tag can be weighted too (`::3`).
+pub fn split_tag_count(token: &str) -> (&str, Option<u32>) {
+    match token.rsplit_once(':') {
+        Some((tag, count)) if !tag.is_empty() => match count.parse() {
+            Ok(count) => (tag, Some(count)),
+            Err(_) => (token, None),
+        },
+        _ => (token, None),
+    }
+}
+
+
+/// A lexicon with the number of times each word was seen with each of its tags.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeightedLexicon {
+    /// Each word's tags, most likely first, with their counts. Tags read from a plain lexicon line have no count.
+    pub entries: HashMap<String, Vec<(Wordclass, Option<u32>)>>,
+}
+
+impl WeightedLexicon {
+    pub fn new() -> WeightedLexicon {
+        WeightedLexicon::default()
+    }
+
+    /// Function to read a lexicon of weighted or plain lines (or both). Lines are read as `initialize_tagger` reads
+    /// them: tags with no `Wordclass` are dropped, words left with no tags are left out, and multiword expressions are
+    /// kept under their words joined by spaces, with no counts.
+    pub fn parse(contents: &str) -> WeightedLexicon {
+        let mut lexicon = WeightedLexicon::new();
+        for line in contents.lines() {
+            let LexiconLine::Entry(entry) = parse_lexicon_line(line) else { continue };
+            if entry.tags.is_empty() {
+                lexicon.entries.remove(&entry.key);
+            } else {
+                lexicon.entries.insert(entry.key, entry.tags);
+            }
+        }
+        lexicon
+    }
+
+    /// Function to read the weighted lexicon at `path`.
+    pub fn from_file(path: &str) -> Result<WeightedLexicon, Error> {
+        Ok(WeightedLexicon::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Function to build a weighted lexicon from word/tag counts, each word's tags ordered most frequent first (ties in
+    /// Penn tag order).
+    pub fn from_counts(counts: &HashMap<String, HashMap<Wordclass, u32>>) -> WeightedLexicon {
+        let entries = counts.iter().map(|(word, tag_counts)| {
+            let mut tags: Vec<(Wordclass, Option<u32>)> = tag_counts.iter().map(|(tag, count)| (tag.clone(), Some(*count))).collect();
+            tags.sort_by(|(tag_a, count_a), (tag_b, count_b)|
+                count_b.cmp(count_a).then_with(|| wordclass_to_pos_tag(tag_a).cmp(wordclass_to_pos_tag(tag_b))));
+            (word.clone(), tags)
+        }).collect();
+        WeightedLexicon { entries }
+    }
+
+    /// Function to give the number of times `word` was seen, or `None` if it is not in the lexicon or any of its tags has
+    /// no count.
+    pub fn word_count(&self, word: &str) -> Option<u32> {
+        self.entries.get(word)?.iter().map(|(_, count)| *count).sum()
+    }
+
+    /// Function to give the probability of each of `word`'s tags, most likely first. A word whose tags have no counts is
+    /// given the same probability for each tag; a word seen with counts of zero is too.
+    pub fn probabilities(&self, word: &str) -> Option<Vec<(Wordclass, f64)>> {
+        let tags = self.entries.get(word)?;
+        let total: u32 = tags.iter().map(|(_, count)| count.unwrap_or(0)).sum();
+        let counted = tags.iter().all(|(_, count)| count.is_some()) && total > 0;
+        Some(tags.iter().map(|(tag, count)| {
+            let probability = if counted { count.unwrap_or(0) as f64 / total as f64 } else { 1.0 / tags.len() as f64 };
+            (tag.clone(), probability)
+        }).collect())
+    }
+
+    /// Function to give the probability that `word` takes `tag`: 0 if the lexicon does not list the tag for it, `None`
+    /// if the word is not in the lexicon.
+    pub fn tag_probability(&self, word: &str, tag: &Wordclass) -> Option<f64> {
+        Some(self.probabilities(word)?.into_iter().find(|(candidate, _)| candidate == tag).map_or(0.0, |(_, probability)| probability))
+    }
+
+    /// Function to give the plain lexicon the tagger uses: each word's tags in the same order, without counts.
+    pub fn to_wordclass_map(&self) -> WordclassMap {
+        self.entries.iter()
+            .map(|(word, tags)| (word.clone(), tags.iter().map(|(tag, _)| tag.clone()).collect()))
+            .collect()
+    }
+}
+
+
+/// Function to write a weighted lexicon sorted by word, each tag followed by its count where it has one.
+pub fn weighted_lexicon_to_string(lexicon: &WeightedLexicon) -> String {
+    let mut words: Vec<&String> = lexicon.entries.keys().collect();
+    words.sort();
+    words.into_iter().map(|word| {
+        if word.contains(' ') {
+            let tags: Vec<Wordclass> = lexicon.entries[word].iter().map(|(tag, _)| tag.clone()).collect();
+            return format!("{}\n", format_multiword_entry(word, &tags));
+        }
+        let tags: Vec<String> = lexicon.entries[word].iter().map(|(tag, count)| match count {
+            Some(count) => format!("{}:{}", wordclass_to_pos_tag(tag), count),
+            None => wordclass_to_pos_tag(tag).to_string(),
+        }).collect();
+        format!("{} {}\n", word, tags.join(" "))
+    }).collect()
+}
+
+
+#[test]
+fn test_weighted_lexicon() {
+    use crate::initialize_tagger_from_str;
+
+    let contents = "run VB:120 NN:45 VBP:35\nthe DT\nwalk NN:0 VB:0\nbecause SCONJ:30 ADP:10\n";
+    let lexicon = WeightedLexicon::parse(contents);
+    assert_eq!(lexicon.entries["run"][1], (Wordclass::NN, Some(45)));
+    assert_eq!(lexicon.word_count("run"), Some(200));
+    assert_eq!(lexicon.word_count("the"), None);
+
+    assert_eq!(lexicon.probabilities("run").unwrap(), vec![(Wordclass::VB, 0.6), (Wordclass::NN, 0.225), (Wordclass::VBP, 0.175)]);
+    assert_eq!(lexicon.tag_probability("because", &Wordclass::SCONJ), Some(0.75));
+    assert_eq!(lexicon.tag_probability("run", &Wordclass::JJ), Some(0.0));
+    assert_eq!(lexicon.tag_probability("walk", &Wordclass::VB), Some(0.5));
+    assert_eq!(lexicon.tag_probability("the", &Wordclass::DT), Some(1.0));
+    assert_eq!(lexicon.tag_probability("sprint", &Wordclass::VB), None);
+
+    // Written out and read back, the lexicon is unchanged; the plain reader sees the same tags in the same order.
+    let written = weighted_lexicon_to_string(&lexicon);
+    assert_eq!(written.lines().next(), Some("because SCONJ:30 ADP:10"));
+    assert_eq!(WeightedLexicon::parse(&written), lexicon);
+    assert_eq!(initialize_tagger_from_str(contents), lexicon.to_wordclass_map());
+
+    // Multiword expressions and words with no known tags are read as the tagger's loader reads them.
+    let contents = "in_spite_of IN+NN+IN\nblorf XX:3\nrun VB:2 XX:1\n";
+    let lexicon = WeightedLexicon::parse(contents);
+    assert_eq!(lexicon.entries["in spite of"], vec![(Wordclass::IN, None), (Wordclass::NN, None), (Wordclass::IN, None)]);
+    assert!(!lexicon.entries.contains_key("in_spite_of"));
+    assert!(!lexicon.entries.contains_key("blorf"));
+    assert_eq!(lexicon.entries["run"], vec![(Wordclass::VB, Some(2))]);
+    assert_eq!(lexicon.to_wordclass_map(), initialize_tagger_from_str(contents));
+    assert_eq!(WeightedLexicon::parse(&weighted_lexicon_to_string(&lexicon)), lexicon);
+
+    assert_eq!(split_tag_count("::3"), (":", Some(3)));
+    assert_eq!(split_tag_count(":"), (":", None));
+    assert_eq!(split_tag_count("PRP$:7"), ("PRP$", Some(7)));
+}
@@ -61,6 +61,23 @@ pub enum Wordclass {
     WPO,    // Possessive wh-pronoun
     WRB,    // Wh-adverb
     PUNC,   // Punctuation
+    // Universal POS tags, for models trained on the UPOS column of UD treebanks. `SYM` is shared with the Penn tags.
+    ADJ,    // Adjective (UPOS)
+    ADP,    // Adposition (UPOS)
+    ADV,    // Adverb (UPOS)
+    AUX,    // Auxiliary (UPOS)
+    CCONJ,  // Coordinating conjunction (UPOS)
+    DET,    // Determiner (UPOS)
+    INTJ,   // Interjection (UPOS)
+    NOUN,   // Noun (UPOS)
+    NUM,    // Numeral (UPOS)
+    PART,   // Particle (UPOS)
+    PRON,   // Pronoun (UPOS)
+    PROPN,  // Proper noun (UPOS)
+    PUNCT,  // Punctuation (UPOS)
+    SCONJ,  // Subordinating conjunction (UPOS)
+    VERB,   // Verb (UPOS)
+    X,      // Other (UPOS)
     ANY,    // Any, used in contextual rules.
 }
 
@@ -104,6 +121,22 @@ impl fmt::Display for Wordclass {
             Wordclass::WPO => "Possessive wh-pronoun",
             Wordclass::WRB => "Wh-adverb",
             Wordclass::PUNC => "Punctuation",
+            Wordclass::ADJ => "Adjective (UPOS)",
+            Wordclass::ADP => "Adposition (UPOS)",
+            Wordclass::ADV => "Adverb (UPOS)",
+            Wordclass::AUX => "Auxiliary (UPOS)",
+            Wordclass::CCONJ => "Coordinating conjunction (UPOS)",
+            Wordclass::DET => "Determiner (UPOS)",
+            Wordclass::INTJ => "Interjection (UPOS)",
+            Wordclass::NOUN => "Noun (UPOS)",
+            Wordclass::NUM => "Numeral (UPOS)",
+            Wordclass::PART => "Particle (UPOS)",
+            Wordclass::PRON => "Pronoun (UPOS)",
+            Wordclass::PROPN => "Proper noun (UPOS)",
+            Wordclass::PUNCT => "Punctuation (UPOS)",
+            Wordclass::SCONJ => "Subordinating conjunction (UPOS)",
+            Wordclass::VERB => "Verb (UPOS)",
+            Wordclass::X => "Other (UPOS)",
             Wordclass::ANY => "Any!",
 
         };
@@ -154,7 +187,116 @@ pub fn map_pos_tag(tag: &str) -> Option<Wordclass> {
         "," => Some(Wordclass::PUNC),
         "!" => Some(Wordclass::PUNC),
         ";" => Some(Wordclass::PUNC),
+        "ADJ" => Some(Wordclass::ADJ),
+        "ADP" => Some(Wordclass::ADP),
+        "ADV" => Some(Wordclass::ADV),
+        "AUX" => Some(Wordclass::AUX),
+        "CCONJ" => Some(Wordclass::CCONJ),
+        "DET" => Some(Wordclass::DET),
+        "INTJ" => Some(Wordclass::INTJ),
+        "NOUN" => Some(Wordclass::NOUN),
+        "NUM" => Some(Wordclass::NUM),
+        "PART" => Some(Wordclass::PART),
+        "PRON" => Some(Wordclass::PRON),
+        "PROPN" => Some(Wordclass::PROPN),
+        "PUNCT" => Some(Wordclass::PUNCT),
+        "SCONJ" => Some(Wordclass::SCONJ),
+        "VERB" => Some(Wordclass::VERB),
+        "X" => Some(Wordclass::X),
         tag if tag.contains("|") => Some(Wordclass::ANY),
         _ => None,
     }
 }
+
+
+/// Function to map a `Wordclass` back to the POS tag string used in Brill's lexicon and rule files (inverse of `map_pos_tag`).
+/// `Wordclass::PUNC` collapses several punctuation tags, so it is always written as ".".
+pub fn wordclass_to_pos_tag(wordclass: &Wordclass) -> &'static str {
+    match wordclass {
+        Wordclass::CC => "CC",
+        Wordclass::CD => "CD",
+        Wordclass::DT => "DT",
+        Wordclass::EX => "EX",
+        Wordclass::FW => "FW",
+        Wordclass::IN => "IN",
+        Wordclass::JJ => "JJ",
+        Wordclass::JJR => "JJR",
+        Wordclass::JJS => "JJS",
+        Wordclass::LS => "LS",
+        Wordclass::MD => "MD",
+        Wordclass::NN => "NN",
+        Wordclass::NNS => "NNS",
+        Wordclass::NNP => "NNP",
+        Wordclass::NNPS => "NNPS",
+        Wordclass::PDT => "PDT",
+        Wordclass::POS => "POS",
+        Wordclass::PRPE => "PRP",
+        Wordclass::PRPO => "PRP$",
+        Wordclass::RB => "RB",
+        Wordclass::RBR => "RBR",
+        Wordclass::RBS => "RBS",
+        Wordclass::RP => "RP",
+        Wordclass::SYM => "SYM",
+        Wordclass::TO => "TO",
+        Wordclass::UH => "UH",
+        Wordclass::VB => "VB",
+        Wordclass::VBD => "VBD",
+        Wordclass::VBG => "VBG",
+        Wordclass::VBN => "VBN",
+        Wordclass::VBP => "VBP",
+        Wordclass::VBZ => "VBZ",
+        Wordclass::WDT => "WDT",
+        Wordclass::WPR => "WP",
+        Wordclass::WPO => "WP$",
+        Wordclass::WRB => "WRB",
+        Wordclass::PUNC => ".",
+        Wordclass::ADJ => "ADJ",
+        Wordclass::ADP => "ADP",
+        Wordclass::ADV => "ADV",
+        Wordclass::AUX => "AUX",
+        Wordclass::CCONJ => "CCONJ",
+        Wordclass::DET => "DET",
+        Wordclass::INTJ => "INTJ",
+        Wordclass::NOUN => "NOUN",
+        Wordclass::NUM => "NUM",
+        Wordclass::PART => "PART",
+        Wordclass::PRON => "PRON",
+        Wordclass::PROPN => "PROPN",
+        Wordclass::PUNCT => "PUNCT",
+        Wordclass::SCONJ => "SCONJ",
+        Wordclass::VERB => "VERB",
+        Wordclass::X => "X",
+        Wordclass::ANY => "|",
+    }
+}
+
+
+/// Function to check whether `wordclass` is a Universal POS tag rather than a Penn tag. `SYM`, which both tag sets share,
+/// counts as a Penn tag.
+pub fn is_universal_tag(wordclass: &Wordclass) -> bool {
+    matches!(wordclass, Wordclass::ADJ | Wordclass::ADP | Wordclass::ADV | Wordclass::AUX | Wordclass::CCONJ | Wordclass::DET
+        | Wordclass::INTJ | Wordclass::NOUN | Wordclass::NUM | Wordclass::PART | Wordclass::PRON | Wordclass::PROPN
+        | Wordclass::PUNCT | Wordclass::SCONJ | Wordclass::VERB | Wordclass::X)
+}
+
+
+/// Test that every `Wordclass` written by `wordclass_to_pos_tag` maps back to itself through `map_pos_tag`.
+#[test]
+fn test_wordclass_to_pos_tag_round_trip() {
+    for tag in ["CC", "CD", "DT", "NN", "NNS", "PRP", "PRP$", "VBZ", "WP", "WP$", "WRB", "."] {
+        let wordclass = map_pos_tag(tag).unwrap();
+        assert_eq!(wordclass_to_pos_tag(&wordclass), tag);
+    }
+    assert_eq!(map_pos_tag(wordclass_to_pos_tag(&Wordclass::ANY)), Some(Wordclass::ANY));
+}
+
+
+#[test]
+fn test_universal_tags_round_trip() {
+    for tag in ["ADJ", "ADP", "ADV", "AUX", "CCONJ", "DET", "INTJ", "NOUN", "NUM", "PART", "PRON", "PROPN", "PUNCT", "SCONJ", "SYM", "VERB", "X"] {
+        let wordclass = map_pos_tag(tag).unwrap();
+        assert_eq!(wordclass_to_pos_tag(&wordclass), tag);
+        assert_eq!(is_universal_tag(&wordclass), tag != "SYM");
+    }
+    assert!(!is_universal_tag(&Wordclass::NN));
+}